@@ -110,6 +110,11 @@ class TauriIpcRenderer implements IpcRenderer {
       }
 
       default:
+        // Backend commands, e.g. `record_command_invocation`, take their
+        // arguments as one object
+        if (/^[a-z0-9_]+$/.test(channel)) {
+          return window.__TAURI__!.core.invoke(channel, args[0] as Record<string, unknown> | undefined)
+        }
         console.warn('Unhandled Tauri invoke channel:', channel)
         return null
     }
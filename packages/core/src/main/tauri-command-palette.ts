@@ -31,8 +31,15 @@ export interface CommandStats {
   hit_count: number
   last_used: string
   avg_execution_time?: number
+  /** Uses weighted by recency: each counts 1 when new, half that one half-life later */
+  frecency?: number
 }
 
+/**
+ * Order of top commands and resources: most uses, or highest frecency
+ */
+export type RankBy = 'count' | 'frecency'
+
 /**
  * Recent query entry
  */
@@ -52,6 +59,8 @@ export interface ResourceSummary {
   context?: string
   last_accessed: string
   access_count: number
+  /** Accesses weighted by recency */
+  frecency?: number
 }
 
 /**
@@ -86,6 +95,107 @@ export interface CommandHistory {
   success: boolean
 }
 
+/**
+ * Where a command ran
+ */
+export interface CommandScope {
+  /** Kube context */
+  context?: string
+  namespace?: string
+  /** Kind of the resource last looked at */
+  resource_kind?: string
+}
+
+/**
+ * Evidence behind a predicted command
+ */
+export interface PredictionSupport {
+  /** Commands the predicted one followed, oldest first; empty for how often it is run at all */
+  after: string[]
+  /** Scope the history is from; unset where it isn't restricted */
+  context?: string
+  namespace?: string
+  resource_kind?: string
+  /** Times the command followed in this scope, of `total` */
+  count: number
+  total: number
+  last_seen: string
+  /** Share of the probability coming from this evidence */
+  weight: number
+}
+
+/**
+ * Predicted next command
+ */
+export interface CommandPrediction {
+  command_id: string
+  probability: number
+  support: PredictionSupport[]
+}
+
+/**
+ * Filter of command history searches
+ */
+export interface HistorySearchFilter {
+  success?: boolean
+  context?: string
+  /** RFC 3339 timestamps, inclusive */
+  since?: string
+  until?: string
+  limit?: number
+}
+
+/**
+ * Span of a search result field matching the query, in characters
+ */
+export interface MatchHighlight {
+  field: 'command_id' | 'arguments' | 'context'
+  start: number
+  end: number
+}
+
+/**
+ * Command history search result
+ */
+export interface HistoryMatch {
+  id: number
+  command_id: string
+  arguments?: string
+  context?: string
+  timestamp: string
+  execution_time_ms?: number
+  success: boolean
+  score: number
+  highlights: MatchHighlight[]
+}
+
+/**
+ * State of the backend database
+ */
+export interface DatabaseStatus {
+  schema_version: number
+  latest_version: number
+  backup_path?: string
+  error?: string
+}
+
+/**
+ * Get the state of the backend database
+ *
+ * If the database could not be opened or migrated, `error` says why and
+ * every other call in this module fails until the app is restarted.
+ *
+ * @returns Database status, or undefined in non-Tauri runtimes
+ */
+export async function getDatabaseStatus(): Promise<DatabaseStatus | undefined> {
+  if (!isTauriRuntime()) {
+    return undefined
+  }
+
+  const ipc = getIpcRenderer()
+  return (await ipc.invoke('get_command_palette_db_status')) as DatabaseStatus
+}
+
 /**
  * Record a command invocation in the database
  *
@@ -93,14 +203,17 @@ export interface CommandHistory {
  * @param executionTimeMs - Optional execution time in milliseconds
  * @param success - Whether the command executed successfully
  * @param errorMessage - Optional error message if command failed
- * @param context - Optional context information (JSON string)
+ * @param args - Optional command arguments, indexed for history search
+ * @param scope - Optional context, namespace and resource kind the command left the user in; context
+ *   and namespace default to the kubeconfig's current ones
  */
 export async function recordCommandInvocation(
   commandId: string,
   executionTimeMs?: number,
   success = true,
   errorMessage?: string,
-  context?: string
+  args?: string,
+  scope?: CommandScope
 ): Promise<void> {
   if (!isTauriRuntime()) {
     // Fallback to localStorage for non-Tauri runtimes
@@ -125,7 +238,8 @@ export async function recordCommandInvocation(
     execution_time_ms: executionTimeMs,
     success,
     error_message: errorMessage,
-    context
+    arguments: args,
+    scope
   })
 }
 
@@ -167,16 +281,45 @@ export async function getCommandStats(commandId?: string): Promise<CommandStats[
  * Get top N most frequently used commands
  *
  * @param limit - Maximum number of commands to return
+ * @param rankBy - Order by use count (default) or frecency
  * @returns Array of top command statistics
  */
-export async function getTopCommands(limit = 10): Promise<CommandStats[]> {
+export async function getTopCommands(limit = 10, rankBy: RankBy = 'count'): Promise<CommandStats[]> {
   if (!isTauriRuntime()) {
     const stats = await getCommandStats()
     return stats.slice(0, limit)
   }
 
   const ipc = getIpcRenderer()
-  return (await ipc.invoke('get_top_commands', { limit })) as CommandStats[]
+  return (await ipc.invoke('get_top_commands', { limit, rank_by: rankBy })) as CommandStats[]
+}
+
+/**
+ * Get the frecency half-life
+ *
+ * @returns Half-life in hours
+ */
+export async function getFrecencyHalfLife(): Promise<number | undefined> {
+  if (!isTauriRuntime()) {
+    return undefined
+  }
+
+  const ipc = getIpcRenderer()
+  return (await ipc.invoke('get_frecency_half_life')) as number
+}
+
+/**
+ * Set the frecency half-life, re-ranking all commands and resources
+ *
+ * @param hours - Time after which a use counts half
+ */
+export async function setFrecencyHalfLife(hours: number): Promise<void> {
+  if (!isTauriRuntime()) {
+    return
+  }
+
+  const ipc = getIpcRenderer()
+  await ipc.invoke('set_frecency_half_life', { hours })
 }
 
 /**
@@ -371,11 +514,13 @@ export async function getRecentResources(
  *
  * @param limit - Maximum number of resources to return
  * @param kindFilter - Optional filter by resource kind
- * @returns Array of top resources sorted by access count
+ * @param rankBy - Order by access count (default) or frecency
+ * @returns Array of top resources
  */
 export async function getTopResources(
   limit = 20,
-  kindFilter?: string
+  kindFilter?: string,
+  rankBy: RankBy = 'count'
 ): Promise<ResourceSummary[]> {
   if (!isTauriRuntime()) {
     // Fallback to localStorage for non-Tauri runtimes
@@ -388,7 +533,8 @@ export async function getTopResources(
   const ipc = getIpcRenderer()
   return (await ipc.invoke('get_top_resources', {
     limit,
-    kind_filter: kindFilter
+    kind_filter: kindFilter,
+    rank_by: rankBy
   })) as ResourceSummary[]
 }
 
@@ -494,3 +640,61 @@ export async function getCommandHistory(
     limit
   })) as CommandHistory[]
 }
+
+/**
+ * Search command history
+ *
+ * Every whitespace-separated term of the query must occur in the command
+ * id, arguments or context. Results are ranked by relevance, or newest
+ * first when all terms are shorter than three characters.
+ *
+ * @param query - Search terms
+ * @param filter - Optional success, context and time range filter
+ * @returns Matching history entries with highlighted spans
+ */
+export async function searchCommandHistory(
+  query: string,
+  filter?: HistorySearchFilter
+): Promise<HistoryMatch[]> {
+  if (!isTauriRuntime()) {
+    // History not available in browser mode
+    return []
+  }
+
+  const ipc = getIpcRenderer()
+  return (await ipc.invoke('search_command_history', {
+    query,
+    filter
+  })) as HistoryMatch[]
+}
+
+/**
+ * Predict the next command
+ *
+ * Learns from successful command history which commands follow the last
+ * few ones, preferring history in the same context, namespace and resource
+ * kind. Each prediction lists the evidence it is based on.
+ *
+ * @param lastCommands - Commands just run, oldest first; those of the current session if omitted
+ * @param scope - Context, namespace and resource kind to predict for; context and namespace default to
+ *   the kubeconfig's current ones
+ * @param limit - Maximum number of predictions (default: 5)
+ * @returns Predicted commands, most probable first
+ */
+export async function predictNextCommands(
+  lastCommands?: string[],
+  scope?: CommandScope,
+  limit = 5
+): Promise<CommandPrediction[]> {
+  if (!isTauriRuntime()) {
+    // History not available in browser mode
+    return []
+  }
+
+  const ipc = getIpcRenderer()
+  return (await ipc.invoke('predict_next_commands', {
+    last_commands: lastCommands,
+    scope,
+    limit
+  })) as CommandPrediction[]
+}
@@ -0,0 +1,68 @@
+/*
+ * Copyright 2025 The Kubernetes Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ * http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+/**
+ * Tauri Secret Handling
+ *
+ * Provides TypeScript bindings for the backend's credential masking: output
+ * shows credentials as `[REDACTED:<kind>:<id>]` markers, clipboard copies
+ * are masked the same way, and a marker can be revealed explicitly, which
+ * the backend records in its audit log.
+ */
+
+import { getIpcRenderer, isTauriRuntime } from './tauri-bridge'
+
+/**
+ * What a masked value was
+ */
+export type SecretKind = 'secret-data' | 'token' | 'private-key' | 'credential'
+
+/**
+ * A value behind a redaction marker
+ */
+export interface RevealedSecret {
+  kind: SecretKind
+  value: string
+}
+
+/**
+ * Copy text to the clipboard, with credentials masked in Tauri runtimes
+ *
+ * @param text - Text to copy
+ */
+export async function copyToClipboard(text: string): Promise<void> {
+  if (!isTauriRuntime()) {
+    return navigator.clipboard.writeText(text)
+  }
+
+  const ipc = getIpcRenderer()
+  await ipc.invoke('copy_to_clipboard', { text })
+}
+
+/**
+ * Reveal the value behind a redaction marker; the reveal is audited
+ *
+ * @param marker - A marker as shown in output, e.g. `[REDACTED:token:3]`
+ * @returns The unmasked value
+ */
+export async function revealSecret(marker: string): Promise<RevealedSecret> {
+  if (!isTauriRuntime()) {
+    throw new Error('Revealing secrets requires the Tauri runtime')
+  }
+
+  const ipc = getIpcRenderer()
+  return (await ipc.invoke('reveal_secret', { marker })) as RevealedSecret
+}
@@ -391,6 +391,7 @@ export class Kui extends React.PureComponent<Props, State> {
         category: CommandCategory.Kubectl,
         icon: '📦',
         keyBinding: 'Cmd+Shift+P',
+        args: 'kubectl get pods',
         action: () => {
           console.log('Execute: kubectl get pods')
         }
@@ -401,6 +402,7 @@ export class Kui extends React.PureComponent<Props, State> {
         description: 'List all deployments in the current namespace',
         category: CommandCategory.Workloads,
         resourceType: 'deployment',
+        args: 'kubectl get deployments',
         action: () => {
           console.log('Execute: kubectl get deployments')
         }
@@ -20,7 +20,8 @@ import type { Command } from './commands'
 import { getCategoryColor, getCategoryIcon } from './commands'
 import {
   recordCommandInvocation,
-  getAllCommandHitCounts
+  getAllCommandHitCounts,
+  type CommandScope
 } from '@kui-shell/core/src/main/tauri-command-palette'
 import { getCommandSuggestions, type AISuggestion } from './ai-suggestions'
 import { FuzzyHighlight } from './fuzzy-highlight'
@@ -36,6 +37,23 @@ interface EnhancedCommand extends Command {
   }
 }
 
+/**
+ * Kind of the resource the last command was on, kept across palette openings
+ */
+let lastResourceKind: string | undefined
+
+/**
+ * Scope a command leaves the user in, for history and predictions
+ *
+ * The backend fills in the current kube context and namespace.
+ */
+function scopeAfter(command: Command): CommandScope {
+  if (command.resourceType) {
+    lastResourceKind = command.resourceType
+  }
+  return { resource_kind: lastResourceKind }
+}
+
 /**
  * Command Palette Delegate
  * Implements PickerDelegate for the command palette.
@@ -132,6 +150,8 @@ export class CommandPaletteDelegate implements PickerDelegate<EnhancedCommand> {
     }
 
     // Execute command and track execution time
+    const { args } = command
+    const scope = scopeAfter(command)
     const startTime = Date.now()
     try {
       const result = command.action()
@@ -139,7 +159,7 @@ export class CommandPaletteDelegate implements PickerDelegate<EnhancedCommand> {
         result
           .then(() => {
             const executionTime = Date.now() - startTime
-            recordCommandInvocation(command.id, executionTime, true).catch(err =>
+            recordCommandInvocation(command.id, executionTime, true, undefined, args, scope).catch(err =>
               console.warn('Failed to record command invocation:', err)
             )
           })
@@ -150,20 +170,22 @@ export class CommandPaletteDelegate implements PickerDelegate<EnhancedCommand> {
               command.id,
               executionTime,
               false,
-              err.message || String(err)
+              err.message || String(err),
+              args,
+              scope
             ).catch(e => console.warn('Failed to record command invocation:', e))
           })
       } else {
         const executionTime = Date.now() - startTime
-        recordCommandInvocation(command.id, executionTime, true).catch(err =>
+        recordCommandInvocation(command.id, executionTime, true, undefined, args, scope).catch(err =>
           console.warn('Failed to record command invocation:', err)
         )
       }
     } catch (err: any) {
       const executionTime = Date.now() - startTime
       console.error('Command execution failed:', err)
-      recordCommandInvocation(command.id, executionTime, false, err.message || String(err)).catch(
-        e => console.warn('Failed to record command invocation:', e)
+      recordCommandInvocation(command.id, executionTime, false, err.message || String(err), args, scope).catch(e =>
+        console.warn('Failed to record command invocation:', e)
       )
     }
   }
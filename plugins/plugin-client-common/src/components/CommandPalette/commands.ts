@@ -44,6 +44,7 @@ export interface Command {
   icon?: string
   keyBinding?: string
   resourceType?: string  // e.g., 'pod', 'deployment', 'service'
+  args?: string  // command line it runs, e.g., 'kubectl get pods'; recorded for history search
   action: () => void | Promise<void>
 }
 
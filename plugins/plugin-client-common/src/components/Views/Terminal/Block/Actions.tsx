@@ -18,6 +18,7 @@ import React from 'react'
 
 import { Tab } from '@kui-shell/core/mdist/api/Tab'
 import { i18n } from '@kui-shell/core/mdist/api/i18n'
+import { copyToClipboard } from '@kui-shell/core/src/main/tauri-secrets'
 
 import { InputOptions } from './Input'
 import { SupportedIcon } from '../../../spi/Icons'
@@ -91,7 +92,7 @@ export default class Actions extends React.PureComponent<Props> {
       this.props.command && (
         <Action
           icon="Copy"
-          onClick={() => navigator.clipboard.writeText(this.props.command)}
+          onClick={() => copyToClipboard(this.props.command)}
           title="Copy the command line to the clipboard"
         />
       )
@@ -18,6 +18,7 @@ import { v4 } from 'uuid'
 
 import { getTabId } from '@kui-shell/core/mdist/api/Tab'
 import { eventBus } from '@kui-shell/core/mdist/api/Events'
+import { copyToClipboard } from '@kui-shell/core/src/main/tauri-secrets'
 import type { CommandCompleteEvent, ScalarResponse } from '@kui-shell/core'
 
 import ScrollableTerminal, { getSelectionText } from './ScrollableTerminal'
@@ -157,7 +158,7 @@ export function onCopy(this: ScrollableTerminal, evt: ClipboardEvent, onSuccess?
               blocks
             }
 
-            navigator.clipboard.writeText(JSON.stringify(transfer))
+            await copyToClipboard(JSON.stringify(transfer))
 
             if (typeof onSuccess === 'function') {
               onSuccess(target)
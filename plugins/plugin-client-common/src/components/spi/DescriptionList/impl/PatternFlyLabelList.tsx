@@ -17,6 +17,7 @@
 import React from 'react'
 import { Label } from '@patternfly/react-core/dist/esm/components/Label'
 import { LabelGroup } from '@patternfly/react-core/dist/esm/components/LabelGroup'
+import { copyToClipboard } from '@kui-shell/core/src/main/tauri-secrets'
 
 import BaseProps from '../model'
 
@@ -46,7 +47,7 @@ export default class PatternFlyDescriptionList extends React.PureComponent<Props
   private readonly _onClick = (evt: React.MouseEvent<HTMLSpanElement>) => {
     const key = evt.currentTarget.getAttribute('data-term')
     const value = evt.currentTarget.getAttribute('data-description')
-    navigator.clipboard.writeText(`${key}=${value}`)
+    copyToClipboard(`${key}=${value}`)
   }
 
   public render() {
@@ -0,0 +1,43 @@
+/*
+ * Copyright 2019 The Kubernetes Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ * http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+import type { Registrar } from '@kui-shell/core'
+
+/**
+ * The command usage model
+ *
+ */
+const usage = {
+  command: 'reveal',
+  strict: 'reveal',
+  required: [{ name: 'marker', docs: 'A masked value as shown in output, e.g. [REDACTED:token:3]' }]
+}
+
+/**
+ * The command handlers. Every reveal is recorded in the audit log.
+ *
+ */
+export default (commandTree: Registrar) => {
+  commandTree.listen(
+    '/reveal',
+    async ({ argvNoOptions }) => {
+      const { revealSecret } = await import('@kui-shell/core/src/main/tauri-secrets')
+      const { value } = await revealSecret(argvNoOptions[1])
+      return value
+    },
+    { usage }
+  )
+}
@@ -27,6 +27,7 @@ import mutable from './lib/cmds/toggle-editability'
 import prompt from './lib/cmds/prompt'
 import replay from './lib/cmds/replay'
 import sleep from './lib/cmds/sleep'
+import reveal from './lib/cmds/reveal'
 import history from './lib/cmds/history/history'
 import kuiConfig from './lib/cmds/kui-config'
 import tabManagement from './lib/cmds/tab-management'
@@ -50,6 +51,7 @@ export default async (commandTree: Registrar) => {
     prompt(commandTree),
     replay(commandTree),
     sleep(commandTree),
+    reveal(commandTree),
     history(commandTree),
     kuiConfig(commandTree),
     tabManagement(commandTree)
@@ -12,13 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use chrono::Utc;
-use log::{debug, info};
-use rusqlite::{params, Connection, Result as SqlResult};
+use crate::k8s::{kubeconfig, KubeClients};
+use crate::prediction::{self, CommandPrediction, HistoryStep, PredictionModel};
+use crate::redact::mask_text;
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{debug, error, info};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Result as SqlResult};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::sync::Mutex;
-use tauri::{AppHandle, Manager};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::oneshot;
 
 /// Command statistics for display
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +36,20 @@ pub struct CommandStats {
     pub hit_count: i64,
     pub last_used: String,
     pub avg_execution_time: Option<f64>,
+    /// Uses weighted by recency: each counts 1 when new, half that one
+    /// half-life later
+    pub frecency: Option<f64>,
+}
+
+/// Order of top commands and resources
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RankBy {
+    /// Most uses first
+    #[default]
+    Count,
+    /// Highest frecency first
+    Frecency,
 }
 
 /// Recent query entry
@@ -58,6 +81,8 @@ pub struct ResourceSummary {
     pub context: Option<String>,
     pub last_accessed: String,
     pub access_count: i64,
+    /// Accesses weighted by recency, as for `CommandStats::frecency`
+    pub frecency: Option<f64>,
 }
 
 /// Command pattern for smart history
@@ -89,46 +114,240 @@ pub struct CommandHistory {
     pub success: bool,
 }
 
-/// Command palette database manager
-pub struct CommandPaletteDb {
-    conn: Mutex<Connection>,
+/// Where a command ran
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandScope {
+    /// Kube context
+    pub context: Option<String>,
+    pub namespace: Option<String>,
+    /// Kind of the resource last looked at
+    pub resource_kind: Option<String>,
 }
 
-impl CommandPaletteDb {
-    /// Create a new database connection
-    pub fn new(app: &AppHandle) -> SqlResult<Self> {
-        let db_path = Self::get_db_path(app)?;
-        info!("Opening command palette database at: {:?}", db_path);
-
-        let conn = Connection::open(&db_path)?;
-        let db = CommandPaletteDb {
-            conn: Mutex::new(conn),
+impl CommandScope {
+    /// Fill in the context and namespace the renderer left out with the
+    /// kubeconfig's current ones
+    fn or_current(mut self) -> Self {
+        if self.context.is_some() && self.namespace.is_some() {
+            return self;
+        }
+        let Ok(config) = kubeconfig::load() else {
+            return self;
         };
 
-        db.initialize()?;
-        Ok(db)
+        let context = self.context.take().or(config.current_context);
+        if self.namespace.is_none() {
+            self.namespace = config
+                .contexts
+                .iter()
+                .find(|named| Some(&named.name) == context.as_ref())
+                .map(|named| {
+                    named
+                        .context
+                        .as_ref()
+                        .and_then(|c| c.namespace.clone())
+                        .unwrap_or_else(|| "default".to_string())
+                });
+        }
+        self.context = context;
+        self
     }
+}
 
-    /// Get the database file path
-    fn get_db_path(app: &AppHandle) -> SqlResult<PathBuf> {
-        let app_data_dir = app.path().app_data_dir().map_err(|e| {
-            rusqlite::Error::InvalidPath(format!("Failed to get app data dir: {}", e).into())
-        })?;
+/// Filter of command history searches
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HistorySearchFilter {
+    pub success: Option<bool>,
+    pub context: Option<String>,
+    /// RFC 3339 timestamps, inclusive
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+}
 
-        std::fs::create_dir_all(&app_data_dir).map_err(|e| {
-            rusqlite::Error::InvalidPath(format!("Failed to create app data dir: {}", e).into())
-        })?;
+/// Command history search result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryMatch {
+    pub id: i64,
+    pub command_id: String,
+    pub arguments: Option<String>,
+    pub context: Option<String>,
+    pub timestamp: String,
+    pub execution_time_ms: Option<i64>,
+    pub success: bool,
+    /// Relevance, higher is better; 0 when the query had no indexed terms
+    pub score: f64,
+    pub highlights: Vec<MatchHighlight>,
+}
 
-        Ok(app_data_dir.join("command-palette.db"))
+/// Span of a search result field matching the query, in characters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchHighlight {
+    /// `command_id`, `arguments` or `context`
+    pub field: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Port-forward registry entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPortForward {
+    pub id: i64,
+    pub context: Option<String>,
+    pub namespace: String,
+    /// `pod` or `service`
+    pub target_kind: String,
+    pub target_name: String,
+    pub address: String,
+    pub local_port: u16,
+    pub remote_port: u16,
+    /// Whether the forward is started again when the app restarts
+    pub restore: bool,
+    pub created_at: String,
+}
+
+/// An entry of the audit log of mutating cluster operations
+///
+/// Entries form a hash chain: `hash` is the hex SHA-256 of the JSON array
+/// `[timestamp, operation, context, namespace, kind, name, user, outcome,
+/// detail, prev_hash]`, and `prev_hash` is the `hash` of the entry before
+/// (64 zeros for the first). Editing or removing an entry breaks the chain
+/// from there on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: String,
+    /// `apply`, `delete`, `exec`, `port-forward`, `stop-port-forward`
+    pub operation: String,
+    pub context: Option<String>,
+    pub namespace: Option<String>,
+    pub kind: Option<String>,
+    pub name: Option<String>,
+    /// Kubeconfig user of the context
+    pub user: Option<String>,
+    /// `success`, `failure` or `dry-run`
+    pub outcome: String,
+    /// Error message or command, credentials masked
+    pub detail: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Hash of the entry's content chained to `prev_hash`
+    pub fn compute_hash(&self) -> String {
+        let content = serde_json::json!([
+            self.timestamp,
+            self.operation,
+            self.context,
+            self.namespace,
+            self.kind,
+            self.name,
+            self.user,
+            self.outcome,
+            self.detail,
+            self.prev_hash,
+        ]);
+        format!("{:x}", Sha256::digest(content.to_string().as_bytes()))
     }
+}
+
+/// Filter of audit log queries
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditFilter {
+    pub context: Option<String>,
+    pub operation: Option<String>,
+    /// RFC 3339 timestamps, inclusive
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Result of checking the audit log's hash chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditVerification {
+    pub entries: i64,
+    pub valid: bool,
+    /// First entry whose hash or link doesn't match
+    pub first_invalid_id: Option<i64>,
+}
+
+/// `prev_hash` of the first audit entry
+pub const AUDIT_GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Kubernetes event as stored in the event timeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterEvent {
+    /// UID of the Event object
+    pub uid: String,
+    pub namespace: Option<String>,
+    /// Kind of the object the event is about
+    pub kind: String,
+    pub name: String,
+    pub event_type: String,
+    pub reason: String,
+    pub note: String,
+    pub reporting_controller: Option<String>,
+    /// Occurrences folded into the Event object by the cluster
+    pub count: i64,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// Repeated events of one object, rolled up into one entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventRollup {
+    pub namespace: Option<String>,
+    pub kind: String,
+    pub name: String,
+    pub event_type: String,
+    pub reason: String,
+    pub note: String,
+    pub count: i64,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// Events sharing a reason
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventReasonGroup {
+    pub reason: String,
+    pub event_type: String,
+    pub count: i64,
+    /// Number of distinct objects with events of this reason
+    pub object_count: i64,
+    pub last_seen: String,
+}
+
+/// How long a statement waits on a lock held by the other connection
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
-    /// Initialize database schema
-    fn initialize(&self) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
+/// Prepared statements kept per connection; enough for every query here
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+/// A schema change, applied once to databases older than its version
+struct Migration {
+    /// `PRAGMA user_version` of the database once applied
+    version: i64,
+    description: &'static str,
+    sql: &'static str,
+    /// Data changes SQL alone can't make, run after `sql`
+    backfill: Option<fn(&Connection) -> SqlResult<()>>,
+}
 
-        // Command invocations table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS command_invocations (
+/// Schema migrations, oldest first
+///
+/// Append new migrations here; never edit one that has shipped. The first
+/// one only creates what's missing, so databases from before versioning,
+/// which are at version 0, take it as a no-op.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: "
+            -- Command invocations table
+            CREATE TABLE IF NOT EXISTS command_invocations (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 command_id TEXT NOT NULL,
                 timestamp TEXT NOT NULL,
@@ -136,24 +355,18 @@ impl CommandPaletteDb {
                 success BOOLEAN NOT NULL DEFAULT 1,
                 error_message TEXT,
                 context TEXT
-            )",
-            [],
-        )?;
+            );
 
-        // Recent queries table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS recent_queries (
+            -- Recent queries table
+            CREATE TABLE IF NOT EXISTS recent_queries (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 query TEXT NOT NULL,
                 timestamp TEXT NOT NULL,
                 result_count INTEGER NOT NULL DEFAULT 0
-            )",
-            [],
-        )?;
+            );
 
-        // Recent resources table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS recent_resources (
+            -- Recent resources table
+            CREATE TABLE IF NOT EXISTS recent_resources (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 kind TEXT NOT NULL,
                 name TEXT NOT NULL,
@@ -162,13 +375,10 @@ impl CommandPaletteDb {
                 timestamp TEXT NOT NULL,
                 access_count INTEGER NOT NULL DEFAULT 1,
                 UNIQUE(kind, name, namespace, context)
-            )",
-            [],
-        )?;
+            );
 
-        // Command patterns table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS command_patterns (
+            -- Command patterns table
+            CREATE TABLE IF NOT EXISTS command_patterns (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 pattern_id TEXT UNIQUE NOT NULL,
                 command_sequence TEXT NOT NULL,
@@ -176,124 +386,478 @@ impl CommandPaletteDb {
                 confidence REAL NOT NULL,
                 last_seen TEXT NOT NULL,
                 avg_time_between_commands REAL
-            )",
-            [],
-        )?;
+            );
 
-        // Indexes for performance
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_command_id
-             ON command_invocations(command_id)",
-            [],
-        )?;
+            -- Port-forward registry
+            CREATE TABLE IF NOT EXISTS port_forwards (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                context TEXT,
+                namespace TEXT NOT NULL,
+                target_kind TEXT NOT NULL,
+                target_name TEXT NOT NULL,
+                address TEXT NOT NULL,
+                local_port INTEGER NOT NULL,
+                remote_port INTEGER NOT NULL,
+                restore BOOLEAN NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            );
+
+            -- Kubernetes event timeline, one row per Event object
+            CREATE TABLE IF NOT EXISTS cluster_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                context TEXT NOT NULL,
+                uid TEXT NOT NULL,
+                namespace TEXT,
+                kind TEXT NOT NULL,
+                name TEXT NOT NULL,
+                event_type TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                note TEXT NOT NULL,
+                reporting_controller TEXT,
+                count INTEGER NOT NULL DEFAULT 1,
+                first_seen TEXT NOT NULL,
+                last_seen TEXT NOT NULL,
+                UNIQUE(context, uid)
+            );
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_timestamp
-             ON command_invocations(timestamp DESC)",
-            [],
-        )?;
+            -- Reveals of masked credentials, for auditing
+            CREATE TABLE IF NOT EXISTS secret_reveals (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                secret_id INTEGER NOT NULL,
+                secret_kind TEXT NOT NULL,
+                window_label TEXT NOT NULL
+            );
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_recent_queries_timestamp
-             ON recent_queries(timestamp DESC)",
-            [],
-        )?;
+            -- Audit log of mutating cluster operations, append-only
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                operation TEXT NOT NULL,
+                context TEXT,
+                namespace TEXT,
+                kind TEXT,
+                name TEXT,
+                user TEXT,
+                outcome TEXT NOT NULL,
+                detail TEXT,
+                prev_hash TEXT NOT NULL,
+                hash TEXT NOT NULL
+            );
+
+            CREATE TRIGGER IF NOT EXISTS audit_log_no_update
+                BEFORE UPDATE ON audit_log
+                BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;
+
+            CREATE TRIGGER IF NOT EXISTS audit_log_no_delete
+                BEFORE DELETE ON audit_log
+                BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;
+
+            -- Indexes for performance
+            CREATE INDEX IF NOT EXISTS idx_command_id
+                ON command_invocations(command_id);
+
+            CREATE INDEX IF NOT EXISTS idx_timestamp
+                ON command_invocations(timestamp DESC);
+
+            CREATE INDEX IF NOT EXISTS idx_recent_queries_timestamp
+                ON recent_queries(timestamp DESC);
+
+            CREATE INDEX IF NOT EXISTS idx_recent_resources_timestamp
+                ON recent_resources(timestamp DESC);
+
+            CREATE INDEX IF NOT EXISTS idx_recent_resources_kind
+                ON recent_resources(kind);
+
+            CREATE INDEX IF NOT EXISTS idx_audit_log_timestamp
+                ON audit_log(timestamp);
+
+            CREATE INDEX IF NOT EXISTS idx_cluster_events_last_seen
+                ON cluster_events(context, last_seen DESC);
+
+            CREATE INDEX IF NOT EXISTS idx_cluster_events_object
+                ON cluster_events(context, kind, name, namespace);
+
+            CREATE INDEX IF NOT EXISTS idx_patterns_confidence
+                ON command_patterns(confidence DESC);
+
+            CREATE INDEX IF NOT EXISTS idx_patterns_last_seen
+                ON command_patterns(last_seen DESC);
+        ",
+        backfill: None,
+    },
+    Migration {
+        version: 2,
+        description: "full-text index of command history",
+        sql: "
+            ALTER TABLE command_invocations ADD COLUMN arguments TEXT;
+
+            -- Trigram index over the history, so that any substring of three or
+            -- more characters is an indexed lookup
+            CREATE VIRTUAL TABLE command_history_fts USING fts5(
+                command_id,
+                arguments,
+                context,
+                content = 'command_invocations',
+                content_rowid = 'id',
+                tokenize = 'trigram'
+            );
+
+            CREATE TRIGGER command_history_fts_insert
+            AFTER INSERT ON command_invocations
+            BEGIN
+                INSERT INTO command_history_fts (rowid, command_id, arguments, context)
+                VALUES (new.id, new.command_id, new.arguments, new.context);
+            END;
+
+            CREATE TRIGGER command_history_fts_delete
+            AFTER DELETE ON command_invocations
+            BEGIN
+                INSERT INTO command_history_fts
+                (command_history_fts, rowid, command_id, arguments, context)
+                VALUES ('delete', old.id, old.command_id, old.arguments, old.context);
+            END;
+
+            CREATE TRIGGER command_history_fts_update
+            AFTER UPDATE ON command_invocations
+            BEGIN
+                INSERT INTO command_history_fts
+                (command_history_fts, rowid, command_id, arguments, context)
+                VALUES ('delete', old.id, old.command_id, old.arguments, old.context);
+                INSERT INTO command_history_fts (rowid, command_id, arguments, context)
+                VALUES (new.id, new.command_id, new.arguments, new.context);
+            END;
+
+            INSERT INTO command_history_fts (command_history_fts) VALUES ('rebuild');
+        ",
+        backfill: None,
+    },
+    Migration {
+        version: 3,
+        description: "frecency ranking",
+        sql: "
+            -- Frecency rank keys, see `frecency_add`
+            CREATE TABLE command_frecency (
+                command_id TEXT PRIMARY KEY,
+                frecency REAL NOT NULL
+            );
+
+            CREATE INDEX idx_command_frecency
+                ON command_frecency(frecency DESC);
+
+            ALTER TABLE recent_resources ADD COLUMN frecency REAL NOT NULL DEFAULT 0;
+
+            CREATE INDEX idx_recent_resources_frecency
+                ON recent_resources(frecency DESC);
+
+            CREATE TABLE palette_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+        ",
+        backfill: Some(rebuild_frecency),
+    },
+    Migration {
+        version: 4,
+        description: "scope of command invocations",
+        sql: "
+            ALTER TABLE command_invocations ADD COLUMN namespace TEXT;
+            ALTER TABLE command_invocations ADD COLUMN resource_kind TEXT;
+        ",
+        backfill: None,
+    },
+];
+
+/// Schema version this build expects
+const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// Successful commands next-command predictions learn from, newest first
+const PREDICTION_HISTORY_LEN: usize = 5000;
+
+/// Frecency half-life until one is set, in hours
+const DEFAULT_FRECENCY_HALF_LIFE_HOURS: f64 = 168.0;
+
+const FRECENCY_HALF_LIFE_SETTING: &str = "frecency_half_life_hours";
+
+/// A write, run on the writer thread
+type WriteJob = Box<dyn FnOnce(&mut Connection) + Send>;
+
+/// State of the database, for the UI to explain why history is missing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseStatus {
+    /// Schema version of the database file, 0 if it couldn't be read
+    pub schema_version: i64,
+    /// Schema version this build expects
+    pub latest_version: i64,
+    /// Copy of the database taken before the last migration
+    pub backup_path: Option<String>,
+    /// Why the database is unavailable, if it is
+    pub error: Option<String>,
+}
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_recent_resources_timestamp
-             ON recent_resources(timestamp DESC)",
-            [],
-        )?;
+/// Command palette database manager
+///
+/// One instance is opened at startup and shared as managed state. The
+/// database runs in WAL mode so that reads, which the palette issues on
+/// every keystroke, go through their own connection and never wait on a
+/// write; writes are serialized on a dedicated thread owning the second
+/// connection.
+///
+/// If the database can't be opened or migrated the instance is still
+/// created, unavailable: every call fails and `status` says why.
+pub struct CommandPaletteDb {
+    handles: Option<Handles>,
+    status: DatabaseStatus,
+}
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_recent_resources_kind
-             ON recent_resources(kind)",
-            [],
-        )?;
+/// Connections of an open database
+struct Handles {
+    conn: Mutex<Connection>,
+    writer: mpsc::Sender<WriteJob>,
+    /// Bumped whenever the history predictions learn from changes
+    history_version: AtomicU64,
+    /// Prediction model and the `history_version` it was trained at
+    predictor: Mutex<Option<(u64, Arc<Predictor>)>>,
+}
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_patterns_confidence
-             ON command_patterns(confidence DESC)",
-            [],
-        )?;
+/// Next-command model with the history it was trained on
+struct Predictor {
+    model: PredictionModel,
+    history: Vec<HistoryStep>,
+}
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_patterns_last_seen
-             ON command_patterns(last_seen DESC)",
-            [],
-        )?;
+impl CommandPaletteDb {
+    /// Open the database in the app data dir
+    pub fn new(app: &AppHandle) -> Self {
+        match Self::get_db_path(app) {
+            Ok(db_path) => {
+                info!("Opening command palette database at: {:?}", db_path);
+                Self::open(&db_path)
+            }
+            Err(e) => Self::unavailable(DatabaseStatus::new(), e),
+        }
+    }
 
-        info!("Command palette database initialized");
-        Ok(())
+    /// Open the database at `db_path`, migrating it and starting the writer
+    fn open(db_path: &Path) -> Self {
+        let mut status = DatabaseStatus::new();
+        match Self::open_handles(db_path, &mut status) {
+            Ok(handles) => CommandPaletteDb {
+                handles: Some(handles),
+                status,
+            },
+            Err(e) => Self::unavailable(status, e),
+        }
+    }
+
+    fn unavailable(mut status: DatabaseStatus, error: rusqlite::Error) -> Self {
+        error!("Command palette database unavailable: {}", error);
+        status.error = Some(error.to_string());
+        CommandPaletteDb {
+            handles: None,
+            status,
+        }
+    }
+
+    fn open_handles(db_path: &Path, status: &mut DatabaseStatus) -> SqlResult<Handles> {
+        let mut writer_conn = Self::connect(db_path)?;
+        let journal_mode: String =
+            writer_conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        debug!("Command palette database journal mode: {}", journal_mode);
+        writer_conn.pragma_update(None, "synchronous", "NORMAL")?;
+        migrate(&mut writer_conn, db_path, status)?;
+
+        let conn = Self::connect(db_path)?;
+        conn.pragma_update(None, "query_only", true)?;
+
+        let (writer, jobs) = mpsc::channel::<WriteJob>();
+        std::thread::Builder::new()
+            .name("command-palette-db".to_string())
+            .spawn(move || {
+                let mut conn = writer_conn;
+                for job in jobs {
+                    job(&mut conn);
+                }
+                debug!("Command palette database writer stopped");
+            })
+            .map_err(|e| db_error(format!("Failed to start writer: {}", e)))?;
+
+        Ok(Handles {
+            conn: Mutex::new(conn),
+            writer,
+            history_version: AtomicU64::new(0),
+            predictor: Mutex::new(None),
+        })
+    }
+
+    /// State of the database
+    pub fn status(&self) -> &DatabaseStatus {
+        &self.status
+    }
+
+    /// Lock the read connection
+    fn reader(&self) -> SqlResult<MutexGuard<'_, Connection>> {
+        Ok(self.handles()?.conn.lock().unwrap())
+    }
+
+    fn handles(&self) -> SqlResult<&Handles> {
+        self.handles.as_ref().ok_or_else(|| {
+            db_error(format!(
+                "Database unavailable: {}",
+                self.status.error.as_deref().unwrap_or("not open")
+            ))
+        })
+    }
+
+    /// Open a connection with the settings both connections share
+    fn connect(db_path: &Path) -> SqlResult<Connection> {
+        let conn = Connection::open(db_path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        Ok(conn)
+    }
+
+    /// Run a write on the writer thread and wait for its result
+    ///
+    /// Waiting doesn't block the runtime thread the caller is on; callers
+    /// are commands and watch tasks, which would otherwise stall behind a
+    /// slow write such as pattern detection.
+    async fn write<T, F>(&self, f: F) -> SqlResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> SqlResult<T> + Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        self.handles()?
+            .writer
+            .send(Box::new(move |conn| {
+                let _ = reply.send(f(conn));
+            }))
+            .map_err(|_| db_error("Writer has stopped"))?;
+        result.await.map_err(|_| db_error("Writer has stopped"))?
+    }
+
+    /// Get the database file path
+    fn get_db_path(app: &AppHandle) -> SqlResult<PathBuf> {
+        let app_data_dir = app.path().app_data_dir().map_err(|e| {
+            rusqlite::Error::InvalidPath(format!("Failed to get app data dir: {}", e).into())
+        })?;
+
+        std::fs::create_dir_all(&app_data_dir).map_err(|e| {
+            rusqlite::Error::InvalidPath(format!("Failed to create app data dir: {}", e).into())
+        })?;
+
+        Ok(app_data_dir.join("command-palette.db"))
     }
 
     /// Record a command invocation
-    pub fn record_invocation(
+    pub async fn record_invocation(
         &self,
         command_id: &str,
         execution_time_ms: Option<i64>,
         success: bool,
         error_message: Option<&str>,
-        context: Option<&str>,
+        arguments: Option<&str>,
+        scope: &CommandScope,
     ) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        let timestamp = Utc::now().to_rfc3339();
-
-        conn.execute(
-            "INSERT INTO command_invocations
-             (command_id, timestamp, execution_time_ms, success, error_message, context)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![
+        // Errors and contexts often echo command output, and arguments may
+        // carry tokens; keep credentials out of the database
+        let error_message = error_message.map(mask_text);
+        let context = scope.context.as_deref().map(mask_text);
+        let arguments = arguments.map(mask_text);
+        let namespace = scope.namespace.clone();
+        let resource_kind = scope.resource_kind.clone();
+
+        let command_id = command_id.to_string();
+        let now = Utc::now();
+        let timestamp = timestamp(&now);
+
+        self.write(move |conn| {
+            let tx = conn.transaction()?;
+            tx.prepare_cached(
+                "INSERT INTO command_invocations
+                 (command_id, timestamp, execution_time_ms, success, error_message, context,
+                  arguments, namespace, resource_kind)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?
+            .execute(params![
                 command_id,
                 timestamp,
                 execution_time_ms,
                 success,
                 error_message,
-                context
-            ],
-        )?;
+                context,
+                arguments,
+                namespace,
+                resource_kind
+            ])?;
+
+            if success {
+                let key = tx
+                    .prepare_cached("SELECT frecency FROM command_frecency WHERE command_id = ?1")?
+                    .query_row(params![command_id], |row| row.get(0))
+                    .optional()?;
+                let frecency = frecency_add(key, unix_time(&now), frecency_half_life(&tx)?);
+                tx.prepare_cached(
+                    "INSERT INTO command_frecency (command_id, frecency)
+                     VALUES (?1, ?2)
+                     ON CONFLICT(command_id) DO UPDATE SET frecency = excluded.frecency",
+                )?
+                .execute(params![command_id, frecency])?;
+            }
+            tx.commit()?;
+
+            debug!("Recorded command invocation: {}", command_id);
+            Ok(())
+        })
+        .await?;
 
-        debug!("Recorded command invocation: {}", command_id);
+        if success {
+            self.history_changed()?;
+        }
         Ok(())
     }
 
     /// Get command statistics
     pub fn get_command_stats(&self, command_id: Option<&str>) -> SqlResult<Vec<CommandStats>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader()?;
+        let clock = FrecencyClock::new(&conn)?;
 
-        let query = if let Some(id) = command_id {
-            format!(
-                "SELECT
+        let (query, params_vec): (&str, Vec<&dyn rusqlite::ToSql>) =
+            if let Some(id_ref) = command_id.as_ref() {
+                (
+                    "SELECT
                     command_id,
                     COUNT(*) as hit_count,
                     MAX(timestamp) as last_used,
-                    AVG(execution_time_ms) as avg_execution_time
-                 FROM command_invocations
-                 WHERE command_id = '{}' AND success = 1
+                    AVG(execution_time_ms) as avg_execution_time,
+                    (SELECT frecency FROM command_frecency f
+                     WHERE f.command_id = c.command_id) as frecency
+                 FROM command_invocations c
+                 WHERE command_id = ?1 AND success = 1
                  GROUP BY command_id",
-                id
-            )
-        } else {
-            "SELECT
-                command_id,
-                COUNT(*) as hit_count,
-                MAX(timestamp) as last_used,
-                AVG(execution_time_ms) as avg_execution_time
-             FROM command_invocations
-             WHERE success = 1
-             GROUP BY command_id
-             ORDER BY hit_count DESC"
-                .to_string()
-        };
+                    vec![id_ref as &dyn rusqlite::ToSql],
+                )
+            } else {
+                (
+                    "SELECT
+                    command_id,
+                    COUNT(*) as hit_count,
+                    MAX(timestamp) as last_used,
+                    AVG(execution_time_ms) as avg_execution_time,
+                    (SELECT frecency FROM command_frecency f
+                     WHERE f.command_id = c.command_id) as frecency
+                 FROM command_invocations c
+                 WHERE success = 1
+                 GROUP BY command_id
+                 ORDER BY hit_count DESC",
+                    vec![],
+                )
+            };
 
-        let mut stmt = conn.prepare(&query)?;
-        let stats_iter = stmt.query_map([], |row| {
-            Ok(CommandStats {
-                command_id: row.get(0)?,
-                hit_count: row.get(1)?,
-                last_used: row.get(2)?,
-                avg_execution_time: row.get(3)?,
-            })
+        let mut stmt = conn.prepare_cached(query)?;
+        let stats_iter = stmt.query_map(params_vec.as_slice(), |row| {
+            command_stats_from_row(row, &clock)
         })?;
 
         let mut results = Vec::new();
@@ -304,31 +868,45 @@ impl CommandPaletteDb {
         Ok(results)
     }
 
-    /// Get most frequently used commands
-    pub fn get_top_commands(&self, limit: usize) -> SqlResult<Vec<CommandStats>> {
-        let conn = self.conn.lock().unwrap();
+    /// Get the most used commands, by count or frecency
+    pub fn get_top_commands(&self, limit: usize, rank_by: RankBy) -> SqlResult<Vec<CommandStats>> {
+        let conn = self.reader()?;
+        let clock = FrecencyClock::new(&conn)?;
 
-        let mut stmt = conn.prepare(
-            "SELECT
-                command_id,
-                COUNT(*) as hit_count,
-                MAX(timestamp) as last_used,
-                AVG(execution_time_ms) as avg_execution_time
-             FROM command_invocations
-             WHERE success = 1
-             GROUP BY command_id
-             ORDER BY hit_count DESC
-             LIMIT ?1",
-        )?;
+        let mut stmt = match rank_by {
+            RankBy::Count => conn.prepare_cached(
+                "SELECT
+                    command_id,
+                    COUNT(*) as hit_count,
+                    MAX(timestamp) as last_used,
+                    AVG(execution_time_ms) as avg_execution_time,
+                    (SELECT frecency FROM command_frecency f
+                     WHERE f.command_id = c.command_id) as frecency
+                 FROM command_invocations c
+                 WHERE success = 1
+                 GROUP BY command_id
+                 ORDER BY hit_count DESC
+                 LIMIT ?1",
+            )?,
+            // Limited after the join: commands whose history was cleaned up
+            // keep their frecency but are not listed
+            RankBy::Frecency => conn.prepare_cached(
+                "SELECT
+                    f.command_id,
+                    COUNT(*) as hit_count,
+                    MAX(c.timestamp) as last_used,
+                    AVG(c.execution_time_ms) as avg_execution_time,
+                    f.frecency
+                 FROM command_frecency f
+                 JOIN command_invocations c ON c.command_id = f.command_id AND c.success = 1
+                 GROUP BY f.command_id
+                 ORDER BY f.frecency DESC
+                 LIMIT ?1",
+            )?,
+        };
 
-        let stats_iter = stmt.query_map(params![limit], |row| {
-            Ok(CommandStats {
-                command_id: row.get(0)?,
-                hit_count: row.get(1)?,
-                last_used: row.get(2)?,
-                avg_execution_time: row.get(3)?,
-            })
-        })?;
+        let stats_iter =
+            stmt.query_map(params![limit], |row| command_stats_from_row(row, &clock))?;
 
         let mut results = Vec::new();
         for stat in stats_iter {
@@ -339,36 +917,39 @@ impl CommandPaletteDb {
     }
 
     /// Record a search query
-    pub fn record_query(&self, query: &str, result_count: i32) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        let timestamp = Utc::now().to_rfc3339();
-
-        conn.execute(
-            "INSERT INTO recent_queries (query, timestamp, result_count)
-             VALUES (?1, ?2, ?3)",
-            params![query, timestamp, result_count],
-        )?;
-
-        // Keep only last 100 queries
-        conn.execute(
-            "DELETE FROM recent_queries
-             WHERE id NOT IN (
-                 SELECT id FROM recent_queries
-                 ORDER BY timestamp DESC
-                 LIMIT 100
-             )",
-            [],
-        )?;
-
-        debug!("Recorded query: {}", query);
-        Ok(())
+    pub async fn record_query(&self, query: &str, result_count: i32) -> SqlResult<()> {
+        let query = query.to_string();
+        let timestamp = timestamp(&Utc::now());
+
+        self.write(move |conn| {
+            conn.prepare_cached(
+                "INSERT INTO recent_queries (query, timestamp, result_count)
+                 VALUES (?1, ?2, ?3)",
+            )?
+            .execute(params![query, timestamp, result_count])?;
+
+            // Keep only last 100 queries
+            conn.prepare_cached(
+                "DELETE FROM recent_queries
+                 WHERE id NOT IN (
+                     SELECT id FROM recent_queries
+                     ORDER BY timestamp DESC
+                     LIMIT 100
+                 )",
+            )?
+            .execute([])?;
+
+            debug!("Recorded query: {}", query);
+            Ok(())
+        })
+        .await
     }
 
     /// Get recent queries
     pub fn get_recent_queries(&self, limit: usize) -> SqlResult<Vec<RecentQuery>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader()?;
 
-        let mut stmt = conn.prepare(
+        let mut stmt = conn.prepare_cached(
             "SELECT query, timestamp, result_count
              FROM recent_queries
              ORDER BY timestamp DESC
@@ -392,57 +973,97 @@ impl CommandPaletteDb {
     }
 
     /// Clear old data (older than 90 days)
-    pub fn cleanup_old_data(&self) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        let cutoff_date = Utc::now()
-            .checked_sub_signed(chrono::Duration::days(90))
-            .unwrap()
-            .to_rfc3339();
+    pub async fn cleanup_old_data(&self) -> SqlResult<()> {
+        let cutoff_date = timestamp(
+            &Utc::now()
+                .checked_sub_signed(chrono::Duration::days(90))
+                .unwrap(),
+        );
+
+        self.write(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM command_invocations WHERE timestamp < ?1",
+                params![cutoff_date],
+            )?;
 
-        let deleted = conn.execute(
-            "DELETE FROM command_invocations WHERE timestamp < ?1",
-            params![cutoff_date],
-        )?;
+            info!("Cleaned up {} old command invocation records", deleted);
+            Ok(())
+        })
+        .await?;
 
-        info!("Cleaned up {} old command invocation records", deleted);
-        Ok(())
+        self.history_changed()
     }
 
     /// Record a resource access
-    pub fn record_resource_access(
+    pub async fn record_resource_access(
         &self,
         kind: &str,
         name: &str,
         namespace: Option<&str>,
         context: Option<&str>,
     ) -> SqlResult<()> {
-        let conn = self.conn.lock().unwrap();
-        let timestamp = Utc::now().to_rfc3339();
-
-        // Try to insert or update if exists
-        conn.execute(
-            "INSERT INTO recent_resources (kind, name, namespace, context, timestamp, access_count)
-             VALUES (?1, ?2, ?3, ?4, ?5, 1)
-             ON CONFLICT(kind, name, namespace, context)
-             DO UPDATE SET
-                 timestamp = excluded.timestamp,
-                 access_count = access_count + 1",
-            params![kind, name, namespace, context, timestamp],
-        )?;
-
-        // Keep only last 100 resources
-        conn.execute(
-            "DELETE FROM recent_resources
-             WHERE id NOT IN (
-                 SELECT id FROM recent_resources
-                 ORDER BY timestamp DESC
-                 LIMIT 100
-             )",
-            [],
-        )?;
+        let kind = kind.to_string();
+        let name = name.to_string();
+        let namespace = namespace.map(String::from);
+        let context = context.map(String::from);
+        let now = Utc::now();
+        let timestamp = timestamp(&now);
+
+        self.write(move |conn| {
+            let tx = conn.transaction()?;
+
+            // Looked up with IS rather than relying on the UNIQUE constraint,
+            // which never matches NULL namespaces or contexts
+            let existing: Option<(i64, f64)> = tx
+                .prepare_cached(
+                    "SELECT id, frecency FROM recent_resources
+                     WHERE kind = ?1 AND name = ?2 AND namespace IS ?3 AND context IS ?4
+                     ORDER BY timestamp DESC
+                     LIMIT 1",
+                )?
+                .query_row(params![kind, name, namespace, context], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .optional()?;
+            let half_life = frecency_half_life(&tx)?;
+
+            match existing {
+                Some((id, key)) => {
+                    let frecency = frecency_add(Some(key), unix_time(&now), half_life);
+                    tx.prepare_cached(
+                        "UPDATE recent_resources
+                         SET timestamp = ?2, access_count = access_count + 1, frecency = ?3
+                         WHERE id = ?1",
+                    )?
+                    .execute(params![id, timestamp, frecency])?;
+                }
+                None => {
+                    let frecency = frecency_add(None, unix_time(&now), half_life);
+                    tx.prepare_cached(
+                        "INSERT INTO recent_resources
+                         (kind, name, namespace, context, timestamp, access_count, frecency)
+                         VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6)",
+                    )?
+                    .execute(params![kind, name, namespace, context, timestamp, frecency])?;
+                }
+            }
 
-        debug!("Recorded resource access: {} {}", kind, name);
-        Ok(())
+            // Keep only last 100 resources
+            tx.prepare_cached(
+                "DELETE FROM recent_resources
+                 WHERE id NOT IN (
+                     SELECT id FROM recent_resources
+                     ORDER BY timestamp DESC
+                     LIMIT 100
+                 )",
+            )?
+            .execute([])?;
+            tx.commit()?;
+
+            debug!("Recorded resource access: {} {}", kind, name);
+            Ok(())
+        })
+        .await
     }
 
     /// Get recent resources
@@ -451,12 +1072,13 @@ impl CommandPaletteDb {
         limit: usize,
         kind_filter: Option<&str>,
     ) -> SqlResult<Vec<ResourceSummary>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader()?;
+        let clock = FrecencyClock::new(&conn)?;
 
         let (query, params_vec): (String, Vec<&dyn rusqlite::ToSql>) =
             if let Some(kind_ref) = kind_filter.as_ref() {
                 (
-                    "SELECT kind, name, namespace, context, timestamp, access_count
+                    "SELECT kind, name, namespace, context, timestamp, access_count, frecency
                  FROM recent_resources
                  WHERE kind = ?1
                  ORDER BY timestamp DESC
@@ -469,7 +1091,7 @@ impl CommandPaletteDb {
                 )
             } else {
                 (
-                    "SELECT kind, name, namespace, context, timestamp, access_count
+                    "SELECT kind, name, namespace, context, timestamp, access_count, frecency
                  FROM recent_resources
                  ORDER BY timestamp DESC
                  LIMIT ?1"
@@ -478,17 +1100,9 @@ impl CommandPaletteDb {
                 )
             };
 
-        let mut stmt = conn.prepare(&query)?;
-        let resource_iter = stmt.query_map(params_vec.as_slice(), |row| {
-            Ok(ResourceSummary {
-                kind: row.get(0)?,
-                name: row.get(1)?,
-                namespace: row.get(2)?,
-                context: row.get(3)?,
-                last_accessed: row.get(4)?,
-                access_count: row.get(5)?,
-            })
-        })?;
+        let mut stmt = conn.prepare_cached(&query)?;
+        let resource_iter =
+            stmt.query_map(params_vec.as_slice(), |row| resource_from_row(row, &clock))?;
 
         let mut results = Vec::new();
         for resource in resource_iter {
@@ -498,23 +1112,31 @@ impl CommandPaletteDb {
         Ok(results)
     }
 
-    /// Get most accessed resources
+    /// Get the most accessed resources, by count or frecency
     pub fn get_top_resources(
         &self,
         limit: usize,
         kind_filter: Option<&str>,
+        rank_by: RankBy,
     ) -> SqlResult<Vec<ResourceSummary>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader()?;
+        let clock = FrecencyClock::new(&conn)?;
 
+        let order = match rank_by {
+            RankBy::Count => "access_count DESC, timestamp DESC",
+            RankBy::Frecency => "frecency DESC",
+        };
         let (query, params_vec): (String, Vec<&dyn rusqlite::ToSql>) =
             if let Some(kind_ref) = kind_filter.as_ref() {
                 (
-                    "SELECT kind, name, namespace, context, timestamp, access_count
+                    format!(
+                        "SELECT kind, name, namespace, context, timestamp, access_count, frecency
                  FROM recent_resources
                  WHERE kind = ?1
-                 ORDER BY access_count DESC, timestamp DESC
-                 LIMIT ?2"
-                        .to_string(),
+                 ORDER BY {}
+                 LIMIT ?2",
+                        order
+                    ),
                     vec![
                         kind_ref as &dyn rusqlite::ToSql,
                         &limit as &dyn rusqlite::ToSql,
@@ -522,26 +1144,20 @@ impl CommandPaletteDb {
                 )
             } else {
                 (
-                    "SELECT kind, name, namespace, context, timestamp, access_count
+                    format!(
+                        "SELECT kind, name, namespace, context, timestamp, access_count, frecency
                  FROM recent_resources
-                 ORDER BY access_count DESC, timestamp DESC
-                 LIMIT ?1"
-                        .to_string(),
+                 ORDER BY {}
+                 LIMIT ?1",
+                        order
+                    ),
                     vec![&limit as &dyn rusqlite::ToSql],
                 )
             };
 
-        let mut stmt = conn.prepare(&query)?;
-        let resource_iter = stmt.query_map(params_vec.as_slice(), |row| {
-            Ok(ResourceSummary {
-                kind: row.get(0)?,
-                name: row.get(1)?,
-                namespace: row.get(2)?,
-                context: row.get(3)?,
-                last_accessed: row.get(4)?,
-                access_count: row.get(5)?,
-            })
-        })?;
+        let mut stmt = conn.prepare_cached(&query)?;
+        let resource_iter =
+            stmt.query_map(params_vec.as_slice(), |row| resource_from_row(row, &clock))?;
 
         let mut results = Vec::new();
         for resource in resource_iter {
@@ -552,126 +1168,126 @@ impl CommandPaletteDb {
     }
 
     /// Detect command patterns from history
-    pub fn detect_patterns(
+    pub async fn detect_patterns(
         &self,
         min_sequence_length: usize,
         max_sequence_length: usize,
     ) -> SqlResult<Vec<CommandPattern>> {
-        let conn = self.conn.lock().unwrap();
-
-        // Get recent command sequences (last 1000 commands)
-        let mut stmt = conn.prepare(
-            "SELECT command_id, timestamp
-             FROM command_invocations
-             WHERE success = 1
-             ORDER BY timestamp DESC
-             LIMIT 1000",
-        )?;
-
-        let commands: Vec<(String, String)> = stmt
-            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        if commands.len() < min_sequence_length {
-            return Ok(Vec::new());
-        }
-
-        // Reverse to get chronological order
-        let commands: Vec<(String, String)> = commands.into_iter().rev().collect();
+        self.write(move |conn| {
+            // Get recent command sequences (last 1000 commands)
+            let mut stmt = conn.prepare_cached(
+                "SELECT command_id, timestamp
+                 FROM command_invocations
+                 WHERE success = 1
+                 ORDER BY timestamp DESC
+                 LIMIT 1000",
+            )?;
 
-        // Find patterns by sliding window
-        let mut pattern_map: std::collections::HashMap<String, (Vec<String>, Vec<f64>, String)> =
-            std::collections::HashMap::new();
+            let commands: Vec<(String, String)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
 
-        for seq_len in min_sequence_length..=max_sequence_length {
-            for i in 0..commands.len().saturating_sub(seq_len) {
-                let sequence: Vec<String> = commands[i..i + seq_len]
-                    .iter()
-                    .map(|(cmd, _)| cmd.clone())
-                    .collect();
-
-                let pattern_id = sequence.join(" -> ");
-
-                // Calculate time between commands
-                let time_diffs: Vec<f64> = (0..seq_len - 1)
-                    .filter_map(|j| {
-                        let t1 = chrono::DateTime::parse_from_rfc3339(&commands[i + j].1).ok()?;
-                        let t2 =
-                            chrono::DateTime::parse_from_rfc3339(&commands[i + j + 1].1).ok()?;
-                        Some((t2 - t1).num_seconds() as f64)
-                    })
-                    .collect();
-
-                pattern_map
-                    .entry(pattern_id.clone())
-                    .and_modify(|(_, times, _)| {
-                        times.extend(time_diffs.clone());
-                    })
-                    .or_insert((sequence, time_diffs, commands[i + seq_len - 1].1.clone()));
+            if commands.len() < min_sequence_length {
+                return Ok(Vec::new());
             }
-        }
-
-        // Calculate confidence and store patterns
-        let timestamp = Utc::now().to_rfc3339();
-        let mut detected_patterns = Vec::new();
 
-        for (pattern_id, (sequence, time_diffs, last_seen)) in pattern_map {
-            let frequency = (time_diffs.len() / (sequence.len() - 1).max(1)) as i64;
+            // Reverse to get chronological order
+            let commands: Vec<(String, String)> = commands.into_iter().rev().collect();
+
+            // Find patterns by sliding window
+            let mut pattern_map: std::collections::HashMap<String, (Vec<String>, Vec<f64>, String)> =
+                std::collections::HashMap::new();
+
+            for seq_len in min_sequence_length..=max_sequence_length {
+                for i in 0..commands.len().saturating_sub(seq_len) {
+                    let sequence: Vec<String> = commands[i..i + seq_len]
+                        .iter()
+                        .map(|(cmd, _)| cmd.clone())
+                        .collect();
+
+                    let pattern_id = sequence.join(" -> ");
+
+                    // Calculate time between commands
+                    let time_diffs: Vec<f64> = (0..seq_len - 1)
+                        .filter_map(|j| {
+                            let t1 = chrono::DateTime::parse_from_rfc3339(&commands[i + j].1).ok()?;
+                            let t2 =
+                                chrono::DateTime::parse_from_rfc3339(&commands[i + j + 1].1).ok()?;
+                            Some((t2 - t1).num_seconds() as f64)
+                        })
+                        .collect();
 
-            // Skip patterns that only occurred once
-            if frequency < 2 {
-                continue;
+                    pattern_map
+                        .entry(pattern_id.clone())
+                        .and_modify(|(_, times, _)| {
+                            times.extend(time_diffs.clone());
+                        })
+                        .or_insert((sequence, time_diffs, commands[i + seq_len - 1].1.clone()));
+                }
             }
 
-            // Calculate confidence based on frequency and recency
-            let recency_factor =
-                if let Ok(last_time) = chrono::DateTime::parse_from_rfc3339(&last_seen) {
-                    let now = Utc::now().with_timezone(&chrono::FixedOffset::east_opt(0).unwrap());
-                    let days_ago = (now - last_time).num_days() as f64;
-                    (1.0 / (1.0 + days_ago / 30.0)).max(0.1)
-                } else {
-                    0.5
-                };
+            // Calculate confidence and store patterns
+            let timestamp = timestamp(&Utc::now());
+            let mut detected_patterns = Vec::new();
 
-            // Normalize frequency (max 20 occurrences = 1.0)
-            let frequency_factor = (frequency as f64 / 20.0).min(1.0);
+            for (pattern_id, (sequence, time_diffs, last_seen)) in pattern_map {
+                let frequency = (time_diffs.len() / (sequence.len() - 1).max(1)) as i64;
 
-            let confidence = (frequency_factor * 0.7 + recency_factor * 0.3).min(1.0);
+                // Skip patterns that only occurred once
+                if frequency < 2 {
+                    continue;
+                }
 
-            // Calculate average time between commands
-            let avg_time = if !time_diffs.is_empty() {
-                Some(time_diffs.iter().sum::<f64>() / time_diffs.len() as f64)
-            } else {
-                None
-            };
+                // Calculate confidence based on frequency and recency
+                let recency_factor =
+                    if let Ok(last_time) = chrono::DateTime::parse_from_rfc3339(&last_seen) {
+                        let now = Utc::now().with_timezone(&chrono::FixedOffset::east_opt(0).unwrap());
+                        let days_ago = (now - last_time).num_days() as f64;
+                        (1.0 / (1.0 + days_ago / 30.0)).max(0.1)
+                    } else {
+                        0.5
+                    };
 
-            // Store or update pattern
-            let sequence_json = serde_json::to_string(&sequence).unwrap_or_default();
+                // Normalize frequency (max 20 occurrences = 1.0)
+                let frequency_factor = (frequency as f64 / 20.0).min(1.0);
 
-            conn.execute(
-                "INSERT INTO command_patterns (pattern_id, command_sequence, frequency, confidence, last_seen, avg_time_between_commands)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-                 ON CONFLICT(pattern_id)
-                 DO UPDATE SET
-                     frequency = ?3,
-                     confidence = ?4,
-                     last_seen = ?5,
-                     avg_time_between_commands = ?6",
-                params![pattern_id, sequence_json, frequency, confidence, timestamp, avg_time],
-            )?;
+                let confidence = (frequency_factor * 0.7 + recency_factor * 0.3).min(1.0);
 
-            detected_patterns.push(CommandPattern {
-                pattern_id,
-                command_sequence: sequence,
-                frequency,
-                confidence,
-                last_seen: timestamp.clone(),
-                avg_time_between_commands: avg_time,
-            });
-        }
+                // Calculate average time between commands
+                let avg_time = if !time_diffs.is_empty() {
+                    Some(time_diffs.iter().sum::<f64>() / time_diffs.len() as f64)
+                } else {
+                    None
+                };
+
+                // Store or update pattern
+                let sequence_json = serde_json::to_string(&sequence).unwrap_or_default();
+
+                conn.execute(
+                    "INSERT INTO command_patterns (pattern_id, command_sequence, frequency, confidence, last_seen, avg_time_between_commands)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                     ON CONFLICT(pattern_id)
+                     DO UPDATE SET
+                         frequency = ?3,
+                         confidence = ?4,
+                         last_seen = ?5,
+                         avg_time_between_commands = ?6",
+                    params![pattern_id, sequence_json, frequency, confidence, timestamp, avg_time],
+                )?;
+
+                detected_patterns.push(CommandPattern {
+                    pattern_id,
+                    command_sequence: sequence,
+                    frequency,
+                    confidence,
+                    last_seen: timestamp.clone(),
+                    avg_time_between_commands: avg_time,
+                });
+            }
 
-        info!("Detected {} command patterns", detected_patterns.len());
-        Ok(detected_patterns)
+            info!("Detected {} command patterns", detected_patterns.len());
+            Ok(detected_patterns)
+        }).await
     }
 
     /// Get command patterns with minimum confidence
@@ -680,9 +1296,9 @@ impl CommandPaletteDb {
         min_confidence: f64,
         limit: usize,
     ) -> SqlResult<Vec<CommandPattern>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader()?;
 
-        let mut stmt = conn.prepare(
+        let mut stmt = conn.prepare_cached(
             "SELECT pattern_id, command_sequence, frequency, confidence, last_seen, avg_time_between_commands
              FROM command_patterns
              WHERE confidence >= ?1
@@ -719,13 +1335,13 @@ impl CommandPaletteDb {
         last_commands: Vec<String>,
         limit: usize,
     ) -> SqlResult<Vec<PatternSuggestion>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader()?;
 
         let mut suggestions: std::collections::HashMap<String, (f64, i64, String)> =
             std::collections::HashMap::new();
 
         // Get all patterns
-        let mut stmt = conn.prepare(
+        let mut stmt = conn.prepare_cached(
             "SELECT pattern_id, command_sequence, frequency, confidence
              FROM command_patterns
              ORDER BY confidence DESC",
@@ -785,13 +1401,89 @@ impl CommandPaletteDb {
         Ok(result)
     }
 
-    /// Get recent command history for fuzzy search
-    /// Returns recent command invocations in chronological order
-    pub fn get_command_history(&self, limit: usize) -> SqlResult<Vec<CommandHistory>> {
-        let conn = self.conn.lock().unwrap();
+    /// Predict the next command from history in a similar scope
+    ///
+    /// `last_commands` are the commands just run, oldest first; if `None`,
+    /// those of the session still going on in history.
+    pub fn predict_next_commands(
+        &self,
+        last_commands: Option<Vec<String>>,
+        scope: &CommandScope,
+        limit: usize,
+    ) -> SqlResult<Vec<CommandPrediction>> {
+        let predictor = self.predictor()?;
+        let last_commands = last_commands.unwrap_or_else(|| {
+            prediction::current_session(&predictor.history, Utc::now().timestamp())
+        });
+        Ok(predictor.model.predict(&last_commands, scope, limit))
+    }
 
-        let mut stmt = conn.prepare(
-            "SELECT command_id, timestamp, execution_time_ms, success
+    /// The model trained on current history, retrained if history changed
+    ///
+    /// Training holds only the model's lock, so other reads go on; a
+    /// concurrent prediction waits for the model rather than training too.
+    fn predictor(&self) -> SqlResult<Arc<Predictor>> {
+        let handles = self.handles()?;
+        let mut cached = handles.predictor.lock().unwrap();
+        let version = handles.history_version.load(Ordering::SeqCst);
+        if let Some((trained, predictor)) = cached.as_ref() {
+            if *trained == version {
+                return Ok(predictor.clone());
+            }
+        }
+
+        let history = self.prediction_history()?;
+        let predictor = Arc::new(Predictor {
+            model: PredictionModel::train(&history),
+            history,
+        });
+        *cached = Some((version, predictor.clone()));
+        Ok(predictor)
+    }
+
+    /// Successful invocations to predict from, oldest first
+    fn prediction_history(&self) -> SqlResult<Vec<HistoryStep>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare_cached(
+            "SELECT command_id, timestamp, context, namespace, resource_kind
+             FROM command_invocations
+             WHERE success = 1
+             ORDER BY timestamp DESC
+             LIMIT ?1",
+        )?;
+
+        let mut history = stmt
+            .query_map(params![PREDICTION_HISTORY_LEN], |row| {
+                Ok(HistoryStep {
+                    command_id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    scope: CommandScope {
+                        context: row.get(2)?,
+                        namespace: row.get(3)?,
+                        resource_kind: row.get(4)?,
+                    },
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        history.reverse();
+        Ok(history)
+    }
+
+    /// Invalidate the prediction model
+    fn history_changed(&self) -> SqlResult<()> {
+        self.handles()?
+            .history_version
+            .fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Get recent command history for fuzzy search
+    /// Returns recent command invocations in chronological order
+    pub fn get_command_history(&self, limit: usize) -> SqlResult<Vec<CommandHistory>> {
+        let conn = self.reader()?;
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT command_id, timestamp, execution_time_ms, success
              FROM command_invocations
              WHERE success = 1
              ORDER BY timestamp DESC
@@ -814,106 +1506,984 @@ impl CommandPaletteDb {
 
         Ok(results)
     }
+
+    /// Get the frecency half-life, in hours
+    pub fn get_frecency_half_life(&self) -> SqlResult<f64> {
+        let conn = self.reader()?;
+        Ok(frecency_half_life(&conn)? / 3600.0)
+    }
+
+    /// Set the frecency half-life, in hours, re-ranking everything
+    pub async fn set_frecency_half_life(&self, hours: f64) -> SqlResult<()> {
+        if !(hours.is_finite() && hours > 0.0) {
+            return Err(db_error(format!(
+                "Invalid frecency half-life: {} hours",
+                hours
+            )));
+        }
+
+        self.write(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO palette_settings (key, value) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![FRECENCY_HALF_LIFE_SETTING, hours.to_string()],
+            )?;
+            rebuild_frecency(&tx)?;
+            tx.commit()?;
+
+            info!("Frecency half-life set to {} hours", hours);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Search command history, best matches first
+    ///
+    /// Every whitespace-separated term of `query` must occur, case
+    /// insensitively, in the command id, arguments or context. Terms of
+    /// three or more characters are looked up in the trigram index and
+    /// ranked with BM25, weighing command ids over arguments over contexts;
+    /// shorter ones can't be indexed and only filter. Without indexed terms
+    /// results are newest first.
+    pub fn search_command_history(
+        &self,
+        query: &str,
+        filter: &HistorySearchFilter,
+    ) -> SqlResult<Vec<HistoryMatch>> {
+        let conn = self.reader()?;
+
+        let terms = search_terms(query);
+        let (indexed, short): (Vec<&String>, Vec<&String>) =
+            terms.iter().partition(|t| t.chars().count() >= 3);
+
+        let mut conditions = Vec::new();
+        let mut values: Vec<Value> = Vec::new();
+        let (mut sql, order) = if indexed.is_empty() {
+            let sql = "SELECT c.id, c.command_id, c.arguments, c.context, c.timestamp,
+                    c.execution_time_ms, c.success, 0.0 AS score
+             FROM command_invocations c"
+                .to_string();
+            (sql, " ORDER BY c.timestamp DESC")
+        } else {
+            conditions.push("command_history_fts MATCH ?");
+            values.push(fts_phrases(&indexed).into());
+            let sql = "SELECT c.id, c.command_id, c.arguments, c.context, c.timestamp,
+                    c.execution_time_ms, c.success,
+                    -bm25(command_history_fts, 10.0, 5.0, 1.0) AS score
+             FROM command_history_fts
+             JOIN command_invocations c ON c.id = command_history_fts.rowid"
+                .to_string();
+            (sql, " ORDER BY score DESC, c.timestamp DESC")
+        };
+
+        for term in short {
+            conditions.push(
+                "instr(lower(c.command_id || ' ' || IFNULL(c.arguments, '') || ' ' ||
+                             IFNULL(c.context, '')), ?) > 0",
+            );
+            values.push(term.clone().into());
+        }
+        if let Some(success) = filter.success {
+            conditions.push("c.success = ?");
+            values.push(success.into());
+        }
+        if let Some(context) = &filter.context {
+            conditions.push("c.context = ?");
+            values.push(context.clone().into());
+        }
+        if let Some(since) = &filter.since {
+            conditions.push("c.timestamp >= ?");
+            values.push(timestamp_bound(since).into());
+        }
+        if let Some(until) = &filter.until {
+            conditions.push("c.timestamp <= ?");
+            values.push(timestamp_bound(until).into());
+        }
+
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(order);
+        sql.push_str(" LIMIT ?");
+        values.push((filter.limit.unwrap_or(50) as i64).into());
+
+        let mut stmt = conn.prepare_cached(&sql)?;
+        let match_iter = stmt.query_map(params_from_iter(values), |row| {
+            Ok(HistoryMatch {
+                id: row.get(0)?,
+                command_id: row.get(1)?,
+                arguments: row.get(2)?,
+                context: row.get(3)?,
+                timestamp: row.get(4)?,
+                execution_time_ms: row.get(5)?,
+                success: row.get(6)?,
+                score: row.get(7)?,
+                highlights: Vec::new(),
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for item in match_iter {
+            let mut item = item?;
+            item.highlights = highlights("command_id", &item.command_id, &terms);
+            if let Some(arguments) = &item.arguments {
+                item.highlights
+                    .extend(highlights("arguments", arguments, &terms));
+            }
+            if let Some(context) = &item.context {
+                item.highlights
+                    .extend(highlights("context", context, &terms));
+            }
+            results.push(item);
+        }
+
+        Ok(results)
+    }
+
+    /// Record Kubernetes events, updating the ones already known
+    ///
+    /// Only the newest `max_events` events of the context are kept.
+    pub async fn record_cluster_events(
+        &self,
+        context: &str,
+        events: &[ClusterEvent],
+        max_events: usize,
+    ) -> SqlResult<()> {
+        let context = context.to_string();
+        let events = events.to_vec();
+
+        self.write(move |conn| {
+            let tx = conn.transaction()?;
+
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT INTO cluster_events
+                     (context, uid, namespace, kind, name, event_type, reason, note,
+                      reporting_controller, count, first_seen, last_seen)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+                     ON CONFLICT(context, uid)
+                     DO UPDATE SET
+                         note = excluded.note,
+                         count = MAX(count, excluded.count),
+                         last_seen = MAX(last_seen, excluded.last_seen)",
+                )?;
+                for event in &events {
+                    stmt.execute(params![
+                        context,
+                        event.uid,
+                        event.namespace,
+                        event.kind,
+                        event.name,
+                        event.event_type,
+                        event.reason,
+                        event.note,
+                        event.reporting_controller,
+                        event.count,
+                        event.first_seen,
+                        event.last_seen
+                    ])?;
+                }
+            }
+
+            tx.execute(
+                "DELETE FROM cluster_events
+                 WHERE context = ?1 AND id NOT IN (
+                     SELECT id FROM cluster_events
+                     WHERE context = ?1
+                     ORDER BY last_seen DESC
+                     LIMIT ?2
+                 )",
+                params![context, max_events],
+            )?;
+            tx.commit()?;
+
+            debug!("Recorded {} cluster events for {}", events.len(), context);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get the rolled-up events of one object, newest first
+    pub fn get_object_events(
+        &self,
+        context: &str,
+        kind: &str,
+        name: &str,
+        namespace: Option<&str>,
+    ) -> SqlResult<Vec<EventRollup>> {
+        let conn = self.reader()?;
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT namespace, kind, name, event_type, reason, note,
+                    SUM(count), MIN(first_seen), MAX(last_seen)
+             FROM cluster_events
+             WHERE context = ?1 AND kind = ?2 AND name = ?3
+               AND (?4 IS NULL OR namespace = ?4)
+             GROUP BY namespace, kind, name, event_type, reason, note
+             ORDER BY MAX(last_seen) DESC",
+        )?;
+
+        let rollup_iter =
+            stmt.query_map(params![context, kind, name, namespace], rollup_from_row)?;
+
+        let mut results = Vec::new();
+        for rollup in rollup_iter {
+            results.push(rollup?);
+        }
+
+        Ok(results)
+    }
+
+    /// Get the rolled-up warnings seen since `since`, newest first
+    pub fn get_recent_warnings(
+        &self,
+        context: &str,
+        since: &str,
+        limit: usize,
+    ) -> SqlResult<Vec<EventRollup>> {
+        let conn = self.reader()?;
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT namespace, kind, name, event_type, reason, note,
+                    SUM(count), MIN(first_seen), MAX(last_seen)
+             FROM cluster_events
+             WHERE context = ?1 AND event_type = 'Warning' AND last_seen >= ?2
+             GROUP BY namespace, kind, name, event_type, reason, note
+             ORDER BY MAX(last_seen) DESC
+             LIMIT ?3",
+        )?;
+
+        let rollup_iter = stmt.query_map(params![context, since, limit], rollup_from_row)?;
+
+        let mut results = Vec::new();
+        for rollup in rollup_iter {
+            results.push(rollup?);
+        }
+
+        Ok(results)
+    }
+
+    /// Get events seen since `since` grouped by reason, most frequent first
+    pub fn get_events_by_reason(
+        &self,
+        context: &str,
+        since: &str,
+    ) -> SqlResult<Vec<EventReasonGroup>> {
+        let conn = self.reader()?;
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT reason, event_type, SUM(count),
+                    COUNT(DISTINCT kind || '/' || IFNULL(namespace, '') || '/' || name),
+                    MAX(last_seen)
+             FROM cluster_events
+             WHERE context = ?1 AND last_seen >= ?2
+             GROUP BY reason, event_type
+             ORDER BY SUM(count) DESC",
+        )?;
+
+        let group_iter = stmt.query_map(params![context, since], |row| {
+            Ok(EventReasonGroup {
+                reason: row.get(0)?,
+                event_type: row.get(1)?,
+                count: row.get(2)?,
+                object_count: row.get(3)?,
+                last_seen: row.get(4)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for group in group_iter {
+            results.push(group?);
+        }
+
+        Ok(results)
+    }
+
+    /// Record that a masked credential was revealed
+    pub async fn record_secret_reveal(
+        &self,
+        secret_id: u64,
+        secret_kind: &str,
+        window_label: &str,
+    ) -> SqlResult<()> {
+        let secret_kind = secret_kind.to_string();
+        let window_label = window_label.to_string();
+
+        self.write(move |conn| {
+            let timestamp = timestamp(&Utc::now());
+
+            conn.execute(
+                "INSERT INTO secret_reveals (timestamp, secret_id, secret_kind, window_label)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![timestamp, secret_id as i64, secret_kind, window_label],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Append an entry to the audit log, chaining it to the last one; fills
+    /// in the entry's id and hashes
+    pub async fn append_audit_entry(&self, entry: &mut AuditEntry) -> SqlResult<()> {
+        let mut appended = entry.clone();
+        *entry = self
+            .write(move |conn| {
+                let entry = &mut appended;
+                let tx = conn.transaction()?;
+
+                entry.prev_hash = tx
+                    .query_row(
+                        "SELECT hash FROM audit_log ORDER BY id DESC LIMIT 1",
+                        [],
+                        |row| row.get(0),
+                    )
+                    .optional()?
+                    .unwrap_or_else(|| AUDIT_GENESIS_HASH.to_string());
+                entry.hash = entry.compute_hash();
+
+                tx.execute(
+                    "INSERT INTO audit_log
+                 (timestamp, operation, context, namespace, kind, name, user, outcome, detail,
+                  prev_hash, hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    params![
+                        entry.timestamp,
+                        entry.operation,
+                        entry.context,
+                        entry.namespace,
+                        entry.kind,
+                        entry.name,
+                        entry.user,
+                        entry.outcome,
+                        entry.detail,
+                        entry.prev_hash,
+                        entry.hash
+                    ],
+                )?;
+                entry.id = tx.last_insert_rowid();
+                tx.commit()?;
+
+                debug!("Audited {} as entry {}", entry.operation, entry.id);
+                Ok(appended)
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Get audit entries matching a filter, oldest first
+    pub fn query_audit_log(&self, filter: &AuditFilter) -> SqlResult<Vec<AuditEntry>> {
+        let conn = self.reader()?;
+
+        // The newest `limit` entries, in chain order
+        let mut stmt = conn.prepare_cached(
+            "SELECT * FROM (
+                SELECT id, timestamp, operation, context, namespace, kind, name, user, outcome,
+                       detail, prev_hash, hash
+                FROM audit_log
+                WHERE (?1 IS NULL OR context = ?1)
+                  AND (?2 IS NULL OR operation = ?2)
+                  AND (?3 IS NULL OR timestamp >= ?3)
+                  AND (?4 IS NULL OR timestamp <= ?4)
+                ORDER BY id DESC
+                LIMIT ?5
+             ) ORDER BY id",
+        )?;
+
+        let limit = filter.limit.map(|l| l as i64).unwrap_or(-1);
+        let entry_iter = stmt.query_map(
+            params![
+                filter.context,
+                filter.operation,
+                filter.since.as_deref().map(timestamp_bound),
+                filter.until.as_deref().map(timestamp_bound),
+                limit
+            ],
+            audit_entry_from_row,
+        )?;
+
+        let mut results = Vec::new();
+        for entry in entry_iter {
+            results.push(entry?);
+        }
+
+        Ok(results)
+    }
+
+    /// Check every entry's hash and its link to the entry before
+    pub fn verify_audit_log(&self) -> SqlResult<AuditVerification> {
+        let conn = self.reader()?;
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, timestamp, operation, context, namespace, kind, name, user, outcome,
+                    detail, prev_hash, hash
+             FROM audit_log ORDER BY id",
+        )?;
+
+        let mut entries = 0;
+        let mut prev_hash = AUDIT_GENESIS_HASH.to_string();
+        for entry in stmt.query_map([], audit_entry_from_row)? {
+            let entry = entry?;
+            entries += 1;
+            if entry.prev_hash != prev_hash || entry.compute_hash() != entry.hash {
+                return Ok(AuditVerification {
+                    entries,
+                    valid: false,
+                    first_invalid_id: Some(entry.id),
+                });
+            }
+            prev_hash = entry.hash;
+        }
+
+        Ok(AuditVerification {
+            entries,
+            valid: true,
+            first_invalid_id: None,
+        })
+    }
+
+    /// Add a port-forward to the registry, returning its id
+    pub async fn save_port_forward(&self, forward: &SavedPortForward) -> SqlResult<i64> {
+        let forward = forward.clone();
+
+        self.write(move |conn| {
+            conn.execute(
+                "INSERT INTO port_forwards
+                 (context, namespace, target_kind, target_name, address, local_port, remote_port,
+                  restore, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    forward.context,
+                    forward.namespace,
+                    forward.target_kind,
+                    forward.target_name,
+                    forward.address,
+                    forward.local_port,
+                    forward.remote_port,
+                    forward.restore,
+                    forward.created_at
+                ],
+            )?;
+
+            let id = conn.last_insert_rowid();
+            debug!("Saved port-forward {}", id);
+            Ok(id)
+        })
+        .await
+    }
+
+    /// Remove a port-forward from the registry
+    pub async fn delete_port_forward(&self, id: i64) -> SqlResult<()> {
+        self.write(move |conn| {
+            conn.execute("DELETE FROM port_forwards WHERE id = ?1", params![id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Get every registered port-forward, oldest first
+    pub fn get_port_forwards(&self) -> SqlResult<Vec<SavedPortForward>> {
+        let conn = self.reader()?;
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT id, context, namespace, target_kind, target_name, address, local_port,
+                    remote_port, restore, created_at
+             FROM port_forwards
+             ORDER BY id",
+        )?;
+
+        let forward_iter = stmt.query_map([], |row| {
+            Ok(SavedPortForward {
+                id: row.get(0)?,
+                context: row.get(1)?,
+                namespace: row.get(2)?,
+                target_kind: row.get(3)?,
+                target_name: row.get(4)?,
+                address: row.get(5)?,
+                local_port: row.get(6)?,
+                remote_port: row.get(7)?,
+                restore: row.get(8)?,
+                created_at: row.get(9)?,
+            })
+        })?;
+
+        let mut results = Vec::new();
+        for forward in forward_iter {
+            results.push(forward?);
+        }
+
+        Ok(results)
+    }
+}
+
+impl DatabaseStatus {
+    fn new() -> Self {
+        DatabaseStatus {
+            schema_version: 0,
+            latest_version: SCHEMA_VERSION,
+            backup_path: None,
+            error: None,
+        }
+    }
+}
+
+/// Bring the schema up to `SCHEMA_VERSION`
+///
+/// Databases holding data are copied next to the original first. Pending
+/// migrations then run in a single transaction, so a failure leaves the
+/// database as it was.
+fn migrate(conn: &mut Connection, db_path: &Path, status: &mut DatabaseStatus) -> SqlResult<()> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    status.schema_version = version;
+    if version > SCHEMA_VERSION {
+        return Err(db_error(format!(
+            "Database schema version {} is newer than the {} this version of Kui supports",
+            version, SCHEMA_VERSION
+        )));
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let has_tables: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table')",
+        [],
+        |row| row.get(0),
+    )?;
+    if has_tables {
+        let path = backup_path(db_path, version);
+        backup(conn, &path)?;
+        info!("Backed up command palette database to {:?}", path);
+        status.backup_path = Some(path.display().to_string());
+    }
+
+    let tx = conn.transaction()?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        info!(
+            "Migrating command palette database to version {}: {}",
+            migration.version, migration.description
+        );
+        tx.execute_batch(migration.sql).map_err(|e| {
+            db_error(format!(
+                "Migration to version {} ({}) failed: {}",
+                migration.version, migration.description, e
+            ))
+        })?;
+        if let Some(backfill) = migration.backfill {
+            backfill(&tx)?;
+        }
+        tx.pragma_update(None, "user_version", migration.version)?;
+    }
+    tx.commit()?;
+
+    status.schema_version = SCHEMA_VERSION;
+    Ok(())
+}
+
+/// Where the copy of a database at `version` goes, next to the database
+fn backup_path(db_path: &Path, version: i64) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(format!(".v{}.bak", version));
+    PathBuf::from(path)
+}
+
+/// Copy the database to `path`, replacing an older copy
+fn backup(conn: &Connection, path: &Path) -> SqlResult<()> {
+    if path.exists() {
+        std::fs::remove_file(path)
+            .map_err(|e| db_error(format!("Failed to remove old backup: {}", e)))?;
+    }
+    conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+    Ok(())
+}
+
+fn db_error(message: impl Into<String>) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ABORT),
+        Some(message.into()),
+    )
+}
+
+/// Frecency rank key after a use at `time`, in Unix seconds
+///
+/// The key is log2 of the sum over all uses of 2^(time / half-life). Keys
+/// never need decaying: ordering by key orders by decayed score at any
+/// later time, and one update per use maintains it.
+fn frecency_add(key: Option<f64>, time: f64, half_life: f64) -> f64 {
+    let point = time / half_life;
+    match key {
+        None => point,
+        Some(key) => {
+            let (high, low) = if key > point {
+                (key, point)
+            } else {
+                (point, key)
+            };
+            high + (low - high).exp2().ln_1p() / std::f64::consts::LN_2
+        }
+    }
+}
+
+/// Frecency score of a rank key at `now`, in Unix seconds
+fn frecency_score(key: f64, now: f64, half_life: f64) -> f64 {
+    (key - now / half_life).exp2()
+}
+
+/// Frecency half-life, in seconds
+fn frecency_half_life(conn: &Connection) -> SqlResult<f64> {
+    let hours: Option<String> = conn
+        .prepare_cached("SELECT value FROM palette_settings WHERE key = ?1")?
+        .query_row(params![FRECENCY_HALF_LIFE_SETTING], |row| row.get(0))
+        .optional()?;
+    let hours = hours
+        .and_then(|hours| hours.parse().ok())
+        .unwrap_or(DEFAULT_FRECENCY_HALF_LIFE_HOURS);
+    Ok(hours * 3600.0)
+}
+
+/// Recompute every frecency rank key for the current half-life
+///
+/// Commands are replayed from their history. Resources only keep a count
+/// and their last access, so all their accesses are taken to have happened
+/// then.
+fn rebuild_frecency(conn: &Connection) -> SqlResult<()> {
+    let half_life = frecency_half_life(conn)?;
+
+    let mut keys: HashMap<String, f64> = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT command_id, timestamp FROM command_invocations
+         WHERE success = 1
+         ORDER BY id",
+    )?;
+    let uses = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?;
+    for command_use in uses {
+        let (command_id, timestamp): (String, String) = command_use?;
+        let Some(time) = parse_unix_time(&timestamp) else {
+            continue;
+        };
+        let key = keys.get(&command_id).copied();
+        keys.insert(command_id, frecency_add(key, time, half_life));
+    }
+
+    conn.execute("DELETE FROM command_frecency", [])?;
+    let mut insert =
+        conn.prepare("INSERT INTO command_frecency (command_id, frecency) VALUES (?1, ?2)")?;
+    for (command_id, key) in &keys {
+        insert.execute(params![command_id, key])?;
+    }
+
+    let mut stmt = conn.prepare("SELECT id, access_count, timestamp FROM recent_resources")?;
+    let resources = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut update = conn.prepare("UPDATE recent_resources SET frecency = ?2 WHERE id = ?1")?;
+    for (id, access_count, timestamp) in resources {
+        let time = parse_unix_time(&timestamp).unwrap_or(0.0);
+        let key = (access_count.max(1) as f64).log2() + time / half_life;
+        update.execute(params![id, key])?;
+    }
+
+    info!(
+        "Rebuilt frecency of {} commands for a half-life of {} hours",
+        keys.len(),
+        half_life / 3600.0
+    );
+    Ok(())
+}
+
+/// Format a time as stored, so stored times compare as strings
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Bring an RFC 3339 filter bound into the stored format; bounds that
+/// don't parse are compared as given
+fn timestamp_bound(bound: &str) -> String {
+    match DateTime::parse_from_rfc3339(bound) {
+        Ok(time) => timestamp(&time.with_timezone(&Utc)),
+        Err(_) => bound.to_string(),
+    }
+}
+
+fn unix_time(time: &DateTime<Utc>) -> f64 {
+    time.timestamp_millis() as f64 / 1000.0
+}
+
+fn parse_unix_time(timestamp: &str) -> Option<f64> {
+    let time = DateTime::parse_from_rfc3339(timestamp).ok()?;
+    Some(unix_time(&time.with_timezone(&Utc)))
+}
+
+/// Turns frecency rank keys read from the database into scores
+struct FrecencyClock {
+    now: f64,
+    half_life: f64,
+}
+
+impl FrecencyClock {
+    fn new(conn: &Connection) -> SqlResult<Self> {
+        Ok(FrecencyClock {
+            now: unix_time(&Utc::now()),
+            half_life: frecency_half_life(conn)?,
+        })
+    }
+
+    fn score(&self, key: Option<f64>) -> Option<f64> {
+        key.map(|key| frecency_score(key, self.now, self.half_life))
+    }
+}
+
+fn command_stats_from_row(row: &rusqlite::Row, clock: &FrecencyClock) -> SqlResult<CommandStats> {
+    Ok(CommandStats {
+        command_id: row.get(0)?,
+        hit_count: row.get(1)?,
+        last_used: row.get(2)?,
+        avg_execution_time: row.get(3)?,
+        frecency: clock.score(row.get(4)?),
+    })
+}
+
+fn resource_from_row(row: &rusqlite::Row, clock: &FrecencyClock) -> SqlResult<ResourceSummary> {
+    Ok(ResourceSummary {
+        kind: row.get(0)?,
+        name: row.get(1)?,
+        namespace: row.get(2)?,
+        context: row.get(3)?,
+        last_accessed: row.get(4)?,
+        access_count: row.get(5)?,
+        frecency: clock.score(row.get(6)?),
+    })
+}
+
+/// Lowercased whitespace-separated terms of a search query
+fn search_terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(str::to_lowercase).collect()
+}
+
+/// FTS5 query requiring every term, each quoted as a phrase so that
+/// operators and punctuation in it are taken literally
+fn fts_phrases(terms: &[&String]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Spans of `text` where any of the lowercased `terms` occurs, merged where
+/// they overlap
+fn highlights(field: &str, text: &str, terms: &[String]) -> Vec<MatchHighlight> {
+    let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let text: Vec<char> = text.chars().map(fold).collect();
+
+    let mut spans = Vec::new();
+    for term in terms {
+        let term: Vec<char> = term.chars().collect();
+        if term.is_empty() || term.len() > text.len() {
+            continue;
+        }
+        for start in 0..=text.len() - term.len() {
+            if text[start..start + term.len()] == term[..] {
+                spans.push((start, start + term.len()));
+            }
+        }
+    }
+    spans.sort_unstable();
+
+    let mut merged: Vec<MatchHighlight> = Vec::new();
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.end => last.end = last.end.max(end),
+            _ => merged.push(MatchHighlight {
+                field: field.to_string(),
+                start,
+                end,
+            }),
+        }
+    }
+    merged
+}
+
+fn audit_entry_from_row(row: &rusqlite::Row) -> SqlResult<AuditEntry> {
+    Ok(AuditEntry {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        operation: row.get(2)?,
+        context: row.get(3)?,
+        namespace: row.get(4)?,
+        kind: row.get(5)?,
+        name: row.get(6)?,
+        user: row.get(7)?,
+        outcome: row.get(8)?,
+        detail: row.get(9)?,
+        prev_hash: row.get(10)?,
+        hash: row.get(11)?,
+    })
+}
+
+fn rollup_from_row(row: &rusqlite::Row) -> SqlResult<EventRollup> {
+    Ok(EventRollup {
+        namespace: row.get(0)?,
+        kind: row.get(1)?,
+        name: row.get(2)?,
+        event_type: row.get(3)?,
+        reason: row.get(4)?,
+        note: row.get(5)?,
+        count: row.get(6)?,
+        first_seen: row.get(7)?,
+        last_seen: row.get(8)?,
+    })
+}
+
+/// Tauri command: Get the state of the command palette database
+#[tauri::command]
+pub async fn get_command_palette_db_status(
+    db: State<'_, CommandPaletteDb>,
+) -> Result<DatabaseStatus, String> {
+    Ok(db.status().clone())
 }
 
 /// Tauri command: Record a command invocation
+///
+/// The scope's context and namespace default to the kubeconfig's current ones.
 #[tauri::command]
 pub async fn record_command_invocation(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
     command_id: String,
     execution_time_ms: Option<i64>,
     success: bool,
     error_message: Option<String>,
-    context: Option<String>,
+    arguments: Option<String>,
+    scope: Option<CommandScope>,
 ) -> Result<(), String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
     db.record_invocation(
         &command_id,
         execution_time_ms,
         success,
         error_message.as_deref(),
-        context.as_deref(),
+        arguments.as_deref(),
+        &scope.unwrap_or_default().or_current(),
     )
+    .await
     .map_err(|e| format!("Failed to record command invocation: {}", e))
 }
 
+/// Tauri command: Search command history
+#[tauri::command]
+pub async fn search_command_history(
+    db: State<'_, CommandPaletteDb>,
+    query: String,
+    filter: Option<HistorySearchFilter>,
+) -> Result<Vec<HistoryMatch>, String> {
+    db.search_command_history(&query, &filter.unwrap_or_default())
+        .map_err(|e| format!("Failed to search command history: {}", e))
+}
+
 /// Tauri command: Get command statistics
 #[tauri::command]
 pub async fn get_command_stats(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
     command_id: Option<String>,
 ) -> Result<Vec<CommandStats>, String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
     db.get_command_stats(command_id.as_deref())
         .map_err(|e| format!("Failed to get command stats: {}", e))
 }
 
 /// Tauri command: Get top commands
 #[tauri::command]
-pub async fn get_top_commands(app: AppHandle, limit: usize) -> Result<Vec<CommandStats>, String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
-    db.get_top_commands(limit)
+pub async fn get_top_commands(
+    db: State<'_, CommandPaletteDb>,
+    limit: usize,
+    rank_by: Option<RankBy>,
+) -> Result<Vec<CommandStats>, String> {
+    db.get_top_commands(limit, rank_by.unwrap_or_default())
         .map_err(|e| format!("Failed to get top commands: {}", e))
 }
 
+/// Tauri command: Get the frecency half-life, in hours
+#[tauri::command]
+pub async fn get_frecency_half_life(db: State<'_, CommandPaletteDb>) -> Result<f64, String> {
+    db.get_frecency_half_life()
+        .map_err(|e| format!("Failed to get frecency half-life: {}", e))
+}
+
+/// Tauri command: Set the frecency half-life, in hours
+#[tauri::command]
+pub async fn set_frecency_half_life(
+    db: State<'_, CommandPaletteDb>,
+    hours: f64,
+) -> Result<(), String> {
+    db.set_frecency_half_life(hours)
+        .await
+        .map_err(|e| format!("Failed to set frecency half-life: {}", e))
+}
+
 /// Tauri command: Record a search query
 #[tauri::command]
 pub async fn record_search_query(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
     query: String,
     result_count: i32,
 ) -> Result<(), String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
     db.record_query(&query, result_count)
+        .await
         .map_err(|e| format!("Failed to record query: {}", e))
 }
 
 /// Tauri command: Get recent queries
 #[tauri::command]
-pub async fn get_recent_queries(app: AppHandle, limit: usize) -> Result<Vec<RecentQuery>, String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
+pub async fn get_recent_queries(
+    db: State<'_, CommandPaletteDb>,
+    limit: usize,
+) -> Result<Vec<RecentQuery>, String> {
     db.get_recent_queries(limit)
         .map_err(|e| format!("Failed to get recent queries: {}", e))
 }
 
 /// Tauri command: Cleanup old data
 #[tauri::command]
-pub async fn cleanup_command_palette_data(app: AppHandle) -> Result<(), String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
+pub async fn cleanup_command_palette_data(db: State<'_, CommandPaletteDb>) -> Result<(), String> {
     db.cleanup_old_data()
+        .await
         .map_err(|e| format!("Failed to cleanup old data: {}", e))
 }
 
 /// Tauri command: Record a resource access
 #[tauri::command]
 pub async fn record_resource_access(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
+    clients: State<'_, KubeClients>,
     kind: String,
     name: String,
     namespace: Option<String>,
     context: Option<String>,
 ) -> Result<(), String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
+    // Store `po`, `pods` and `Pod` under one kind
+    let kind = clients.canonical_kind(context.as_deref(), &kind).await;
     db.record_resource_access(&kind, &name, namespace.as_deref(), context.as_deref())
+        .await
         .map_err(|e| format!("Failed to record resource access: {}", e))
 }
 
 /// Tauri command: Get recent resources
 #[tauri::command]
 pub async fn get_recent_resources(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
+    clients: State<'_, KubeClients>,
     limit: usize,
     kind_filter: Option<String>,
 ) -> Result<Vec<ResourceSummary>, String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
+    let kind_filter = match kind_filter {
+        Some(kind) => Some(clients.canonical_kind(None, &kind).await),
+        None => None,
+    };
     db.get_recent_resources(limit, kind_filter.as_deref())
         .map_err(|e| format!("Failed to get recent resources: {}", e))
 }
@@ -921,38 +2491,39 @@ pub async fn get_recent_resources(
 /// Tauri command: Get top accessed resources
 #[tauri::command]
 pub async fn get_top_resources(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
+    clients: State<'_, KubeClients>,
     limit: usize,
     kind_filter: Option<String>,
+    rank_by: Option<RankBy>,
 ) -> Result<Vec<ResourceSummary>, String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
-    db.get_top_resources(limit, kind_filter.as_deref())
+    let kind_filter = match kind_filter {
+        Some(kind) => Some(clients.canonical_kind(None, &kind).await),
+        None => None,
+    };
+    db.get_top_resources(limit, kind_filter.as_deref(), rank_by.unwrap_or_default())
         .map_err(|e| format!("Failed to get top resources: {}", e))
 }
 
 /// Tauri command: Detect command patterns
 #[tauri::command]
 pub async fn detect_command_patterns(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
     min_sequence_length: usize,
     max_sequence_length: usize,
 ) -> Result<Vec<CommandPattern>, String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
     db.detect_patterns(min_sequence_length, max_sequence_length)
+        .await
         .map_err(|e| format!("Failed to detect patterns: {}", e))
 }
 
 /// Tauri command: Get command patterns
 #[tauri::command]
 pub async fn get_command_patterns(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
     min_confidence: f64,
     limit: usize,
 ) -> Result<Vec<CommandPattern>, String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
     db.get_patterns(min_confidence, limit)
         .map_err(|e| format!("Failed to get patterns: {}", e))
 }
@@ -960,24 +2531,296 @@ pub async fn get_command_patterns(
 /// Tauri command: Get pattern suggestions
 #[tauri::command]
 pub async fn get_pattern_suggestions(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
     last_commands: Vec<String>,
     limit: usize,
 ) -> Result<Vec<PatternSuggestion>, String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
     db.get_pattern_suggestions(last_commands, limit)
         .map_err(|e| format!("Failed to get pattern suggestions: {}", e))
 }
 
+/// Tauri command: Predict the next command
+///
+/// The scope's context and namespace default to the kubeconfig's current ones.
+#[tauri::command]
+pub async fn predict_next_commands(
+    db: State<'_, CommandPaletteDb>,
+    last_commands: Option<Vec<String>>,
+    scope: Option<CommandScope>,
+    limit: Option<usize>,
+) -> Result<Vec<CommandPrediction>, String> {
+    db.predict_next_commands(
+        last_commands,
+        &scope.unwrap_or_default().or_current(),
+        limit.unwrap_or(5),
+    )
+    .map_err(|e| format!("Failed to predict next commands: {}", e))
+}
+
 /// Tauri command: Get command history for fuzzy search
 #[tauri::command]
 pub async fn get_command_history(
-    app: AppHandle,
+    db: State<'_, CommandPaletteDb>,
     limit: usize,
 ) -> Result<Vec<CommandHistory>, String> {
-    let db = CommandPaletteDb::new(&app).map_err(|e| format!("Database error: {}", e))?;
-
     db.get_command_history(limit)
         .map_err(|e| format!("Failed to get command history: {}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_writes_visible_to_reads() {
+        let dir = std::env::temp_dir().join(format!("kui-palette-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = CommandPaletteDb::open(&dir.join("command-palette.db"));
+
+        db.record_invocation(
+            "get pods",
+            Some(12),
+            true,
+            None,
+            None,
+            &CommandScope::default(),
+        )
+        .await
+        .unwrap();
+        db.record_invocation(
+            "get pods",
+            Some(8),
+            true,
+            None,
+            None,
+            &CommandScope::default(),
+        )
+        .await
+        .unwrap();
+        db.record_invocation(
+            "describe pod",
+            None,
+            false,
+            Some("boom"),
+            None,
+            &CommandScope::default(),
+        )
+        .await
+        .unwrap();
+
+        let top = db.get_top_commands(10, RankBy::Count).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].command_id, "get pods");
+        assert_eq!(top[0].hit_count, 2);
+        let top = db.get_top_commands(10, RankBy::Frecency).unwrap();
+        assert_eq!(top.len(), 1);
+        assert!((top[0].frecency.unwrap() - 2.0).abs() < 0.01);
+
+        // The cached prediction model picks up new history
+        let predicted = |db: &CommandPaletteDb| -> Vec<String> {
+            db.predict_next_commands(Some(Vec::new()), &CommandScope::default(), 5)
+                .unwrap()
+                .into_iter()
+                .map(|p| p.command_id)
+                .collect()
+        };
+        assert_eq!(predicted(&db), vec!["get pods"]);
+        db.record_invocation("logs", None, true, None, None, &CommandScope::default())
+            .await
+            .unwrap();
+        assert!(predicted(&db).contains(&"logs".to_string()));
+
+        // Commands without history left don't take up slots
+        db.write(|conn| {
+            conn.execute(
+                "DELETE FROM command_invocations WHERE command_id = 'get pods'",
+                [],
+            )
+        })
+        .await
+        .unwrap();
+        let top = db.get_top_commands(1, RankBy::Frecency).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].command_id, "logs");
+
+        // The read connection refuses writes
+        let read_only = db
+            .reader()
+            .unwrap()
+            .execute("DELETE FROM command_invocations", []);
+        assert!(read_only.is_err());
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrations() {
+        let dir = std::env::temp_dir().join(format!("kui-migrate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("command-palette.db");
+
+        // A database from before versioning
+        let conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE recent_queries (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 query TEXT NOT NULL,
+                 timestamp TEXT NOT NULL,
+                 result_count INTEGER NOT NULL DEFAULT 0
+             );
+             INSERT INTO recent_queries (query, timestamp) VALUES ('pods', '2025-01-01');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let db = CommandPaletteDb::open(&db_path);
+        let status = db.status().clone();
+        assert_eq!(status.error, None);
+        assert_eq!(status.schema_version, SCHEMA_VERSION);
+        let backup = status.backup_path.unwrap();
+        assert!(backup.ends_with("command-palette.db.v0.bak"));
+        assert_eq!(db.get_recent_queries(10).unwrap().len(), 1);
+        drop(db);
+
+        // Up to date: no new backup
+        std::fs::remove_file(&backup).unwrap();
+        let db = CommandPaletteDb::open(&db_path);
+        assert_eq!(db.status().backup_path, None);
+        drop(db);
+
+        // Written by a newer build: unavailable, not clobbered
+        let conn = Connection::open(&db_path).unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        drop(conn);
+        let db = CommandPaletteDb::open(&db_path);
+        assert!(db.status().error.is_some());
+        assert!(db.get_recent_queries(10).is_err());
+        drop(db);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_search_command_history() {
+        let dir = std::env::temp_dir().join(format!("kui-search-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = CommandPaletteDb::open(&dir.join("command-palette.db"));
+
+        let in_context = |context: &str| CommandScope {
+            context: Some(context.to_string()),
+            ..Default::default()
+        };
+        db.record_invocation(
+            "get",
+            None,
+            true,
+            None,
+            Some("pods -n shop"),
+            &in_context("prod"),
+        )
+        .await
+        .unwrap();
+        db.record_invocation(
+            "logs",
+            None,
+            false,
+            None,
+            Some("deploy/shop-api"),
+            &in_context("dev"),
+        )
+        .await
+        .unwrap();
+        db.record_invocation(
+            "describe",
+            None,
+            true,
+            None,
+            Some("node a1"),
+            &in_context("dev"),
+        )
+        .await
+        .unwrap();
+
+        let found = db
+            .search_command_history("SHOP", &HistorySearchFilter::default())
+            .unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|m| m.score > 0.0));
+        let logs = found.iter().find(|m| m.command_id == "logs").unwrap();
+        assert_eq!(
+            logs.highlights,
+            vec![MatchHighlight {
+                field: "arguments".to_string(),
+                start: 7,
+                end: 11,
+            }]
+        );
+
+        // Short terms filter without the index
+        let found = db
+            .search_command_history("shop a1", &HistorySearchFilter::default())
+            .unwrap();
+        assert!(found.is_empty());
+        let found = db
+            .search_command_history("a1", &HistorySearchFilter::default())
+            .unwrap();
+        assert_eq!(found[0].command_id, "describe");
+
+        let filter = HistorySearchFilter {
+            success: Some(true),
+            context: Some("prod".to_string()),
+            ..Default::default()
+        };
+        let found = db.search_command_history("shop", &filter).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].command_id, "get");
+
+        // Bounds in other offsets compare by time, not as strings
+        let minute_ago = Utc::now() - chrono::Duration::minutes(1);
+        let east = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        let filter = HistorySearchFilter {
+            since: Some(minute_ago.with_timezone(&east).to_rfc3339()),
+            ..Default::default()
+        };
+        let found = db.search_command_history("", &filter).unwrap();
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().all(|m| m.timestamp.ends_with('Z')));
+        let filter = HistorySearchFilter {
+            until: Some(minute_ago.to_rfc3339()),
+            ..Default::default()
+        };
+        assert!(db.search_command_history("", &filter).unwrap().is_empty());
+
+        drop(db);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_frecency() {
+        let day = 86400.0;
+        let half_life = 7.0 * day;
+        let now = 400.0 * day;
+
+        // 500 uses a year ago against 20 today
+        let old = (0..500).fold(None, |key, i| {
+            Some(frecency_add(
+                key,
+                now - 365.0 * day + f64::from(i),
+                half_life,
+            ))
+        });
+        let new = (0..20).fold(None, |key, i| {
+            Some(frecency_add(key, now - f64::from(i), half_life))
+        });
+        assert!(new.unwrap() > old.unwrap());
+
+        // A use counts 1 when new and halves every half-life
+        let key = frecency_add(None, now, half_life);
+        assert!((frecency_score(key, now, half_life) - 1.0).abs() < 1e-9);
+        assert!((frecency_score(key, now + half_life, half_life) - 0.5).abs() < 1e-9);
+        let key = frecency_add(Some(key), now, half_life);
+        assert!((frecency_score(key, now, half_life) - 2.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,482 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scoped filesystem access for the renderer
+//!
+//! Every path coming from the webview is resolved against an allow-list of
+//! roots: the app data dir, the notebooks dir and any path the user picked
+//! through a native dialog. Writes go through a temp file and a rename so a
+//! crash never leaves a half-written notebook behind.
+
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::{DialogExt, FilePath};
+use tokio::sync::oneshot;
+
+/// Error type for filesystem operations
+///
+/// Serialized as `{ "kind": "...", "path": "...", "message": "..." }` so the
+/// renderer can distinguish a missing file from a denied one.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum FsError {
+    /// The path does not exist
+    NotFound { path: String, message: String },
+    /// The OS refused access to the path
+    PermissionDenied { path: String, message: String },
+    /// The path is outside every allowed root
+    OutsideScope { path: String, message: String },
+    /// The path is malformed (relative, contains `..`, ...)
+    InvalidPath { path: String, message: String },
+    /// Any other I/O failure
+    Io { path: String, message: String },
+}
+
+impl FsError {
+    fn from_io(path: &Path, err: std::io::Error) -> Self {
+        let path = path.display().to_string();
+        let message = err.to_string();
+        match err.kind() {
+            std::io::ErrorKind::NotFound => FsError::NotFound { path, message },
+            std::io::ErrorKind::PermissionDenied => FsError::PermissionDenied { path, message },
+            _ => FsError::Io { path, message },
+        }
+    }
+
+    fn invalid(path: &str, message: &str) -> Self {
+        FsError::InvalidPath {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { path, message } => write!(f, "Not found: {}: {}", path, message),
+            Self::PermissionDenied { path, message } => {
+                write!(f, "Permission denied: {}: {}", path, message)
+            }
+            Self::OutsideScope { path, message } => {
+                write!(f, "Access denied: {}: {}", path, message)
+            }
+            Self::InvalidPath { path, message } => write!(f, "Invalid path: {}: {}", path, message),
+            Self::Io { path, message } => write!(f, "I/O error: {}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for FsError {}
+
+/// Result type for filesystem operations
+pub type FsResult<T> = Result<T, FsError>;
+
+/// Directory entry returned by `read_dir`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub is_file: bool,
+    pub size: u64,
+    /// Modification time in milliseconds since the epoch
+    pub modified: Option<u64>,
+}
+
+/// Directory holding saved notebooks, relative to the home directory
+const NOTEBOOKS_DIR: &str = ".kui/notebooks";
+
+/// Allow-list of filesystem roots the renderer may touch
+///
+/// Clones share the allow-list, so one can be moved to a blocking thread.
+#[derive(Clone)]
+pub struct FsScope {
+    roots: Arc<RwLock<Vec<PathBuf>>>,
+}
+
+impl FsScope {
+    /// Create the scope with the app data dir and the notebooks dir
+    pub fn new(app: &AppHandle) -> tauri::Result<Self> {
+        let app_data_dir = app.path().app_data_dir()?;
+        let notebooks_dir = app.path().home_dir()?.join(NOTEBOOKS_DIR);
+
+        for dir in [&app_data_dir, &notebooks_dir] {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        Ok(Self::with_roots(vec![app_data_dir, notebooks_dir]))
+    }
+
+    /// Create a scope from an explicit list of roots
+    pub fn with_roots(roots: Vec<PathBuf>) -> Self {
+        let scope = FsScope {
+            roots: Arc::new(RwLock::new(Vec::new())),
+        };
+        for root in roots {
+            scope.allow(&root);
+        }
+        scope
+    }
+
+    /// Add a root to the allow-list
+    pub fn allow(&self, path: &Path) {
+        let root = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        info!("Allowing filesystem access to {:?}", root);
+
+        let mut roots = self.roots.write().unwrap();
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+
+    /// Resolve a renderer-supplied path and check it against the allow-list
+    ///
+    /// The path does not have to exist yet: its longest existing ancestor is
+    /// canonicalized (resolving symlinks) and the remaining components are
+    /// appended verbatim.
+    pub fn resolve(&self, path: &str) -> FsResult<PathBuf> {
+        let requested = Path::new(path);
+        if !requested.is_absolute() {
+            return Err(FsError::invalid(path, "path must be absolute"));
+        }
+
+        if requested
+            .components()
+            .any(|c| matches!(c, Component::ParentDir))
+        {
+            return Err(FsError::invalid(
+                path,
+                "parent directory references are not allowed",
+            ));
+        }
+
+        let mut existing = requested.to_path_buf();
+        let mut missing = Vec::new();
+        while !existing.exists() {
+            match (existing.file_name(), existing.parent()) {
+                (Some(name), Some(parent)) => {
+                    missing.push(name.to_os_string());
+                    existing = parent.to_path_buf();
+                }
+                _ => return Err(FsError::invalid(path, "no existing ancestor")),
+            }
+        }
+
+        let mut resolved = existing
+            .canonicalize()
+            .map_err(|e| FsError::from_io(&existing, e))?;
+        for name in missing.into_iter().rev() {
+            resolved.push(name);
+        }
+
+        let roots = self.roots.read().unwrap();
+        if roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(resolved)
+        } else {
+            Err(FsError::OutsideScope {
+                path: path.to_string(),
+                message: "path is outside the allowed directories".to_string(),
+            })
+        }
+    }
+
+    pub fn read_file(&self, path: &str) -> FsResult<String> {
+        let resolved = self.resolve(path)?;
+        std::fs::read_to_string(&resolved).map_err(|e| FsError::from_io(&resolved, e))
+    }
+
+    pub fn write_file(&self, path: &str, contents: &str) -> FsResult<()> {
+        let resolved = self.resolve(path)?;
+        if let Some(parent) = resolved.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| FsError::from_io(parent, e))?;
+        }
+        write_atomic(&resolved, contents.as_bytes()).map_err(|e| FsError::from_io(&resolved, e))
+    }
+
+    pub fn read_dir(&self, path: &str) -> FsResult<Vec<DirEntry>> {
+        let resolved = self.resolve(path)?;
+        let entries = std::fs::read_dir(&resolved).map_err(|e| FsError::from_io(&resolved, e))?;
+
+        let mut results = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| FsError::from_io(&resolved, e))?;
+            let metadata = entry
+                .metadata()
+                .map_err(|e| FsError::from_io(&entry.path(), e))?;
+
+            results.push(DirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                path: entry.path().display().to_string(),
+                is_dir: metadata.is_dir(),
+                is_file: metadata.is_file(),
+                size: metadata.len(),
+                modified: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as u64),
+            });
+        }
+
+        results.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(results)
+    }
+
+    pub fn remove_file(&self, path: &str) -> FsResult<()> {
+        let resolved = self.resolve(path)?;
+        std::fs::remove_file(&resolved).map_err(|e| FsError::from_io(&resolved, e))
+    }
+
+    pub fn path_exists(&self, path: &str) -> FsResult<bool> {
+        Ok(self.resolve(path)?.exists())
+    }
+}
+
+/// Counter making temp file names unique within this process
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Atomically replace `path` with `contents`
+///
+/// The data is written and fsynced to a sibling temp file which is then
+/// renamed over the destination, so readers see either the old or the new
+/// contents and never a partial write. The directory is fsynced as well, so
+/// the rename itself survives a crash.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let parent = path.parent().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no parent")
+    })?;
+    let file_name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no file name")
+    })?;
+
+    let tmp_path = parent.join(format!(
+        ".{}.{}-{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp_path)?;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)?;
+        // Directories can't be opened for syncing on Windows
+        #[cfg(unix)]
+        {
+            let dir = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            std::fs::File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => debug!("Atomically wrote {} bytes to {:?}", contents.len(), path),
+        Err(_) => {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+    }
+
+    result
+}
+
+/// Run a filesystem operation on a blocking thread, so slow disks or
+/// network mounts don't stall the async runtime
+pub async fn blocking<T, F>(scope: &FsScope, op: F) -> FsResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&FsScope) -> FsResult<T> + Send + 'static,
+{
+    let scope = scope.clone();
+    tokio::task::spawn_blocking(move || op(&scope))
+        .await
+        .map_err(|e| FsError::Io {
+            path: String::new(),
+            message: e.to_string(),
+        })?
+}
+
+/// Tauri command: Read a UTF-8 file
+#[tauri::command]
+pub async fn read_file(scope: State<'_, FsScope>, path: String) -> FsResult<String> {
+    blocking(&scope, move |scope| scope.read_file(&path)).await
+}
+
+/// Tauri command: Atomically write a UTF-8 file
+#[tauri::command]
+pub async fn write_file(scope: State<'_, FsScope>, path: String, contents: String) -> FsResult<()> {
+    blocking(&scope, move |scope| scope.write_file(&path, &contents)).await
+}
+
+/// Tauri command: List a directory
+#[tauri::command]
+pub async fn read_dir(scope: State<'_, FsScope>, path: String) -> FsResult<Vec<DirEntry>> {
+    blocking(&scope, move |scope| scope.read_dir(&path)).await
+}
+
+/// Tauri command: Remove a file
+#[tauri::command]
+pub async fn remove_file(scope: State<'_, FsScope>, path: String) -> FsResult<()> {
+    blocking(&scope, move |scope| scope.remove_file(&path)).await
+}
+
+/// Tauri command: Check whether a path exists
+#[tauri::command]
+pub async fn path_exists(scope: State<'_, FsScope>, path: String) -> FsResult<bool> {
+    blocking(&scope, move |scope| scope.path_exists(&path)).await
+}
+
+/// Tauri command: Get the user's home directory
+#[tauri::command]
+pub async fn path_home_dir(app: AppHandle) -> FsResult<String> {
+    app.path()
+        .home_dir()
+        .map(|p| p.display().to_string())
+        .map_err(|e| FsError::Io {
+            path: "~".to_string(),
+            message: e.to_string(),
+        })
+}
+
+/// Tauri command: Let the user pick a file or directory and allow access to it
+///
+/// Returns `None` if the dialog was cancelled.
+#[tauri::command]
+pub async fn pick_fs_path(
+    app: AppHandle,
+    scope: State<'_, FsScope>,
+    directory: Option<bool>,
+    save: Option<bool>,
+) -> FsResult<Option<String>> {
+    // The blocking variants would park a runtime thread until the user
+    // closes the dialog
+    let (reply, picked) = oneshot::channel();
+    let done = move |path: Option<FilePath>| {
+        let _ = reply.send(path);
+    };
+    let dialog = app.dialog().file();
+    if directory.unwrap_or(false) {
+        dialog.pick_folder(done);
+    } else if save.unwrap_or(false) {
+        dialog.save_file(done);
+    } else {
+        dialog.pick_file(done);
+    }
+
+    let Some(picked) = picked.await.ok().flatten() else {
+        return Ok(None);
+    };
+
+    let path = picked.into_path().map_err(|e| FsError::InvalidPath {
+        path: String::new(),
+        message: e.to_string(),
+    })?;
+
+    scope.allow(&path);
+    Ok(Some(path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kui-fs-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_resolve_rejects_paths_outside_roots() {
+        let root = temp_root("scope");
+        std::fs::create_dir_all(root.join("allowed")).unwrap();
+        let scope = FsScope::with_roots(vec![root.join("allowed")]);
+
+        let inside = root.join("allowed/new/notebook.kui.json");
+        assert!(scope.resolve(&inside.display().to_string()).is_ok());
+
+        let outside = root.join("other.txt");
+        assert!(matches!(
+            scope.resolve(&outside.display().to_string()),
+            Err(FsError::OutsideScope { .. })
+        ));
+
+        let escape = root.join("allowed/../other.txt");
+        assert!(matches!(
+            scope.resolve(&escape.display().to_string()),
+            Err(FsError::InvalidPath { .. })
+        ));
+
+        assert!(matches!(
+            scope.resolve("relative/path"),
+            Err(FsError::InvalidPath { .. })
+        ));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_write_atomic_replaces_contents() {
+        let root = temp_root("atomic");
+        let path = root.join("file.txt");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+
+        // No temp files are left behind
+        let entries: Vec<_> = std::fs::read_dir(&root).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocking_sees_allowed_roots() {
+        let root = temp_root("blocking");
+        let scope = FsScope::with_roots(Vec::new());
+        let path = root.join("file.txt").display().to_string();
+
+        let write = path.clone();
+        let denied = blocking(&scope, move |scope| scope.write_file(&write, "x")).await;
+        assert!(matches!(denied, Err(FsError::OutsideScope { .. })));
+
+        // Roots allowed later apply to operations on blocking threads
+        scope.allow(&root);
+        let write = path.clone();
+        blocking(&scope, move |scope| scope.write_file(&write, "x"))
+            .await
+            .unwrap();
+        let contents = blocking(&scope, move |scope| scope.read_file(&path)).await;
+        assert_eq!(contents.unwrap(), "x");
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
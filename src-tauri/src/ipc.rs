@@ -17,6 +17,7 @@
 #![allow(dead_code)]
 
 use log::debug;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 /// IPC message types
@@ -26,6 +27,12 @@ pub struct IpcMessage {
     pub data: serde_json::Value,
 }
 
+/// Decode the single object argument of an exec_invoke method
+pub fn parse_args<T: DeserializeOwned>(args: &[serde_json::Value]) -> Result<T, String> {
+    let value = args.first().cloned().unwrap_or(serde_json::Value::Null);
+    serde_json::from_value(value).map_err(|e| format!("Invalid arguments: {}", e))
+}
+
 /// Initialize IPC subsystem
 pub fn init() {
     debug!("IPC module initialized");
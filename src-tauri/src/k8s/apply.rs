@@ -0,0 +1,480 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Server-side apply of YAML documents
+//!
+//! Every object in the input is first applied with `dryRun=All`, and the
+//! server's answer is diffed against the live object, so the renderer can
+//! show exactly what the API server would change, defaults and mutating
+//! webhooks included. `apply` then repeats the request for real. Objects are
+//! handled one by one and a failing object does not stop the others.
+
+use super::client::{ContextClient, KubeClients};
+use super::discovery::ApiResourceInfo;
+use super::resources::Target;
+use super::{K8sError, K8sResult};
+use crate::redact::contains_marker;
+use kube::api::{DynamicObject, Patch, PatchParams};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+/// Field manager recorded in `managedFields` for fields we apply
+pub const FIELD_MANAGER: &str = "kui";
+
+/// Annotation kubectl's client-side apply keeps a copy of the object in
+const LAST_APPLIED_ANNOTATION: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+/// Metadata the server maintains on its own; leaving it in would make every
+/// diff non-empty
+const SERVER_METADATA: [&str; 6] = [
+    "managedFields",
+    "resourceVersion",
+    "generation",
+    "uid",
+    "creationTimestamp",
+    "selfLink",
+];
+
+/// Arguments of the `diff` and `apply` methods
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyRequest {
+    /// One or more YAML documents; `List` documents are expanded
+    pub yaml: String,
+    /// Defaults to the current context
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Namespace for namespaced objects that do not set one; defaults to
+    /// the context's namespace
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Take over fields owned by other field managers instead of failing
+    /// with a conflict
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// What applying an object did, or would do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApplyStatus {
+    Created,
+    Configured,
+    Unchanged,
+    Error,
+}
+
+/// Kind of change to a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    Add,
+    Remove,
+    Replace,
+}
+
+/// One changed field
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    /// Path to the field, e.g. `spec.template.spec.containers[0].image`
+    pub path: String,
+    pub op: ChangeOp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<serde_json::Value>,
+}
+
+/// Result for one object
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectResult {
+    pub api_version: Option<String>,
+    pub kind: Option<String>,
+    pub name: Option<String>,
+    pub namespace: Option<String>,
+    pub status: ApplyStatus,
+    pub changes: Vec<FieldChange>,
+    pub error: Option<String>,
+}
+
+/// Result of a `diff` or `apply`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyOutcome {
+    pub context: String,
+    /// Whether nothing was persisted
+    pub dry_run: bool,
+    pub objects: Vec<ObjectResult>,
+}
+
+/// Dry-run every object and report the changes it would make
+pub async fn diff(clients: &KubeClients, request: &ApplyRequest) -> K8sResult<ApplyOutcome> {
+    run(clients, request, false).await
+}
+
+/// Apply every object, reporting the changes made
+pub async fn apply(clients: &KubeClients, request: &ApplyRequest) -> K8sResult<ApplyOutcome> {
+    run(clients, request, true).await
+}
+
+async fn run(
+    clients: &KubeClients,
+    request: &ApplyRequest,
+    commit: bool,
+) -> K8sResult<ApplyOutcome> {
+    let client = clients.client(request.context.as_deref()).await?;
+    let documents = parse_documents(&request.yaml)?;
+
+    let mut objects = Vec::with_capacity(documents.len());
+    for document in documents {
+        let field = |pointer: &str| {
+            document
+                .pointer(pointer)
+                .and_then(|v| v.as_str())
+                .map(String::from)
+        };
+        let mut result = ObjectResult {
+            api_version: field("/apiVersion"),
+            kind: field("/kind"),
+            name: field("/metadata/name"),
+            namespace: None,
+            status: ApplyStatus::Error,
+            changes: Vec::new(),
+            error: None,
+        };
+
+        if let Err(e) =
+            apply_object(clients, &client, request, &document, commit, &mut result).await
+        {
+            result.status = ApplyStatus::Error;
+            result.error = Some(e.to_string());
+        }
+        objects.push(result);
+    }
+
+    info!(
+        "{} {} objects in {}",
+        if commit { "Applied" } else { "Dry-ran" },
+        objects.len(),
+        client.context
+    );
+    Ok(ApplyOutcome {
+        context: client.context.clone(),
+        dry_run: !commit,
+        objects,
+    })
+}
+
+async fn apply_object(
+    clients: &KubeClients,
+    client: &ContextClient,
+    request: &ApplyRequest,
+    document: &serde_json::Value,
+    commit: bool,
+    result: &mut ObjectResult,
+) -> K8sResult<()> {
+    let object: DynamicObject = serde_json::from_value(document.clone())
+        .map_err(|e| K8sError::InvalidRequest(format!("invalid object: {}", e)))?;
+    let types = object
+        .types
+        .as_ref()
+        .ok_or_else(|| K8sError::InvalidRequest("object has no apiVersion or kind".to_string()))?;
+    let name = object
+        .metadata
+        .name
+        .as_deref()
+        .ok_or_else(|| K8sError::InvalidRequest("object has no metadata.name".to_string()))?;
+
+    let info = resolve_object(clients, client, &types.api_version, &types.kind).await?;
+    let namespace = info.namespaced.then(|| {
+        object
+            .metadata
+            .namespace
+            .clone()
+            .or_else(|| request.namespace.clone())
+            .unwrap_or_else(|| client.default_namespace().to_string())
+    });
+    result.namespace = namespace.clone();
+
+    let api = Target {
+        client: client.clone(),
+        info,
+        namespace,
+    }
+    .api();
+
+    let live = api.get_opt(name).await?;
+    let mut params = PatchParams::apply(FIELD_MANAGER).dry_run();
+    if request.force {
+        params = params.force();
+    }
+    let applied = api.patch(name, &params, &Patch::Apply(document)).await?;
+
+    let before = match live {
+        Some(ref live) => normalize(serde_json::to_value(live).unwrap_or_default()),
+        None => serde_json::Value::Object(Default::default()),
+    };
+    let after = normalize(serde_json::to_value(&applied).unwrap_or_default());
+    result.changes = diff_values(&before, &after);
+    result.status = if live.is_none() {
+        ApplyStatus::Created
+    } else if result.changes.is_empty() {
+        ApplyStatus::Unchanged
+    } else {
+        ApplyStatus::Configured
+    };
+
+    if commit {
+        params.dry_run = false;
+        api.patch(name, &params, &Patch::Apply(document)).await?;
+        debug!("Applied {} {}", types.kind, name);
+    }
+    Ok(())
+}
+
+/// Resolve an object's type, refreshing discovery once for new CRDs
+async fn resolve_object(
+    clients: &KubeClients,
+    client: &ContextClient,
+    api_version: &str,
+    kind: &str,
+) -> K8sResult<ApiResourceInfo> {
+    for refresh in [false, true] {
+        let discovery = clients.discovery(client, refresh).await?;
+        if let Some(info) = discovery.resolve_object(api_version, kind) {
+            return Ok(info);
+        }
+    }
+    Err(K8sError::UnknownResource(format!(
+        "{} {}",
+        api_version, kind
+    )))
+}
+
+/// Split a YAML stream into objects, skipping empty documents and
+/// expanding `List`s
+pub fn parse_documents(yaml: &str) -> K8sResult<Vec<serde_json::Value>> {
+    let mut objects = Vec::new();
+
+    for (index, document) in serde_yaml::Deserializer::from_str(yaml).enumerate() {
+        let value = serde_json::Value::deserialize(document)
+            .map_err(|e| K8sError::InvalidRequest(format!("document {}: {}", index + 1, e)))?;
+        // Edited `get` output still holds the masks of Secret data and of
+        // the last-applied annotation; applying it would store the masks
+        if contains_marker(&value) {
+            return Err(K8sError::InvalidRequest(format!(
+                "document {} contains redacted values; reveal them before applying",
+                index + 1
+            )));
+        }
+
+        let is_list = value
+            .get("kind")
+            .and_then(|k| k.as_str())
+            .is_some_and(|k| k.ends_with("List"))
+            && value.get("items").is_some_and(|i| i.is_array());
+        match value {
+            serde_json::Value::Null => {}
+            serde_json::Value::Object(mut map) if is_list => {
+                if let Some(serde_json::Value::Array(items)) = map.remove("items") {
+                    objects.extend(items);
+                }
+            }
+            other => objects.push(other),
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Drop the fields that change without the user changing anything
+fn normalize(mut object: serde_json::Value) -> serde_json::Value {
+    if let Some(map) = object.as_object_mut() {
+        map.remove("status");
+        if let Some(metadata) = map.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+            for field in SERVER_METADATA {
+                metadata.remove(field);
+            }
+            if let Some(annotations) = metadata
+                .get_mut("annotations")
+                .and_then(|a| a.as_object_mut())
+            {
+                annotations.remove(LAST_APPLIED_ANNOTATION);
+                if annotations.is_empty() {
+                    metadata.remove("annotations");
+                }
+            }
+        }
+    }
+    object
+}
+
+/// Field-level differences between two JSON values
+pub fn diff_values(old: &serde_json::Value, new: &serde_json::Value) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    diff_into(&mut changes, String::new(), old, new);
+    changes
+}
+
+fn diff_into(
+    changes: &mut Vec<FieldChange>,
+    path: String,
+    old: &serde_json::Value,
+    new: &serde_json::Value,
+) {
+    use serde_json::Value;
+
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = field_path(&path, key);
+                match (old.get(key), new.get(key)) {
+                    (Some(o), Some(n)) => diff_into(changes, path, o, n),
+                    (Some(o), None) => changes.push(FieldChange {
+                        path,
+                        op: ChangeOp::Remove,
+                        old: Some(o.clone()),
+                        new: None,
+                    }),
+                    (None, Some(n)) => changes.push(FieldChange {
+                        path,
+                        op: ChangeOp::Add,
+                        old: None,
+                        new: Some(n.clone()),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                let path = format!("{}[{}]", path, i);
+                match (old.get(i), new.get(i)) {
+                    (Some(o), Some(n)) => diff_into(changes, path, o, n),
+                    (Some(o), None) => changes.push(FieldChange {
+                        path,
+                        op: ChangeOp::Remove,
+                        old: Some(o.clone()),
+                        new: None,
+                    }),
+                    (None, Some(n)) => changes.push(FieldChange {
+                        path,
+                        op: ChangeOp::Add,
+                        old: None,
+                        new: Some(n.clone()),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (old, new) if old != new => changes.push(FieldChange {
+            path,
+            op: ChangeOp::Replace,
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// Append a key to a field path, quoting keys like `app.kubernetes.io/name`
+fn field_path(parent: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match (parent.is_empty(), plain) {
+        (true, true) => key.to_string(),
+        (false, true) => format!("{}.{}", parent, key),
+        (_, false) => format!("{}[{:?}]", parent, key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_documents() {
+        let yaml = "---\napiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: a\n---\n---\n\
+                    apiVersion: v1\nkind: List\nitems:\n- apiVersion: v1\n  kind: Secret\n  \
+                    metadata:\n    name: b\n";
+        let objects = parse_documents(yaml).unwrap();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0]["kind"], "ConfigMap");
+        assert_eq!(objects[1]["kind"], "Secret");
+
+        assert!(parse_documents("a: [").is_err());
+
+        let yaml = "apiVersion: v1\nkind: Secret\nmetadata:\n  name: b\n\
+                    stringData:\n  password: '[REDACTED:password:17]'\n";
+        assert!(parse_documents(yaml).is_err());
+    }
+
+    #[test]
+    fn test_diff_values() {
+        let old = normalize(json!({
+            "metadata": {
+                "name": "web",
+                "resourceVersion": "41",
+                "labels": {"app.kubernetes.io/name": "web"}
+            },
+            "spec": {"replicas": 2, "ports": [80, 443]},
+            "status": {"ready": 2}
+        }));
+        let new = normalize(json!({
+            "metadata": {
+                "name": "web",
+                "resourceVersion": "42",
+                "labels": {"app.kubernetes.io/name": "web", "tier": "front"}
+            },
+            "spec": {"replicas": 3, "ports": [80]},
+            "status": {"ready": 3}
+        }));
+
+        assert_eq!(
+            diff_values(&old, &new),
+            vec![
+                FieldChange {
+                    path: "metadata.labels.tier".to_string(),
+                    op: ChangeOp::Add,
+                    old: None,
+                    new: Some(json!("front")),
+                },
+                FieldChange {
+                    path: "spec.ports[1]".to_string(),
+                    op: ChangeOp::Remove,
+                    old: Some(json!(443)),
+                    new: None,
+                },
+                FieldChange {
+                    path: "spec.replicas".to_string(),
+                    op: ChangeOp::Replace,
+                    old: Some(json!(2)),
+                    new: Some(json!(3)),
+                },
+            ]
+        );
+        assert_eq!(
+            field_path("metadata.labels", "app.kubernetes.io/name"),
+            "metadata.labels[\"app.kubernetes.io/name\"]"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Audit log of the mutating operations the backend performs or brokers
+//!
+//! Applies, deletes, exec sessions and port-forwards are recorded once they
+//! have run, successful or not, together with the kubeconfig user they ran
+//! as. Entries are stored in the command palette database, which refuses
+//! updates and deletes of the log, and are hash-chained so that tampering
+//! with the file itself shows up in `verify_audit_log`.
+
+use super::apply::{ApplyOutcome, ApplyRequest, ApplyStatus};
+use super::kubeconfig;
+use super::resources::{DeleteOutcome, DeleteRequest};
+use super::K8sResult;
+use crate::command_palette::{AuditEntry, AuditFilter, AuditVerification, CommandPaletteDb};
+use crate::redact::mask_text;
+use chrono::{SecondsFormat, Utc};
+use log::error;
+use std::fmt::Display;
+use tauri::{AppHandle, Manager, State};
+
+/// An operation to record
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    operation: &'static str,
+    context: Option<String>,
+    namespace: Option<String>,
+    kind: Option<String>,
+    name: Option<String>,
+    outcome: &'static str,
+    detail: Option<String>,
+}
+
+impl AuditEvent {
+    /// An operation in a context, the current one if `None`
+    pub fn new(operation: &'static str, context: Option<&str>) -> Self {
+        AuditEvent {
+            operation,
+            context: context.map(String::from),
+            namespace: None,
+            kind: None,
+            name: None,
+            outcome: "success",
+            detail: None,
+        }
+    }
+
+    /// The object operated on
+    pub fn object(
+        mut self,
+        kind: Option<&str>,
+        namespace: Option<&str>,
+        name: Option<&str>,
+    ) -> Self {
+        self.kind = kind.map(String::from);
+        self.namespace = namespace.map(String::from);
+        self.name = name.map(String::from);
+        self
+    }
+
+    /// Outcome of the operation, with the error as detail if it failed
+    pub fn result<T, E: Display>(mut self, result: &Result<T, E>) -> Self {
+        if let Err(e) = result {
+            self.outcome = "failure";
+            self.detail = Some(e.to_string());
+        }
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        if dry_run && self.outcome == "success" {
+            self.outcome = "dry-run";
+        }
+        self
+    }
+}
+
+/// Append an operation to the audit log
+///
+/// Failing to audit doesn't fail the operation, which has already run; it
+/// is logged as an error.
+pub async fn record(app: &AppHandle, event: AuditEvent) {
+    let (context, user) = identity(event.context);
+    let mut entry = AuditEntry {
+        id: 0,
+        timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        operation: event.operation.to_string(),
+        context,
+        namespace: event.namespace,
+        kind: event.kind,
+        name: event.name,
+        user,
+        outcome: event.outcome.to_string(),
+        detail: event.detail.as_deref().map(mask_text),
+        prev_hash: String::new(),
+        hash: String::new(),
+    };
+
+    if let Err(e) = app
+        .state::<CommandPaletteDb>()
+        .append_audit_entry(&mut entry)
+        .await
+    {
+        error!(
+            "Failed to audit {} of {:?} {:?}: {}",
+            entry.operation, entry.kind, entry.name, e
+        );
+    }
+}
+
+/// Record each object of an apply
+pub async fn record_apply(
+    app: &AppHandle,
+    request: &ApplyRequest,
+    result: &K8sResult<ApplyOutcome>,
+) {
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(_) => {
+            let event = AuditEvent::new("apply", request.context.as_deref())
+                .object(None, request.namespace.as_deref(), None)
+                .result(result);
+            return record(app, event).await;
+        }
+    };
+
+    for object in &outcome.objects {
+        let event = AuditEvent::new("apply", Some(&outcome.context))
+            .object(
+                object.kind.as_deref(),
+                object.namespace.as_deref(),
+                object.name.as_deref(),
+            )
+            .dry_run(outcome.dry_run);
+        let event = match (object.status, &object.error) {
+            (ApplyStatus::Error, error) => AuditEvent {
+                outcome: "failure",
+                detail: error.clone(),
+                ..event
+            },
+            (ApplyStatus::Created, _) => event.detail("created"),
+            (ApplyStatus::Configured, _) => event.detail("configured"),
+            (ApplyStatus::Unchanged, _) => event.detail("unchanged"),
+        };
+        record(app, event).await;
+    }
+}
+
+/// Record a delete
+pub async fn record_delete(
+    app: &AppHandle,
+    request: &DeleteRequest,
+    result: &K8sResult<DeleteOutcome>,
+) {
+    let event = match result {
+        Ok(outcome) => AuditEvent::new("delete", Some(&outcome.context))
+            .object(
+                Some(&outcome.kind),
+                outcome.namespace.as_deref(),
+                Some(&outcome.name),
+            )
+            .detail(outcome.status.clone()),
+        Err(_) => {
+            let query = &request.query;
+            AuditEvent::new("delete", query.context.as_deref()).object(
+                Some(&query.kind),
+                query.namespace.as_deref(),
+                query.name.as_deref(),
+            )
+        }
+    };
+    record(app, event.result(result).dry_run(request.dry_run)).await;
+}
+
+/// Resolve the context an operation ran in and the kubeconfig user of it
+fn identity(context: Option<String>) -> (Option<String>, Option<String>) {
+    let Ok(config) = kubeconfig::load() else {
+        return (context, None);
+    };
+    let context = context.or(config.current_context);
+    let user = config
+        .contexts
+        .iter()
+        .find(|named| Some(&named.name) == context.as_ref())
+        .and_then(|named| named.context.as_ref())
+        .and_then(|c| c.user.clone());
+    (context, user)
+}
+
+/// Serialize entries as JSON Lines
+fn to_jsonl(entries: &[AuditEntry]) -> Result<String, serde_json::Error> {
+    let mut jsonl = String::new();
+    for entry in entries {
+        jsonl.push_str(&serde_json::to_string(entry)?);
+        jsonl.push('\n');
+    }
+    Ok(jsonl)
+}
+
+/// Tauri command: Get audit entries, oldest first
+#[tauri::command]
+pub async fn query_audit_log(
+    db: State<'_, CommandPaletteDb>,
+    filter: Option<AuditFilter>,
+) -> Result<Vec<AuditEntry>, String> {
+    db.query_audit_log(&filter.unwrap_or_default())
+        .map_err(|e| format!("Failed to query audit log: {}", e))
+}
+
+/// Tauri command: Export audit entries as JSON Lines, oldest first
+#[tauri::command]
+pub async fn export_audit_log(
+    db: State<'_, CommandPaletteDb>,
+    filter: Option<AuditFilter>,
+) -> Result<String, String> {
+    let entries = db
+        .query_audit_log(&filter.unwrap_or_default())
+        .map_err(|e| format!("Failed to query audit log: {}", e))?;
+    to_jsonl(&entries).map_err(|e| format!("Failed to export audit log: {}", e))
+}
+
+/// Tauri command: Check the audit log's hash chain
+#[tauri::command]
+pub async fn verify_audit_log(
+    db: State<'_, CommandPaletteDb>,
+) -> Result<AuditVerification, String> {
+    db.verify_audit_log()
+        .map_err(|e| format!("Failed to verify audit log: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_palette::AUDIT_GENESIS_HASH;
+
+    #[test]
+    fn test_hash_chain() {
+        let mut first = AuditEntry {
+            id: 1,
+            timestamp: "2025-03-01T10:00:00.000Z".to_string(),
+            operation: "delete".to_string(),
+            context: Some("prod".to_string()),
+            namespace: Some("shop".to_string()),
+            kind: Some("Pod".to_string()),
+            name: Some("web-0".to_string()),
+            user: Some("admin".to_string()),
+            outcome: "success".to_string(),
+            detail: Some("deleted".to_string()),
+            prev_hash: AUDIT_GENESIS_HASH.to_string(),
+            hash: String::new(),
+        };
+        first.hash = first.compute_hash();
+        assert_eq!(first.hash.len(), 64);
+
+        let mut second = AuditEntry {
+            id: 2,
+            operation: "exec".to_string(),
+            prev_hash: first.hash.clone(),
+            ..first.clone()
+        };
+        second.hash = second.compute_hash();
+        assert_ne!(first.hash, second.hash);
+
+        // Any edit changes the hash
+        let mut tampered = first.clone();
+        tampered.outcome = "failure".to_string();
+        assert_ne!(tampered.compute_hash(), first.hash);
+
+        let jsonl = to_jsonl(&[first, second]).unwrap();
+        assert_eq!(jsonl.lines().count(), 2);
+        let parsed: AuditEntry = serde_json::from_str(jsonl.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(parsed.compute_hash(), parsed.hash);
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-context Kubernetes clients built from the user's kubeconfig
+
+use super::discovery::ApiDiscovery;
+use super::kubeconfig;
+use super::schema::{self, ResponseCache};
+use super::{K8sError, K8sResult};
+use kube::config::KubeConfigOptions;
+use kube::{Client, Config};
+use log::{info, warn};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Mutex;
+
+/// Name of the discovery result in the response cache
+const DISCOVERY_CACHE: &str = "discovery";
+
+/// A client bound to a kubeconfig context
+#[derive(Clone)]
+pub struct ContextClient {
+    /// Name of the kubeconfig context
+    pub context: String,
+    pub client: Client,
+}
+
+impl ContextClient {
+    /// Namespace configured for the context, `default` if none
+    pub fn default_namespace(&self) -> &str {
+        self.client.default_namespace()
+    }
+}
+
+/// Cache of clients and discovery results, keyed by context name
+///
+/// Building a client re-reads the kubeconfig and sets up a TLS stack, so
+/// clients are created lazily and reused across requests and windows.
+///
+/// The maps are only locked to look up and insert; building a client, which
+/// may run an exec auth plugin, and discovery are serialized per context, so
+/// a slow cluster holds up requests to itself and not to the others.
+pub struct KubeClients {
+    clients: Mutex<HashMap<String, ContextClient>>,
+    discovery: Mutex<HashMap<String, Arc<ApiDiscovery>>>,
+    client_setup: SetupLocks,
+    discovery_setup: SetupLocks,
+    /// Root of the per-context response caches, set once the app's cache
+    /// directory is known
+    cache_dir: OnceLock<PathBuf>,
+}
+
+/// One lock per context
+#[derive(Default)]
+struct SetupLocks(std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>);
+
+impl SetupLocks {
+    fn get(&self, context: &str) -> Arc<Mutex<()>> {
+        let mut locks = self.0.lock().unwrap();
+        Arc::clone(locks.entry(context.to_string()).or_default())
+    }
+}
+
+impl Default for KubeClients {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KubeClients {
+    pub fn new() -> Self {
+        KubeClients {
+            clients: Mutex::new(HashMap::new()),
+            discovery: Mutex::new(HashMap::new()),
+            client_setup: SetupLocks::default(),
+            discovery_setup: SetupLocks::default(),
+            cache_dir: OnceLock::new(),
+        }
+    }
+
+    /// Keep discovery and OpenAPI responses on disk under `dir`
+    pub fn set_cache_dir(&self, dir: PathBuf) {
+        ResponseCache::remove_stale(&dir);
+        let _ = self.cache_dir.set(dir);
+    }
+
+    /// Response cache of a context, if caching is set up
+    pub fn response_cache(&self, context: &str) -> Option<ResponseCache> {
+        self.cache_dir
+            .get()
+            .map(|dir| ResponseCache::new(dir, context))
+    }
+
+    /// Name of the current context, without building a client
+    pub fn current_context(&self) -> K8sResult<String> {
+        kubeconfig::load()?
+            .current_context
+            .ok_or_else(|| K8sError::Config("no current context is set".to_string()))
+    }
+
+    /// Get the client for a context, or for the current context if `None`
+    pub async fn client(&self, context: Option<&str>) -> K8sResult<ContextClient> {
+        let kubeconfig = kubeconfig::load()?;
+        let context = match context {
+            Some(context) => context.to_string(),
+            None => kubeconfig
+                .current_context
+                .clone()
+                .ok_or_else(|| K8sError::Config("no current context is set".to_string()))?,
+        };
+
+        if let Some(client) = self.clients.lock().await.get(&context) {
+            return Ok(client.clone());
+        }
+
+        // Another request may have built it while this one waited
+        let setup = self.client_setup.get(&context);
+        let _setup = setup.lock().await;
+        if let Some(client) = self.clients.lock().await.get(&context) {
+            return Ok(client.clone());
+        }
+
+        let options = KubeConfigOptions {
+            context: Some(context.clone()),
+            ..Default::default()
+        };
+        let config = Config::from_custom_kubeconfig(kubeconfig, &options)
+            .await
+            .map_err(|e| K8sError::Config(e.to_string()))?;
+        let client = Client::try_from(config).map_err(|e| K8sError::Config(e.to_string()))?;
+
+        info!("Created Kubernetes client for context {}", context);
+        let client = ContextClient {
+            context: context.clone(),
+            client,
+        };
+        self.clients.lock().await.insert(context, client.clone());
+        Ok(client)
+    }
+
+    /// Get API discovery for a context, running it on first use
+    ///
+    /// With `refresh` set, discovery is re-run even if a result is cached,
+    /// e.g. after a lookup missed a freshly installed CRD.
+    pub async fn discovery(
+        &self,
+        client: &ContextClient,
+        refresh: bool,
+    ) -> K8sResult<Arc<ApiDiscovery>> {
+        let cached = || async {
+            match refresh {
+                true => None,
+                false => self.discovery.lock().await.get(&client.context).cloned(),
+            }
+        };
+        if let Some(cached) = cached().await {
+            return Ok(cached);
+        }
+
+        // Another request may have run it while this one waited
+        let setup = self.discovery_setup.get(&client.context);
+        let _setup = setup.lock().await;
+        if let Some(cached) = cached().await {
+            return Ok(cached);
+        }
+
+        let cache = self.response_cache(&client.context);
+        let result = Arc::new(ApiDiscovery::run(&client.client, cache.as_ref()).await?);
+        if let Some(cache) = cache {
+            if let Err(e) = cache.put(DISCOVERY_CACHE, &*result) {
+                warn!("Failed to cache discovery of {}: {}", client.context, e);
+            }
+
+            // Keep the schemas of everything discovered for offline validation
+            let client = client.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = schema::prefetch(&cache, &client.client).await {
+                    warn!(
+                        "Failed to cache OpenAPI schemas of {}: {}",
+                        client.context, e
+                    );
+                }
+            });
+        }
+        self.discovery
+            .lock()
+            .await
+            .insert(client.context.clone(), Arc::clone(&result));
+        Ok(result)
+    }
+
+    /// Get API discovery for a context without contacting the cluster
+    ///
+    /// Falls back to the result cached on disk by an earlier run.
+    pub async fn cached_discovery(&self, context: &str) -> Option<Arc<ApiDiscovery>> {
+        if let Some(cached) = self.discovery.lock().await.get(context) {
+            return Some(Arc::clone(cached));
+        }
+        self.response_cache(context)?
+            .read(DISCOVERY_CACHE)
+            .map(Arc::new)
+    }
+
+    /// Canonical kind for a resource name typed by the user (`po` → `Pod`)
+    ///
+    /// Only consults cached discovery; names that can't be resolved are
+    /// returned as given.
+    pub async fn canonical_kind(&self, context: Option<&str>, name: &str) -> String {
+        let context = match context {
+            Some(context) => context.to_string(),
+            None => match self.current_context() {
+                Ok(context) => context,
+                Err(_) => return name.to_string(),
+            },
+        };
+        self.cached_discovery(&context)
+            .await
+            .and_then(|discovery| discovery.resolve(name).map(|r| r.kind.clone()))
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    /// Get the OpenAPI schema of a kind
+    ///
+    /// With `online` unset, only the response cache is consulted, so this
+    /// works without access to the cluster.
+    pub async fn schema(
+        &self,
+        context: Option<&str>,
+        api_version: &str,
+        kind: &str,
+        online: bool,
+    ) -> K8sResult<Option<Value>> {
+        let context = match context {
+            Some(context) => context.to_string(),
+            None => self.current_context()?,
+        };
+        let cache = self
+            .response_cache(&context)
+            .ok_or_else(|| K8sError::Uncached(format!("schema of {}", kind)))?;
+        let client = if online {
+            Some(self.client(Some(&context)).await?.client)
+        } else {
+            None
+        };
+
+        let document = schema::fetch_document(&cache, client.as_ref(), api_version).await?;
+        Ok(document.and_then(|document| schema::find_schema(&document, api_version, kind)))
+    }
+
+    /// Drop the cached client and discovery for a context, so the next
+    /// request picks up kubeconfig changes
+    pub async fn invalidate(&self, context: &str) {
+        self.clients.lock().await.remove(context);
+        self.discovery.lock().await.remove(context);
+    }
+}
@@ -0,0 +1,363 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Kubernetes API discovery
+//!
+//! Maps the names users type (`po`, `pods`, `Deployment`, `deploy.apps`) to
+//! the group/version/resource the API server actually serves.
+
+use super::client::KubeClients;
+use super::schema::ResponseCache;
+use super::{K8sError, K8sResult};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIGroupList, APIResourceList, APIVersions};
+use kube::discovery::ApiResource;
+use kube::Client;
+use log::{debug, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// An API group and the versions the server offers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiGroupInfo {
+    /// Group name, empty for the core group
+    pub name: String,
+    pub versions: Vec<String>,
+    pub preferred_version: String,
+}
+
+/// One resource type served by the API server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiResourceInfo {
+    /// API group, empty for the core group
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+    /// Plural resource name used in URLs, e.g. `deployments`
+    pub plural: String,
+    pub singular: String,
+    pub short_names: Vec<String>,
+    pub namespaced: bool,
+    pub verbs: Vec<String>,
+}
+
+impl ApiResourceInfo {
+    /// `group/version`, or just `version` for the core group
+    pub fn api_version(&self) -> String {
+        if self.group.is_empty() {
+            self.version.clone()
+        } else {
+            format!("{}/{}", self.group, self.version)
+        }
+    }
+
+    /// Convert to the descriptor used by kube's dynamic API
+    pub fn api_resource(&self) -> ApiResource {
+        ApiResource {
+            group: self.group.clone(),
+            version: self.version.clone(),
+            api_version: self.api_version(),
+            kind: self.kind.clone(),
+            plural: self.plural.clone(),
+        }
+    }
+
+    /// Whether the server allows `verb` on this resource
+    pub fn supports(&self, verb: &str) -> bool {
+        self.verbs.iter().any(|v| v == verb)
+    }
+}
+
+/// Result of discovery against one cluster
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiDiscovery {
+    /// API groups, core group first
+    #[serde(default)]
+    pub groups: Vec<ApiGroupInfo>,
+    /// Resources in preference order: core group first, then the order the
+    /// server lists its groups in
+    pub resources: Vec<ApiResourceInfo>,
+}
+
+impl ApiDiscovery {
+    /// Run discovery for the preferred version of every API group
+    ///
+    /// With a `cache`, responses are revalidated against the cached copies
+    /// instead of being fetched in full.
+    pub async fn run(client: &Client, cache: Option<&ResponseCache>) -> K8sResult<Self> {
+        let mut groups = Vec::new();
+        let mut lists = Vec::new();
+
+        let core: APIVersions = get(client, cache, "/api").await?;
+        for version in &core.versions {
+            lists.push(get(client, cache, &format!("/api/{}", version)).await?);
+        }
+        if let Some(preferred) = core.versions.first() {
+            groups.push(ApiGroupInfo {
+                name: String::new(),
+                versions: core.versions.clone(),
+                preferred_version: preferred.clone(),
+            });
+        }
+
+        let group_list: APIGroupList = get(client, cache, "/apis").await?;
+        let fetches = group_list.groups.iter().filter_map(|group| {
+            let version = group
+                .preferred_version
+                .as_ref()
+                .or_else(|| group.versions.first())?;
+            Some(async move {
+                let path = format!("/apis/{}", version.group_version);
+                let result: K8sResult<APIResourceList> = get(client, cache, &path).await;
+                (version.group_version.clone(), result)
+            })
+        });
+
+        // Aggregated APIs (e.g. metrics-server) can be unavailable without the
+        // rest of the cluster being broken, so skip groups that fail
+        for (group_version, result) in futures::future::join_all(fetches).await {
+            match result {
+                Ok(list) => lists.push(list),
+                Err(e) => warn!("Discovery failed for {}: {}", group_version, e),
+            }
+        }
+
+        for group in &group_list.groups {
+            let versions: Vec<String> = group.versions.iter().map(|v| v.version.clone()).collect();
+            let preferred_version = group
+                .preferred_version
+                .as_ref()
+                .map(|v| v.version.clone())
+                .or_else(|| versions.first().cloned())
+                .unwrap_or_default();
+            groups.push(ApiGroupInfo {
+                name: group.name.clone(),
+                versions,
+                preferred_version,
+            });
+        }
+
+        let resources: Vec<_> = lists.iter().flat_map(resources_of).collect();
+        debug!("Discovered {} API resources", resources.len());
+        Ok(ApiDiscovery { groups, resources })
+    }
+
+    /// Resolve a user-supplied resource name
+    ///
+    /// Accepts plural, singular, kind and short names, case-insensitively,
+    /// optionally qualified with a group (`deploy.apps`) or version and group
+    /// (`deployments.v1.apps`).
+    pub fn resolve(&self, name: &str) -> Option<&ApiResourceInfo> {
+        let name = name.to_lowercase();
+
+        // Unqualified names take priority, so `pods` never looks for a group
+        if let Some(found) = self.find(&name, None, None) {
+            return Some(found);
+        }
+
+        let (resource, qualifier) = name.split_once('.')?;
+        match qualifier.split_once('.') {
+            Some((version, group)) if self.has_version(version, group) => {
+                self.find(resource, Some(group), Some(version))
+            }
+            _ => self.find(resource, Some(qualifier), None),
+        }
+    }
+
+    /// Find the resource serving objects of the given `apiVersion` and `kind`
+    ///
+    /// Discovery only covers the preferred version of each group, so this
+    /// matches on group and kind and takes the version from `api_version`.
+    pub fn resolve_object(&self, api_version: &str, kind: &str) -> Option<ApiResourceInfo> {
+        let (group, version) = api_version.split_once('/').unwrap_or(("", api_version));
+        self.resources
+            .iter()
+            .find(|r| r.group == group && r.kind == kind)
+            .map(|r| ApiResourceInfo {
+                version: version.to_string(),
+                ..r.clone()
+            })
+    }
+
+    fn has_version(&self, version: &str, group: &str) -> bool {
+        self.resources
+            .iter()
+            .any(|r| r.group == group && r.version == version)
+    }
+
+    fn find(
+        &self,
+        name: &str,
+        group: Option<&str>,
+        version: Option<&str>,
+    ) -> Option<&ApiResourceInfo> {
+        let candidates = || {
+            self.resources.iter().filter(move |r| {
+                group.is_none_or(|g| r.group == g) && version.is_none_or(|v| r.version == v)
+            })
+        };
+
+        // Match the way kubectl does: exact resource names first, then kinds,
+        // then short names
+        candidates()
+            .find(|r| r.plural == name || r.singular == name)
+            .or_else(|| candidates().find(|r| r.kind.to_lowercase() == name))
+            .or_else(|| candidates().find(|r| r.short_names.iter().any(|s| s == name)))
+    }
+}
+
+/// GET a discovery endpoint, through the response cache if there is one
+async fn get<T: DeserializeOwned>(
+    client: &Client,
+    cache: Option<&ResponseCache>,
+    path: &str,
+) -> K8sResult<T> {
+    match cache {
+        Some(cache) => cache.get_json(Some(client), path).await,
+        None => {
+            let request = http::Request::get(path)
+                .body(Vec::new())
+                .map_err(|e| K8sError::InvalidRequest(e.to_string()))?;
+            Ok(client.request(request).await?)
+        }
+    }
+}
+
+/// Extract top-level resources (no subresources) from a discovery list
+fn resources_of(list: &APIResourceList) -> Vec<ApiResourceInfo> {
+    let (group, version) = match list.group_version.split_once('/') {
+        Some((group, version)) => (group.to_string(), version.to_string()),
+        None => (String::new(), list.group_version.clone()),
+    };
+
+    list.resources
+        .iter()
+        .filter(|r| !r.name.contains('/'))
+        .map(|r| ApiResourceInfo {
+            group: group.clone(),
+            version: version.clone(),
+            kind: r.kind.clone(),
+            plural: r.name.clone(),
+            singular: if r.singular_name.is_empty() {
+                r.kind.to_lowercase()
+            } else {
+                r.singular_name.clone()
+            },
+            short_names: r.short_names.clone().unwrap_or_default(),
+            namespaced: r.namespaced,
+            verbs: r.verbs.clone(),
+        })
+        .collect()
+}
+
+/// Tauri command: Get the API groups and resources of a context
+///
+/// Used for autocompletion of kinds and resource names. With `refresh`,
+/// discovery is re-run, e.g. after installing CRDs.
+#[tauri::command]
+pub async fn get_api_resources(
+    clients: State<'_, KubeClients>,
+    context: Option<String>,
+    refresh: Option<bool>,
+) -> Result<ApiDiscovery, String> {
+    let client = clients.client(context.as_deref()).await?;
+    let discovery = clients
+        .discovery(&client, refresh.unwrap_or(false))
+        .await
+        .map_err(|e| format!("Failed to discover API resources: {}", e))?;
+    Ok((*discovery).clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(
+        group: &str,
+        version: &str,
+        kind: &str,
+        plural: &str,
+        short: &[&str],
+    ) -> ApiResourceInfo {
+        ApiResourceInfo {
+            group: group.to_string(),
+            version: version.to_string(),
+            kind: kind.to_string(),
+            plural: plural.to_string(),
+            singular: kind.to_lowercase(),
+            short_names: short.iter().map(|s| s.to_string()).collect(),
+            namespaced: true,
+            verbs: vec!["get".to_string(), "list".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_resolve_names() {
+        let discovery = ApiDiscovery {
+            groups: Vec::new(),
+            resources: vec![
+                info("", "v1", "Pod", "pods", &["po"]),
+                info("", "v1", "Event", "events", &["ev"]),
+                info("apps", "v1", "Deployment", "deployments", &["deploy"]),
+                info("events.k8s.io", "v1", "Event", "events", &["ev"]),
+            ],
+        };
+
+        assert_eq!(discovery.resolve("pods").unwrap().kind, "Pod");
+        assert_eq!(discovery.resolve("Pod").unwrap().kind, "Pod");
+        assert_eq!(discovery.resolve("po").unwrap().kind, "Pod");
+        assert_eq!(discovery.resolve("deploy").unwrap().group, "apps");
+        assert_eq!(
+            discovery.resolve("deployments.apps").unwrap().kind,
+            "Deployment"
+        );
+        assert_eq!(
+            discovery.resolve("deployments.v1.apps").unwrap().kind,
+            "Deployment"
+        );
+
+        // Core group wins for ambiguous names unless a group is given
+        assert_eq!(discovery.resolve("events").unwrap().group, "");
+        assert_eq!(
+            discovery.resolve("events.events.k8s.io").unwrap().group,
+            "events.k8s.io"
+        );
+
+        assert!(discovery.resolve("widgets").is_none());
+    }
+
+    #[test]
+    fn test_resolve_object() {
+        let discovery = ApiDiscovery {
+            groups: Vec::new(),
+            resources: vec![
+                info("", "v1", "Pod", "pods", &["po"]),
+                info("apps", "v1", "Deployment", "deployments", &["deploy"]),
+            ],
+        };
+
+        assert_eq!(
+            discovery.resolve_object("v1", "Pod").unwrap().plural,
+            "pods"
+        );
+        let deploy = discovery
+            .resolve_object("apps/v1beta2", "Deployment")
+            .unwrap();
+        assert_eq!(deploy.api_version(), "apps/v1beta2");
+        assert!(discovery.resolve_object("v1", "Deployment").is_none());
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cluster event timeline
+//!
+//! A background task watches `events.k8s.io/v1` Events in all namespaces of
+//! the current context and records them in the command palette database,
+//! so questions like "why is this pod pending" can be answered without
+//! re-running `kubectl get events`, including for events the cluster has
+//! already expired. Each Event object is one row; the cluster's own
+//! deduplication (`series`) is kept as the row's count, and repeats of the
+//! same reason and note on an object are rolled up at query time.
+
+use super::client::KubeClients;
+use crate::command_palette::{ClusterEvent, CommandPaletteDb, EventReasonGroup, EventRollup};
+use crate::redact::mask_text;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::StreamExt;
+use k8s_openapi::api::events::v1::Event;
+use kube::runtime::{watcher, WatchStreamExt};
+use kube::Api;
+use log::{debug, info, warn};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Notify;
+
+/// Events kept per context; the oldest are dropped first
+const MAX_EVENTS: usize = 20_000;
+
+/// Events written to the database in one transaction
+const BATCH_SIZE: usize = 200;
+
+/// Time to wait before retrying when no client can be built
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Records the current context's events in the background
+pub struct EventTimeline {
+    /// Context currently being watched
+    context: Mutex<Option<String>>,
+    restart: Notify,
+}
+
+impl Default for EventTimeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventTimeline {
+    pub fn new() -> Self {
+        EventTimeline {
+            context: Mutex::new(None),
+            restart: Notify::new(),
+        }
+    }
+
+    /// Start the background watch
+    pub fn start(app: &AppHandle) {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { run(app).await });
+    }
+
+    /// Switch the watch over to the new current context
+    pub fn context_changed(&self) {
+        self.restart.notify_one();
+    }
+
+    /// Context whose events are being recorded
+    pub fn context(&self) -> Option<String> {
+        self.context.lock().unwrap().clone()
+    }
+}
+
+async fn run(app: AppHandle) {
+    let timeline = app.state::<EventTimeline>();
+    let clients = app.state::<KubeClients>();
+    let db = app.state::<CommandPaletteDb>();
+
+    loop {
+        let client = match clients.client(None).await {
+            Ok(client) => client,
+            Err(e) => {
+                debug!("Event timeline is idle: {}", e);
+                *timeline.context.lock().unwrap() = None;
+                tokio::select! {
+                    _ = tokio::time::sleep(RETRY_DELAY) => {}
+                    _ = timeline.restart.notified() => {}
+                }
+                continue;
+            }
+        };
+
+        info!("Recording events of context {}", client.context);
+        *timeline.context.lock().unwrap() = Some(client.context.clone());
+
+        let api: Api<Event> = Api::all(client.client.clone());
+        let events = watcher(api, watcher::Config::default())
+            .default_backoff()
+            .applied_objects()
+            .ready_chunks(BATCH_SIZE);
+        futures::pin_mut!(events);
+
+        loop {
+            tokio::select! {
+                batch = events.next() => {
+                    let Some(batch) = batch else { break };
+                    let records: Vec<ClusterEvent> = batch
+                        .into_iter()
+                        .filter_map(|event| match event {
+                            Ok(event) => to_record(&event),
+                            Err(e) => {
+                                debug!("Event watch error: {}", e);
+                                None
+                            }
+                        })
+                        .collect();
+                    if records.is_empty() {
+                        continue;
+                    }
+                    if let Err(e) = db.record_cluster_events(&client.context, &records, MAX_EVENTS).await {
+                        warn!("Failed to record cluster events: {}", e);
+                    }
+                }
+                _ = timeline.restart.notified() => {
+                    debug!("Current context changed, restarting event timeline");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Timestamp format used in the timeline; sorts correctly as text
+fn format_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Convert an Event to a timeline row, if it is about an object
+fn to_record(event: &Event) -> Option<ClusterEvent> {
+    let uid = event.metadata.uid.clone()?;
+    let regarding = event.regarding.as_ref()?;
+
+    let created = event.metadata.creation_timestamp.as_ref().map(|t| t.0);
+    let event_time = event.event_time.as_ref().map(|t| t.0);
+    let first_seen = event
+        .deprecated_first_timestamp
+        .as_ref()
+        .map(|t| t.0)
+        .or(event_time)
+        .or(created)?;
+    let last_seen = event
+        .series
+        .as_ref()
+        .map(|s| s.last_observed_time.0)
+        .or_else(|| event.deprecated_last_timestamp.as_ref().map(|t| t.0))
+        .or(event_time)
+        .unwrap_or(first_seen);
+
+    let count = event
+        .series
+        .as_ref()
+        .map(|s| s.count)
+        .or(event.deprecated_count)
+        .unwrap_or(1);
+
+    Some(ClusterEvent {
+        uid,
+        namespace: regarding
+            .namespace
+            .clone()
+            .or_else(|| event.metadata.namespace.clone()),
+        kind: regarding.kind.clone()?,
+        name: regarding.name.clone()?,
+        event_type: event.type_.clone().unwrap_or_else(|| "Normal".to_string()),
+        reason: event.reason.clone().unwrap_or_default(),
+        // Notes often echo failing command lines and env values
+        note: mask_text(event.note.as_deref().unwrap_or_default()),
+        reporting_controller: event.reporting_controller.clone(),
+        count: i64::from(count),
+        first_seen: format_time(&first_seen),
+        last_seen: format_time(&last_seen),
+    })
+}
+
+/// Context to query: the requested one, else the one being recorded
+fn query_context(timeline: &EventTimeline, context: Option<String>) -> Result<String, String> {
+    context
+        .or_else(|| timeline.context())
+        .ok_or_else(|| "No Kubernetes context is being recorded".to_string())
+}
+
+/// Start of a lookback window of `minutes`
+fn since(minutes: u32) -> String {
+    format_time(&(Utc::now() - chrono::Duration::minutes(i64::from(minutes))))
+}
+
+/// Tauri command: Get the events of one object
+#[tauri::command]
+pub async fn get_object_events(
+    db: State<'_, CommandPaletteDb>,
+    timeline: State<'_, EventTimeline>,
+    kind: String,
+    name: String,
+    namespace: Option<String>,
+    context: Option<String>,
+) -> Result<Vec<EventRollup>, String> {
+    let context = query_context(&timeline, context)?;
+
+    db.get_object_events(&context, &kind, &name, namespace.as_deref())
+        .map_err(|e| format!("Failed to get object events: {}", e))
+}
+
+/// Tauri command: Get warnings from the last `minutes`
+#[tauri::command]
+pub async fn get_recent_warnings(
+    db: State<'_, CommandPaletteDb>,
+    timeline: State<'_, EventTimeline>,
+    minutes: u32,
+    limit: Option<usize>,
+    context: Option<String>,
+) -> Result<Vec<EventRollup>, String> {
+    let context = query_context(&timeline, context)?;
+
+    db.get_recent_warnings(&context, &since(minutes), limit.unwrap_or(100))
+        .map_err(|e| format!("Failed to get recent warnings: {}", e))
+}
+
+/// Tauri command: Get events from the last `minutes` grouped by reason
+#[tauri::command]
+pub async fn get_events_by_reason(
+    db: State<'_, CommandPaletteDb>,
+    timeline: State<'_, EventTimeline>,
+    minutes: u32,
+    context: Option<String>,
+) -> Result<Vec<EventReasonGroup>, String> {
+    let context = query_context(&timeline, context)?;
+
+    db.get_events_by_reason(&context, &since(minutes))
+        .map_err(|e| format!("Failed to get events by reason: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use k8s_openapi::api::core::v1::ObjectReference;
+    use k8s_openapi::api::events::v1::EventSeries;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, Time};
+
+    #[test]
+    fn test_to_record_uses_series() {
+        let first = Utc.with_ymd_and_hms(2025, 3, 1, 10, 0, 0).unwrap();
+        let last = Utc.with_ymd_and_hms(2025, 3, 1, 10, 5, 0).unwrap();
+
+        let mut event = Event {
+            event_time: Some(MicroTime(first)),
+            regarding: Some(ObjectReference {
+                kind: Some("Pod".to_string()),
+                name: Some("web-0".to_string()),
+                namespace: Some("shop".to_string()),
+                ..Default::default()
+            }),
+            reason: Some("BackOff".to_string()),
+            note: Some("exec: curl -u admin --password=hunter22 failed".to_string()),
+            type_: Some("Warning".to_string()),
+            series: Some(EventSeries {
+                count: 7,
+                last_observed_time: MicroTime(last),
+            }),
+            ..Default::default()
+        };
+        event.metadata.uid = Some("abc".to_string());
+        event.metadata.creation_timestamp = Some(Time(first));
+
+        let record = to_record(&event).unwrap();
+        assert_eq!(record.kind, "Pod");
+        assert_eq!(record.namespace.as_deref(), Some("shop"));
+        assert_eq!(record.count, 7);
+        assert_eq!(record.first_seen, "2025-03-01T10:00:00Z");
+        assert_eq!(record.last_seen, "2025-03-01T10:05:00Z");
+        assert!(!record.note.contains("hunter22"));
+
+        event.regarding = None;
+        assert!(to_record(&event).is_none());
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Interactive exec into containers
+//!
+//! Attaches a TTY to a process in a container over the remote command
+//! websocket protocol (v5.channel.k8s.io, falling back to v4). The attached
+//! process is handed to the PTY manager, which serves it to the renderer
+//! like any local terminal session.
+
+use super::client::KubeClients;
+use super::K8sResult;
+use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use kube::api::{Api, AttachParams, AttachedProcess};
+use log::info;
+use serde::Deserialize;
+
+/// Picks the best shell the container has, like `kubectl exec -it ... -- sh`
+/// but preferring bash
+const DEFAULT_COMMAND: [&str; 3] = [
+    "/bin/sh",
+    "-c",
+    "command -v bash >/dev/null 2>&1 && exec bash || exec sh",
+];
+
+/// Arguments of the PTY `exec` method
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecRequest {
+    pub pod: String,
+    /// Defaults to the context's namespace
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Defaults to the current context
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Defaults to the pod's default container
+    #[serde(default)]
+    pub container: Option<String>,
+    /// Defaults to a shell
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub cols: Option<u16>,
+    #[serde(default)]
+    pub rows: Option<u16>,
+}
+
+/// Start a process in a container with a TTY attached
+pub async fn attach(clients: &KubeClients, request: &ExecRequest) -> K8sResult<AttachedProcess> {
+    let client = clients.client(request.context.as_deref()).await?;
+    let namespace = request
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+    let api: Api<Pod> = Api::namespaced(client.client.clone(), &namespace);
+
+    let command: Vec<String> = if request.command.is_empty() {
+        DEFAULT_COMMAND.iter().map(|s| s.to_string()).collect()
+    } else {
+        request.command.clone()
+    };
+
+    // With a TTY, stderr is merged into stdout by the container runtime
+    let mut params = AttachParams::interactive_tty();
+    if let Some(ref container) = request.container {
+        params = params.container(container);
+    }
+
+    let process = api.exec(&request.pod, command.clone(), &params).await?;
+
+    info!(
+        "Attached to {}/{} in {}: {:?}",
+        namespace, request.pod, client.context, command
+    );
+    Ok(process)
+}
+
+/// Exit code reported in the final status of a remote command
+///
+/// The API server reports success as `Success` and a non-zero exit as a
+/// `NonZeroExitCode` failure whose `ExitCode` cause carries the code.
+pub fn exit_code(status: &Status) -> Option<u32> {
+    if status.status.as_deref() == Some("Success") {
+        return Some(0);
+    }
+
+    status
+        .details
+        .as_ref()?
+        .causes
+        .as_ref()?
+        .iter()
+        .find(|cause| cause.reason.as_deref() == Some("ExitCode"))
+        .and_then(|cause| cause.message.as_deref())
+        .and_then(|code| code.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{StatusCause, StatusDetails};
+
+    #[test]
+    fn test_exit_code() {
+        let success = Status {
+            status: Some("Success".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(exit_code(&success), Some(0));
+
+        let failure = Status {
+            status: Some("Failure".to_string()),
+            reason: Some("NonZeroExitCode".to_string()),
+            details: Some(StatusDetails {
+                causes: Some(vec![StatusCause {
+                    reason: Some("ExitCode".to_string()),
+                    message: Some("130".to_string()),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(exit_code(&failure), Some(130));
+
+        let unknown = Status {
+            status: Some("Failure".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(exit_code(&unknown), None);
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! get/list across several clusters
+//!
+//! The same query runs concurrently against every selected context, each
+//! with its own timeout, and the rows are merged into one table tagged with
+//! their context. A cluster that fails or times out is reported in the
+//! error list and doesn't fail the query.
+
+use super::client::KubeClients;
+use super::kubeconfig;
+use super::resources::{self, OutputFormat, ResourceQuery, ResourceTable, TableColumn, TableRow};
+use super::{K8sError, K8sResult};
+use log::debug;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Per-cluster timeout when the query doesn't set one
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A row of a multi-context table
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextRow {
+    pub context: String,
+    #[serde(flatten)]
+    pub row: TableRow,
+}
+
+/// A cluster the query failed on
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterError {
+    pub context: String,
+    pub message: String,
+}
+
+/// Rows of several clusters, merged
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiContextTable {
+    /// Contexts the query ran against
+    pub contexts: Vec<String>,
+    /// Kind as resolved by the first cluster that answered
+    pub kind: Option<String>,
+    /// Union of the columns of all clusters; cells of each row follow this
+    /// order, with `null` for columns its cluster doesn't have
+    pub columns: Vec<TableColumn>,
+    pub rows: Vec<ContextRow>,
+    pub errors: Vec<ClusterError>,
+}
+
+/// Run a get or list across the contexts selected by the query
+///
+/// A `name` becomes a field selector, so a missing object is an empty
+/// result rather than an error. Paging is per cluster, so continue tokens
+/// are not supported.
+pub async fn list(clients: &KubeClients, query: &ResourceQuery) -> K8sResult<MultiContextTable> {
+    let available: Vec<String> = kubeconfig::load()?
+        .contexts
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+    let contexts = select_contexts(query, &available);
+    if contexts.is_empty() {
+        return Err(K8sError::InvalidRequest(
+            "no context matches the query".to_string(),
+        ));
+    }
+
+    let timeout = query
+        .timeout_seconds
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TIMEOUT);
+    let queries = contexts.iter().map(|context| {
+        let query = ResourceQuery {
+            context: Some(context.clone()),
+            contexts: None,
+            context_glob: None,
+            continue_token: None,
+            ..query.clone()
+        };
+        async move {
+            let result = tokio::time::timeout(timeout, list_context(clients, &query)).await;
+            (context.clone(), result)
+        }
+    });
+
+    let mut merged = MultiContextTable {
+        contexts: contexts.clone(),
+        kind: None,
+        columns: Vec::new(),
+        rows: Vec::new(),
+        errors: Vec::new(),
+    };
+    for (context, result) in futures::future::join_all(queries).await {
+        match result {
+            Ok(Ok(table)) => merge(&mut merged, table),
+            Ok(Err(e)) => merged.errors.push(ClusterError {
+                context,
+                message: e.to_string(),
+            }),
+            Err(_) => merged.errors.push(ClusterError {
+                context,
+                message: format!("timed out after {}s", timeout.as_secs()),
+            }),
+        }
+    }
+
+    debug!(
+        "Listed {} {} rows across {} contexts, {} failed",
+        merged.rows.len(),
+        query.kind,
+        contexts.len(),
+        merged.errors.len()
+    );
+    Ok(merged)
+}
+
+/// List in one context, as a table even with `output: json`
+async fn list_context(clients: &KubeClients, query: &ResourceQuery) -> K8sResult<ResourceTable> {
+    let target = resources::resolve(clients, query).await?;
+    let mut lp = resources::list_params(query);
+    if let Some(ref name) = query.name {
+        let name = format!("metadata.name={}", name);
+        lp = match lp.field_selector.take() {
+            Some(fields) => lp.fields(&format!("{},{}", fields, name)),
+            None => lp.fields(&name),
+        };
+    }
+
+    if query.output != Some(OutputFormat::Json) {
+        return resources::list_table(&target, &lp).await;
+    }
+
+    let list = target.api().list(&lp).await?;
+    let rows = list
+        .items
+        .into_iter()
+        .map(|object| {
+            Ok(TableRow {
+                name: object.metadata.name.clone().unwrap_or_default(),
+                namespace: object.metadata.namespace.clone(),
+                cells: Vec::new(),
+                object: serde_json::to_value(object)
+                    .map_err(|e| K8sError::Request(e.to_string()))?,
+            })
+        })
+        .collect::<K8sResult<_>>()?;
+
+    Ok(ResourceTable {
+        context: target.client.context.clone(),
+        api_version: target.info.api_version(),
+        kind: target.info.kind.clone(),
+        resource: target.info.plural.clone(),
+        namespaced: target.info.namespaced,
+        columns: Vec::new(),
+        rows,
+        resource_version: list.metadata.resource_version,
+        continue_token: None,
+    })
+}
+
+/// Contexts named in the query, then those matching its glob, without
+/// duplicates
+fn select_contexts(query: &ResourceQuery, available: &[String]) -> Vec<String> {
+    let mut selected: Vec<String> = Vec::new();
+    let named = query.contexts.iter().flatten();
+    let globbed = available.iter().filter(|name| {
+        query
+            .context_glob
+            .as_deref()
+            .is_some_and(|g| glob_match(g, name))
+    });
+
+    for context in named.chain(globbed) {
+        if !selected.contains(context) {
+            selected.push(context.clone());
+        }
+    }
+    selected
+}
+
+/// Add a cluster's table to the merged one, mapping its cells onto the
+/// merged columns by name
+fn merge(merged: &mut MultiContextTable, table: ResourceTable) {
+    if merged.kind.is_none() {
+        merged.kind = Some(table.kind.clone());
+    }
+
+    let mut positions = Vec::with_capacity(table.columns.len());
+    for column in &table.columns {
+        match merged.columns.iter().position(|c| c.name == column.name) {
+            Some(position) => positions.push(position),
+            None => {
+                positions.push(merged.columns.len());
+                merged.columns.push(column.clone());
+            }
+        }
+    }
+
+    let width = merged.columns.len();
+    for row in table.rows {
+        let mut cells = vec![serde_json::Value::Null; width];
+        for (cell, &position) in row.cells.into_iter().zip(&positions) {
+            cells[position] = cell;
+        }
+        merged.rows.push(ContextRow {
+            context: table.context.clone(),
+            row: TableRow { cells, ..row },
+        });
+    }
+
+    // Rows merged before a new column appeared are padded to the new width
+    for row in &mut merged.rows {
+        row.row.cells.resize(width, serde_json::Value::Null);
+    }
+}
+
+/// Match a name against a glob with `*` and `?` wildcards
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*`, and the name position it matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_contexts() {
+        let available: Vec<String> = ["prod-eu", "prod-us", "staging", "dev-prod"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let query = ResourceQuery {
+            kind: "pods".to_string(),
+            contexts: Some(vec!["staging".to_string(), "prod-us".to_string()]),
+            context_glob: Some("prod-*".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            select_contexts(&query, &available),
+            ["staging", "prod-us", "prod-eu"]
+        );
+
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*-prod", "dev-prod"));
+        assert!(glob_match("prod-??", "prod-eu"));
+        assert!(glob_match("p*d*", "prod-us"));
+        assert!(!glob_match("prod-?", "prod-eu"));
+        assert!(!glob_match("prod", "prod-eu"));
+    }
+}
@@ -0,0 +1,614 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Relationship graph around an object
+//!
+//! Starting from one object, follows ownerReferences up and down
+//! (Deployment → ReplicaSet → Pod), Service selectors to Pods, Pod volumes
+//! to ConfigMaps, Secrets and PVCs, and Ingress backends to Services. The
+//! graph stays within the object's namespace; each resource type is listed
+//! at most once per request.
+
+use super::client::{ContextClient, KubeClients};
+use super::discovery::{ApiDiscovery, ApiResourceInfo};
+use super::resources::{self, ResourceQuery};
+use super::{K8sError, K8sResult};
+use kube::api::{Api, DynamicObject, ListParams};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+/// Nodes after which the graph is cut off
+const MAX_NODES: usize = 500;
+
+/// Resource types that commonly carry ownerReferences to other objects
+const CHILD_RESOURCES: [&str; 3] = ["replicasets.apps", "jobs.batch", "pods"];
+
+/// Arguments of the `graph` method
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphRequest {
+    /// The object to start from; `name` is required
+    #[serde(flatten)]
+    pub query: ResourceQuery,
+}
+
+/// How two objects are related
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Relation {
+    /// `from` is listed in the ownerReferences of `to`
+    Owns,
+    /// `from` is a Service whose selector matches the Pod `to`
+    Selects,
+    /// `from` is a Pod with a volume backed by `to`
+    Mounts,
+    /// `from` is an Ingress with `to` as a backend
+    Routes,
+}
+
+/// An object in the graph
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphNode {
+    /// `Kind/namespace/name`, or `Kind/name` for cluster-scoped objects
+    pub id: String,
+    pub api_version: String,
+    pub kind: String,
+    pub name: String,
+    pub namespace: Option<String>,
+    pub uid: Option<String>,
+    /// Short status, e.g. the phase of a Pod or `2/3` ready replicas
+    pub status: Option<String>,
+    /// Referenced by another object but not found
+    pub missing: bool,
+}
+
+/// A relation between two nodes
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub relation: Relation,
+}
+
+/// Graph around one object
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceGraph {
+    pub context: String,
+    /// Id of the object the graph was built from
+    pub root: String,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    /// Whether the graph was cut off at the node limit
+    pub truncated: bool,
+}
+
+/// Build the graph around an object
+pub async fn graph(clients: &KubeClients, request: &GraphRequest) -> K8sResult<ResourceGraph> {
+    let name = request
+        .query
+        .name
+        .as_deref()
+        .ok_or_else(|| K8sError::InvalidRequest("graph requires a name".to_string()))?;
+    let target = resources::resolve(clients, &request.query).await?;
+    let root = target.api().get(name).await?;
+    let discovery = clients.discovery(&target.client, false).await?;
+
+    let mut builder = GraphBuilder::new(&target.client, discovery, target.namespace.clone());
+
+    let root_id = builder.add(&target.info, root);
+    let mut truncated = false;
+    while let Some((info, object)) = builder.queue.pop_front() {
+        if builder.nodes.len() >= MAX_NODES {
+            truncated = true;
+            break;
+        }
+        builder.expand(&info, &object).await;
+    }
+
+    debug!(
+        "Built graph of {} nodes around {} in {}",
+        builder.nodes.len(),
+        root_id,
+        target.client.context
+    );
+    Ok(ResourceGraph {
+        context: target.client.context.clone(),
+        root: root_id,
+        nodes: builder.nodes,
+        edges: builder.edge_list,
+        truncated,
+    })
+}
+
+/// A resource type and its objects in the namespace
+type Listed = (ApiResourceInfo, Arc<Vec<DynamicObject>>);
+
+struct GraphBuilder<'a> {
+    client: &'a ContextClient,
+    discovery: Arc<ApiDiscovery>,
+    /// Namespace of the root object; nothing is listed for cluster-scoped
+    /// roots
+    namespace: Option<String>,
+    /// Objects of a resource type in the namespace, listed on first use
+    lists: HashMap<&'static str, Option<Listed>>,
+    nodes: Vec<GraphNode>,
+    node_ids: HashSet<String>,
+    edges: HashSet<(String, String, Relation)>,
+    edge_list: Vec<GraphEdge>,
+    /// Objects whose relations are still to be followed
+    queue: VecDeque<(ApiResourceInfo, DynamicObject)>,
+}
+
+impl<'a> GraphBuilder<'a> {
+    fn new(
+        client: &'a ContextClient,
+        discovery: Arc<ApiDiscovery>,
+        namespace: Option<String>,
+    ) -> Self {
+        GraphBuilder {
+            client,
+            discovery,
+            namespace,
+            lists: HashMap::new(),
+            nodes: Vec::new(),
+            node_ids: HashSet::new(),
+            edges: HashSet::new(),
+            edge_list: Vec::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Add an object, queueing it for expansion if it is new; returns its id
+    fn add(&mut self, info: &ApiResourceInfo, object: DynamicObject) -> String {
+        let (id, new) = self.insert(info, &object);
+        if new {
+            self.queue.push_back((info.clone(), object));
+        }
+        id
+    }
+
+    /// Add an object without following its relations; returns its id
+    fn add_leaf(&mut self, info: &ApiResourceInfo, object: &DynamicObject) -> String {
+        self.insert(info, object).0
+    }
+
+    fn insert(&mut self, info: &ApiResourceInfo, object: &DynamicObject) -> (String, bool) {
+        let name = object.metadata.name.clone().unwrap_or_default();
+        let namespace = object.metadata.namespace.clone();
+        let id = node_id(&info.kind, namespace.as_deref(), &name);
+        if !self.node_ids.insert(id.clone()) {
+            return (id, false);
+        }
+
+        self.nodes.push(GraphNode {
+            id: id.clone(),
+            api_version: info.api_version(),
+            kind: info.kind.clone(),
+            name,
+            namespace,
+            uid: object.metadata.uid.clone(),
+            status: status_of(&info.kind, &object.data),
+            missing: false,
+        });
+        (id, true)
+    }
+
+    /// Add a node for a referenced object that doesn't exist
+    fn add_missing(&mut self, api_version: &str, kind: &str, name: &str) -> String {
+        let id = node_id(kind, self.namespace.as_deref(), name);
+        if self.node_ids.insert(id.clone()) {
+            self.nodes.push(GraphNode {
+                id: id.clone(),
+                api_version: api_version.to_string(),
+                kind: kind.to_string(),
+                name: name.to_string(),
+                namespace: self.namespace.clone(),
+                uid: None,
+                status: None,
+                missing: true,
+            });
+        }
+        id
+    }
+
+    /// Id of a referenced object that is already in the graph
+    fn known(&self, kind: &str, name: &str) -> Option<String> {
+        // References don't say whether the object is namespaced
+        [self.namespace.as_deref(), None]
+            .into_iter()
+            .map(|namespace| node_id(kind, namespace, name))
+            .find(|id| self.node_ids.contains(id))
+    }
+
+    fn link(&mut self, from: &str, to: &str, relation: Relation) {
+        if self
+            .edges
+            .insert((from.to_string(), to.to_string(), relation))
+        {
+            self.edge_list.push(GraphEdge {
+                from: from.to_string(),
+                to: to.to_string(),
+                relation,
+            });
+        }
+    }
+
+    /// Follow the relations of one object
+    async fn expand(&mut self, info: &ApiResourceInfo, object: &DynamicObject) {
+        let id = node_id(
+            &info.kind,
+            object.metadata.namespace.as_deref(),
+            object.metadata.name.as_deref().unwrap_or_default(),
+        );
+
+        for owner in object.metadata.owner_references.iter().flatten() {
+            // Owners are often already in the graph, e.g. as the parent whose
+            // children were listed to find this object
+            let owner_id = match self.known(&owner.kind, &owner.name) {
+                Some(owner_id) => owner_id,
+                None => match self.get(&owner.api_version, &owner.kind, &owner.name).await {
+                    Some((owner_info, owner_object)) => self.add(&owner_info, owner_object),
+                    None => self.add_missing(&owner.api_version, &owner.kind, &owner.name),
+                },
+            };
+            self.link(&owner_id, &id, Relation::Owns);
+        }
+
+        if let Some(ref uid) = object.metadata.uid {
+            for resource in CHILD_RESOURCES {
+                let Some((child_info, children)) = self.list(resource).await else {
+                    continue;
+                };
+                for child in children.iter().filter(|c| is_owned_by(c, uid)) {
+                    let child_id = self.add(&child_info, child.clone());
+                    self.link(&id, &child_id, Relation::Owns);
+                }
+            }
+        }
+
+        match (info.group.as_str(), info.kind.as_str()) {
+            ("", "Pod") => {
+                for (kind, name) in volume_refs(&object.data) {
+                    let target_id = match self.known(kind, &name) {
+                        Some(target_id) => target_id,
+                        None => match self.get("v1", kind, &name).await {
+                            Some((target_info, target)) => self.add_leaf(&target_info, &target),
+                            None => self.add_missing("v1", kind, &name),
+                        },
+                    };
+                    self.link(&id, &target_id, Relation::Mounts);
+                }
+
+                if let Some((service_info, services)) = self.list("services").await {
+                    let labels = object.metadata.labels.clone().unwrap_or_default();
+                    for service in services.iter() {
+                        if selects(&service.data["spec"]["selector"], &labels) {
+                            let service_id = self.add(&service_info, service.clone());
+                            self.link(&service_id, &id, Relation::Selects);
+                        }
+                    }
+                }
+            }
+            ("", "Service") => {
+                if let Some((pod_info, pods)) = self.list("pods").await {
+                    let selector = &object.data["spec"]["selector"];
+                    for pod in pods.iter() {
+                        let labels = pod.metadata.labels.clone().unwrap_or_default();
+                        if selects(selector, &labels) {
+                            let pod_id = self.add(&pod_info, pod.clone());
+                            self.link(&id, &pod_id, Relation::Selects);
+                        }
+                    }
+                }
+
+                let name = object.metadata.name.clone().unwrap_or_default();
+                if let Some((ingress_info, ingresses)) =
+                    self.list("ingresses.networking.k8s.io").await
+                {
+                    for ingress in ingresses.iter() {
+                        if ingress_services(&ingress.data).contains(&name) {
+                            let ingress_id = self.add(&ingress_info, ingress.clone());
+                            self.link(&ingress_id, &id, Relation::Routes);
+                        }
+                    }
+                }
+            }
+            ("networking.k8s.io", "Ingress") => {
+                for name in ingress_services(&object.data) {
+                    let service_id = match self.known("Service", &name) {
+                        Some(service_id) => service_id,
+                        None => match self.get("v1", "Service", &name).await {
+                            Some((service_info, service)) => self.add(&service_info, service),
+                            None => self.add_missing("v1", "Service", &name),
+                        },
+                    };
+                    self.link(&id, &service_id, Relation::Routes);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Get an object in the namespace by type and name
+    async fn get(
+        &self,
+        api_version: &str,
+        kind: &str,
+        name: &str,
+    ) -> Option<(ApiResourceInfo, DynamicObject)> {
+        let info = self.discovery.resolve_object(api_version, kind)?;
+        let client = self.client.client.clone();
+        let api: Api<DynamicObject> = match (info.namespaced, &self.namespace) {
+            (true, Some(namespace)) => {
+                Api::namespaced_with(client, namespace, &info.api_resource())
+            }
+            (true, None) => return None,
+            (false, _) => Api::all_with(client, &info.api_resource()),
+        };
+
+        match api.get_opt(name).await {
+            Ok(object) => object.map(|object| (info, object)),
+            Err(e) => {
+                warn!("Failed to get {} {}: {}", kind, name, e);
+                None
+            }
+        }
+    }
+
+    /// List a resource type in the namespace, once per graph
+    async fn list(&mut self, resource: &'static str) -> Option<Listed> {
+        if let Some(listed) = self.lists.get(resource) {
+            return listed.clone();
+        }
+
+        let listed = match (self.discovery.resolve(resource), &self.namespace) {
+            (Some(info), Some(namespace)) => {
+                let api: Api<DynamicObject> = Api::namespaced_with(
+                    self.client.client.clone(),
+                    namespace,
+                    &info.api_resource(),
+                );
+                match api.list(&ListParams::default()).await {
+                    Ok(list) => Some((info.clone(), Arc::new(list.items))),
+                    // Typically RBAC; the graph is still useful without it
+                    Err(e) => {
+                        warn!("Failed to list {} in {}: {}", resource, namespace, e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        self.lists.insert(resource, listed.clone());
+        listed
+    }
+}
+
+fn node_id(kind: &str, namespace: Option<&str>, name: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("{}/{}/{}", kind, namespace, name),
+        None => format!("{}/{}", kind, name),
+    }
+}
+
+fn is_owned_by(object: &DynamicObject, uid: &str) -> bool {
+    object
+        .metadata
+        .owner_references
+        .iter()
+        .flatten()
+        .any(|owner| owner.uid == uid)
+}
+
+/// Whether a Service selector matches a set of labels; an empty or absent
+/// selector selects nothing, as for Services without selectors
+fn selects(selector: &Value, labels: &std::collections::BTreeMap<String, String>) -> bool {
+    match selector.as_object() {
+        Some(selector) if !selector.is_empty() => selector
+            .iter()
+            .all(|(key, value)| labels.get(key).map(String::as_str) == value.as_str()),
+        _ => false,
+    }
+}
+
+/// Short status shown on a node
+fn status_of(kind: &str, data: &Value) -> Option<String> {
+    let status = &data["status"];
+    if kind == "Pod" {
+        return status["phase"].as_str().map(String::from);
+    }
+    let replicas = status["replicas"].as_i64()?;
+    let ready = status["readyReplicas"].as_i64().unwrap_or(0);
+    Some(format!("{}/{}", ready, replicas))
+}
+
+/// ConfigMaps, Secrets and PVCs that back the volumes of a Pod
+fn volume_refs(pod: &Value) -> Vec<(&'static str, String)> {
+    let mut refs = Vec::new();
+    let mut push = |kind: &'static str, name: Option<&str>| {
+        if let Some(name) = name {
+            if !refs.iter().any(|(k, n)| *k == kind && n == name) {
+                refs.push((kind, name.to_string()));
+            }
+        }
+    };
+
+    for volume in pod["spec"]["volumes"].as_array().into_iter().flatten() {
+        push("ConfigMap", volume["configMap"]["name"].as_str());
+        push("Secret", volume["secret"]["secretName"].as_str());
+        push(
+            "PersistentVolumeClaim",
+            volume["persistentVolumeClaim"]["claimName"].as_str(),
+        );
+        for source in volume["projected"]["sources"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            push("ConfigMap", source["configMap"]["name"].as_str());
+            push("Secret", source["secret"]["name"].as_str());
+        }
+    }
+    refs
+}
+
+/// Names of the Services an Ingress routes to
+fn ingress_services(ingress: &Value) -> Vec<String> {
+    let spec = &ingress["spec"];
+    let mut names: Vec<String> = spec["rules"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|rule| rule["http"]["paths"].as_array().into_iter().flatten())
+        .chain(std::iter::once(&spec["defaultBackend"]).filter(|b| b.is_object()))
+        .map(|backend| backend.get("backend").unwrap_or(backend))
+        .filter_map(|backend| backend["service"]["name"].as_str())
+        .map(String::from)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::mock;
+    use http::StatusCode;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_references() {
+        let pod = json!({
+            "spec": {
+                "volumes": [
+                    {"name": "config", "configMap": {"name": "web-config"}},
+                    {"name": "tls", "secret": {"secretName": "web-tls"}},
+                    {"name": "data", "persistentVolumeClaim": {"claimName": "web-data"}},
+                    {"name": "token", "projected": {"sources": [
+                        {"serviceAccountToken": {"path": "token"}},
+                        {"configMap": {"name": "web-config"}}
+                    ]}}
+                ]
+            }
+        });
+        assert_eq!(
+            volume_refs(&pod),
+            [
+                ("ConfigMap", "web-config".to_string()),
+                ("Secret", "web-tls".to_string()),
+                ("PersistentVolumeClaim", "web-data".to_string()),
+            ]
+        );
+
+        let ingress = json!({
+            "spec": {
+                "defaultBackend": {"service": {"name": "fallback", "port": {"number": 80}}},
+                "rules": [{"http": {"paths": [
+                    {"path": "/", "backend": {"service": {"name": "web", "port": {"number": 80}}}},
+                    {"path": "/api", "backend": {"service": {"name": "api", "port": {"name": "http"}}}}
+                ]}}]
+            }
+        });
+        assert_eq!(ingress_services(&ingress), ["api", "fallback", "web"]);
+
+        let labels = [("app".to_string(), "web".to_string())].into();
+        assert!(selects(&json!({"app": "web"}), &labels));
+        assert!(!selects(&json!({"app": "web", "tier": "db"}), &labels));
+        assert!(!selects(&json!({}), &labels));
+    }
+
+    fn resource(group: &str, kind: &str, plural: &str) -> ApiResourceInfo {
+        ApiResourceInfo {
+            group: group.to_string(),
+            version: "v1".to_string(),
+            kind: kind.to_string(),
+            plural: plural.to_string(),
+            singular: kind.to_lowercase(),
+            short_names: Vec::new(),
+            namespaced: true,
+            verbs: vec!["get".to_string(), "list".to_string()],
+        }
+    }
+
+    fn list(api_version: &str, kind: &str, items: Vec<Value>) -> Value {
+        json!({"apiVersion": api_version, "kind": kind, "metadata": {}, "items": items})
+    }
+
+    #[tokio::test]
+    async fn test_known_owner_is_not_fetched() {
+        let replica_set = json!({
+            "apiVersion": "apps/v1",
+            "kind": "ReplicaSet",
+            "metadata": {"name": "web-1", "namespace": "shop", "uid": "rs"}
+        });
+        let pod = json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": "web-1-x", "namespace": "shop", "uid": "pod",
+                "ownerReferences": [{
+                    "apiVersion": "apps/v1", "kind": "ReplicaSet", "name": "web-1", "uid": "rs"
+                }]
+            }
+        });
+        let root = replica_set.clone();
+        let (client, requests) = mock::client(move |request| match request.path.as_str() {
+            "/apis/apps/v1/namespaces/shop/replicasets" => (
+                StatusCode::OK,
+                list("apps/v1", "ReplicaSetList", vec![replica_set.clone()]),
+            ),
+            "/apis/apps/v1/namespaces/shop/replicasets/web-1" => {
+                (StatusCode::OK, replica_set.clone())
+            }
+            "/api/v1/namespaces/shop/pods" => {
+                (StatusCode::OK, list("v1", "PodList", vec![pod.clone()]))
+            }
+            _ => mock::failure(StatusCode::NOT_FOUND, "NotFound", "not found"),
+        });
+        let client = ContextClient {
+            context: "test".to_string(),
+            client,
+        };
+        let discovery = ApiDiscovery {
+            groups: Vec::new(),
+            resources: vec![
+                resource("", "Pod", "pods"),
+                resource("apps", "ReplicaSet", "replicasets"),
+            ],
+        };
+
+        let mut builder = GraphBuilder::new(&client, Arc::new(discovery), Some("shop".to_string()));
+        let info = resource("apps", "ReplicaSet", "replicasets");
+        builder.add(&info, serde_json::from_value(root).unwrap());
+        while let Some((info, object)) = builder.queue.pop_front() {
+            builder.expand(&info, &object).await;
+        }
+
+        let ids: Vec<_> = builder.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["ReplicaSet/shop/web-1", "Pod/shop/web-1-x"]);
+        assert_eq!(builder.edge_list.len(), 1);
+        assert_eq!(builder.edge_list[0].relation, Relation::Owns);
+
+        let requests = requests.lock().unwrap();
+        assert!(requests
+            .iter()
+            .all(|r| !r.path.ends_with("/replicasets/web-1")));
+    }
+}
@@ -0,0 +1,398 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resource lists cached in memory and shared across windows
+//!
+//! An informer keeps a copy of every object of one resource type in one
+//! namespace (or all of them) of a context, listed once and then kept fresh
+//! by a watch. Windows acquire an informer before reading from it and
+//! release it when done; the informer stops when no window holds it any
+//! more, including when its windows close.
+
+use super::client::KubeClients;
+use super::resources::{self, ResourceQuery, Target};
+use super::{K8sError, K8sResult};
+use futures::StreamExt;
+use kube::api::DynamicObject;
+use kube::runtime::watcher::Event;
+use kube::runtime::{watcher, WatchStreamExt};
+use kube::Api;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::watch as signal;
+use tokio::task::AbortHandle;
+
+/// How long a read waits for a new informer's initial list
+const SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Arguments of the `cache_acquire` and `cache_list` methods
+///
+/// Only the context, kind and namespace select the informer; the name and
+/// label selector filter what `cache_list` returns.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheRequest {
+    #[serde(flatten)]
+    pub query: ResourceQuery,
+}
+
+/// Returned by the `cache_acquire` method
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheSubscription {
+    /// Pass to `cache_release` when the window no longer needs the list
+    pub cache_id: String,
+}
+
+/// Objects read from an informer
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedList {
+    pub cache_id: String,
+    pub context: String,
+    pub api_version: String,
+    pub kind: String,
+    /// Newest resourceVersion the informer has seen
+    pub resource_version: Option<String>,
+    /// Sorted by namespace, then name
+    pub items: Vec<Value>,
+}
+
+/// Whether an informer can be read from yet
+#[derive(Debug, Clone, PartialEq)]
+enum Phase {
+    Syncing,
+    Synced,
+    /// The initial list failed; the informer keeps retrying
+    Failed(String),
+}
+
+/// Objects of an informer, keyed by namespace and name
+#[derive(Default)]
+struct Store {
+    objects: BTreeMap<(String, String), Value>,
+    resource_version: Option<String>,
+}
+
+/// A running informer and the windows holding it
+struct Informer {
+    context: String,
+    api_version: String,
+    kind: String,
+    store: Arc<RwLock<Store>>,
+    phase: signal::Receiver<Phase>,
+    /// Reference count per window label
+    holders: HashMap<String, usize>,
+    task: AbortHandle,
+}
+
+/// The informers of all windows, keyed by cache id
+pub struct InformerCache {
+    informers: Mutex<HashMap<String, Informer>>,
+}
+
+impl Default for InformerCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InformerCache {
+    pub fn new() -> Self {
+        InformerCache {
+            informers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Hold the informer of a query for a window, starting it if needed
+    pub async fn acquire(
+        &self,
+        clients: &KubeClients,
+        window_label: &str,
+        request: &CacheRequest,
+    ) -> K8sResult<CacheSubscription> {
+        let target = resources::resolve(clients, &cache_query(&request.query)).await?;
+        if !(target.info.supports("list") && target.info.supports("watch")) {
+            return Err(K8sError::InvalidRequest(format!(
+                "{} cannot be cached",
+                target.info.plural
+            )));
+        }
+
+        let cache_id = cache_id(&target);
+        let mut informers = self.informers.lock().unwrap();
+        let informer = informers.entry(cache_id.clone()).or_insert_with(|| {
+            info!("Starting informer {}", cache_id);
+            Informer::start(target, cache_id.clone())
+        });
+        *informer
+            .holders
+            .entry(window_label.to_string())
+            .or_default() += 1;
+
+        Ok(CacheSubscription { cache_id })
+    }
+
+    /// Drop one hold of a window on an informer
+    pub fn release(&self, cache_id: &str, window_label: &str) -> K8sResult<()> {
+        let mut informers = self.informers.lock().unwrap();
+        let informer = informers
+            .get_mut(cache_id)
+            .ok_or_else(|| K8sError::InvalidRequest(format!("Unknown cache: {}", cache_id)))?;
+
+        match informer.holders.get_mut(window_label) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                informer.holders.remove(window_label);
+            }
+            None => {
+                return Err(K8sError::InvalidRequest(format!(
+                    "{} does not hold {}",
+                    window_label, cache_id
+                )))
+            }
+        }
+
+        if informer.holders.is_empty() {
+            info!("Stopping unused informer {}", cache_id);
+            informer.task.abort();
+            informers.remove(cache_id);
+        }
+        Ok(())
+    }
+
+    /// Drop every hold of a window that is going away
+    pub fn close_window(&self, window_label: &str) {
+        self.informers.lock().unwrap().retain(|id, informer| {
+            informer.holders.remove(window_label);
+            if !informer.holders.is_empty() {
+                return true;
+            }
+            debug!("Stopping informer {} of closed window {}", id, window_label);
+            informer.task.abort();
+            false
+        });
+    }
+
+    /// Read the objects of an acquired informer, waiting for its initial
+    /// list if it has just started
+    pub async fn list(
+        &self,
+        clients: &KubeClients,
+        request: &CacheRequest,
+    ) -> K8sResult<CachedList> {
+        let query = &request.query;
+        if query.field_selector.is_some() {
+            return Err(K8sError::InvalidRequest(
+                "field selectors are not supported on cached lists".to_string(),
+            ));
+        }
+        let target = resources::resolve(clients, &cache_query(query)).await?;
+        let cache_id = cache_id(&target);
+
+        let (mut phase, store, mut list) = {
+            let informers = self.informers.lock().unwrap();
+            let informer = informers.get(&cache_id).ok_or_else(|| {
+                K8sError::InvalidRequest(format!("{} has not been acquired", cache_id))
+            })?;
+            let list = CachedList {
+                cache_id: cache_id.clone(),
+                context: informer.context.clone(),
+                api_version: informer.api_version.clone(),
+                kind: informer.kind.clone(),
+                resource_version: None,
+                items: Vec::new(),
+            };
+            (informer.phase.clone(), Arc::clone(&informer.store), list)
+        };
+
+        let ready = tokio::time::timeout(SYNC_TIMEOUT, phase.wait_for(|p| *p != Phase::Syncing))
+            .await
+            .map_err(|_| K8sError::Request(format!("timed out waiting for {} to sync", cache_id)))?
+            .map_err(|_| K8sError::Request(format!("{} was stopped", cache_id)))?
+            .clone();
+        if let Phase::Failed(message) = ready {
+            return Err(K8sError::Request(message));
+        }
+
+        let store = store.read().unwrap();
+        list.resource_version = store.resource_version.clone();
+        for ((_, name), object) in &store.objects {
+            if query.name.as_ref().is_some_and(|n| n != name) {
+                continue;
+            }
+            if let Some(ref selector) = query.label_selector {
+                if !selector_matches(selector, &object["metadata"]["labels"])? {
+                    continue;
+                }
+            }
+            list.items.push(object.clone());
+        }
+        Ok(list)
+    }
+}
+
+impl Informer {
+    fn start(target: Target, cache_id: String) -> Self {
+        let store = Arc::new(RwLock::new(Store::default()));
+        let (phase_tx, phase) = signal::channel(Phase::Syncing);
+        let task = tokio::spawn(run(target.api(), Arc::clone(&store), phase_tx, cache_id));
+
+        Informer {
+            context: target.client.context.clone(),
+            api_version: target.info.api_version(),
+            kind: target.info.kind.clone(),
+            store,
+            phase,
+            holders: HashMap::new(),
+            task: task.abort_handle(),
+        }
+    }
+}
+
+/// Keep a store in sync with the cluster until aborted
+async fn run(
+    api: Api<DynamicObject>,
+    store: Arc<RwLock<Store>>,
+    phase: signal::Sender<Phase>,
+    cache_id: String,
+) {
+    let events = watcher(api, watcher::Config::default()).default_backoff();
+    futures::pin_mut!(events);
+
+    // Objects of a (re-)list, swapped in once it completes
+    let mut listed = BTreeMap::new();
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(Event::Init) => listed.clear(),
+            Ok(Event::InitApply(object)) => {
+                let version = object.metadata.resource_version.clone();
+                listed.insert(object_key(&object), to_json(object));
+                if version.is_some() {
+                    store.write().unwrap().resource_version = version;
+                }
+            }
+            Ok(Event::InitDone) => {
+                store.write().unwrap().objects = std::mem::take(&mut listed);
+                phase.send_replace(Phase::Synced);
+                debug!("Informer {} synced", cache_id);
+            }
+            Ok(Event::Apply(object)) => {
+                let mut store = store.write().unwrap();
+                store.resource_version = object.metadata.resource_version.clone();
+                store.objects.insert(object_key(&object), to_json(object));
+            }
+            Ok(Event::Delete(object)) => {
+                let mut store = store.write().unwrap();
+                store.resource_version = object.metadata.resource_version.clone();
+                store.objects.remove(&object_key(&object));
+            }
+            Err(e) => {
+                warn!("Informer {} error: {}", cache_id, e);
+                // Once synced, readers get the last known state while the
+                // watch recovers
+                phase.send_if_modified(|p| {
+                    if *p == Phase::Synced {
+                        return false;
+                    }
+                    *p = Phase::Failed(e.to_string());
+                    true
+                });
+            }
+        }
+    }
+}
+
+/// The part of a query that selects an informer
+fn cache_query(query: &ResourceQuery) -> ResourceQuery {
+    ResourceQuery {
+        kind: query.kind.clone(),
+        namespace: query.namespace.clone(),
+        context: query.context.clone(),
+        all_namespaces: query.all_namespaces,
+        ..Default::default()
+    }
+}
+
+/// Identifies an informer: context, group/version, resource and namespace
+fn cache_id(target: &Target) -> String {
+    format!(
+        "{}/{}/{}/{}",
+        target.client.context,
+        target.info.api_version(),
+        target.info.plural,
+        target.namespace.as_deref().unwrap_or("*")
+    )
+}
+
+fn object_key(object: &DynamicObject) -> (String, String) {
+    (
+        object.metadata.namespace.clone().unwrap_or_default(),
+        object.metadata.name.clone().unwrap_or_default(),
+    )
+}
+
+fn to_json(object: DynamicObject) -> Value {
+    serde_json::to_value(object).unwrap_or(Value::Null)
+}
+
+/// Match labels against an equality-based selector: `a=b`, `a==b`, `a!=b`,
+/// `a` and `!a`, comma separated
+fn selector_matches(selector: &str, labels: &Value) -> K8sResult<bool> {
+    if selector.contains('(') {
+        return Err(K8sError::InvalidRequest(
+            "set-based label selectors are not supported on cached lists".to_string(),
+        ));
+    }
+
+    let label = |key: &str| labels[key.trim()].as_str();
+    for term in selector.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        let matches = if let Some((key, value)) = term.split_once("!=") {
+            label(key) != Some(value.trim())
+        } else if let Some((key, value)) = term.split_once('=') {
+            label(key) == Some(value.trim_start_matches('=').trim())
+        } else if let Some(key) = term.strip_prefix('!') {
+            label(key).is_none()
+        } else {
+            label(term).is_some()
+        };
+        if !matches {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_selector_matches() {
+        let labels = json!({"app": "web", "tier": "frontend"});
+        assert!(selector_matches("", &labels).unwrap());
+        assert!(selector_matches("app=web, tier", &labels).unwrap());
+        assert!(selector_matches("app==web,!canary", &labels).unwrap());
+        assert!(selector_matches("tier!=backend", &labels).unwrap());
+        assert!(!selector_matches("app=web,tier=backend", &labels).unwrap());
+        assert!(!selector_matches("!app", &labels).unwrap());
+        assert!(!selector_matches("app", &Value::Null).unwrap());
+        assert!(selector_matches("app in (web)", &labels).is_err());
+    }
+}
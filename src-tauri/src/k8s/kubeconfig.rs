@@ -0,0 +1,499 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Kubeconfig inspection and context/namespace switching
+//!
+//! Files are merged the way kubectl merges them: every path in `KUBECONFIG`
+//! is read in order and the first file to define a value wins. Edits are
+//! written back to the file kubectl would modify, atomically, and announced
+//! to every window with a `kube-context-changed` event.
+
+use super::client::KubeClients;
+use super::events::EventTimeline;
+use super::{K8sError, K8sResult};
+use crate::fs::write_atomic;
+use kube::config::{AuthInfo, Kubeconfig};
+use log::info;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// Event broadcast to all windows after the current context or a context's
+/// namespace changed
+pub const KUBE_CONTEXT_CHANGED_EVENT: &str = "kube-context-changed";
+
+/// Serializes read-modify-write cycles on kubeconfig files
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A kubeconfig context, as shown in context pickers
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextInfo {
+    pub name: String,
+    pub cluster: String,
+    pub user: Option<String>,
+    pub namespace: Option<String>,
+    pub current: bool,
+}
+
+/// A kubeconfig cluster, without certificate data
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterInfo {
+    pub name: String,
+    pub server: Option<String>,
+    pub insecure_skip_tls_verify: bool,
+    pub proxy_url: Option<String>,
+}
+
+/// A kubeconfig user, reduced to how it authenticates
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserInfo {
+    pub name: String,
+    /// `token`, `clientCertificate`, `exec`, `authProvider`, `basic` or `none`
+    pub auth_method: String,
+    /// Command of an exec plugin, e.g. `aws` or `gke-gcloud-auth-plugin`
+    pub exec_command: Option<String>,
+}
+
+/// Merged view of the user's kubeconfig files, free of secrets
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KubeconfigSummary {
+    pub files: Vec<String>,
+    pub current_context: Option<String>,
+    pub contexts: Vec<ContextInfo>,
+    pub clusters: Vec<ClusterInfo>,
+    pub users: Vec<UserInfo>,
+}
+
+/// Payload of `kube-context-changed` events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KubeContextChanged {
+    pub context: String,
+    pub namespace: Option<String>,
+}
+
+/// Kubeconfig files in merge order
+///
+/// Entries of `KUBECONFIG` that do not exist are skipped, as kubectl does.
+pub fn kubeconfig_paths() -> Vec<PathBuf> {
+    if let Some(value) = std::env::var_os("KUBECONFIG") {
+        let paths: Vec<PathBuf> = std::env::split_paths(&value)
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect();
+        if !paths.is_empty() {
+            return paths.into_iter().filter(|p| p.exists()).collect();
+        }
+    }
+
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".kube").join("config"))
+        .filter(|p| p.exists())
+        .into_iter()
+        .collect()
+}
+
+/// Load and merge all kubeconfig files
+pub fn load() -> K8sResult<Kubeconfig> {
+    load_from(&kubeconfig_paths())
+}
+
+/// Load and merge the given kubeconfig files, the first taking precedence
+pub fn load_from(paths: &[PathBuf]) -> K8sResult<Kubeconfig> {
+    if paths.is_empty() {
+        return Err(K8sError::Config("no kubeconfig file found".to_string()));
+    }
+
+    paths
+        .iter()
+        .try_fold(Kubeconfig::default(), |merged, path| {
+            Kubeconfig::read_from(path)
+                .and_then(|config| merged.merge(config))
+                .map_err(|e| K8sError::Config(format!("{}: {}", path.display(), e)))
+        })
+}
+
+/// Summarize the merged kubeconfig without exposing credentials
+pub fn summarize() -> K8sResult<KubeconfigSummary> {
+    summarize_from(&kubeconfig_paths())
+}
+
+fn summarize_from(paths: &[PathBuf]) -> K8sResult<KubeconfigSummary> {
+    let config = load_from(paths)?;
+    let current = config.current_context.clone();
+
+    let contexts = config
+        .contexts
+        .iter()
+        .map(|named| {
+            let context = named.context.as_ref();
+            ContextInfo {
+                name: named.name.clone(),
+                cluster: context.map(|c| c.cluster.clone()).unwrap_or_default(),
+                user: context.and_then(|c| c.user.clone()),
+                namespace: context.and_then(|c| c.namespace.clone()),
+                current: current.as_deref() == Some(named.name.as_str()),
+            }
+        })
+        .collect();
+
+    let clusters = config
+        .clusters
+        .iter()
+        .map(|named| {
+            let cluster = named.cluster.as_ref();
+            ClusterInfo {
+                name: named.name.clone(),
+                server: cluster.and_then(|c| c.server.clone()),
+                insecure_skip_tls_verify: cluster
+                    .and_then(|c| c.insecure_skip_tls_verify)
+                    .unwrap_or(false),
+                proxy_url: cluster.and_then(|c| c.proxy_url.clone()),
+            }
+        })
+        .collect();
+
+    let users = config
+        .auth_infos
+        .iter()
+        .map(|named| UserInfo {
+            name: named.name.clone(),
+            auth_method: auth_method(named.auth_info.as_ref()).to_string(),
+            exec_command: named
+                .auth_info
+                .as_ref()
+                .and_then(|a| a.exec.as_ref())
+                .and_then(|e| e.command.clone()),
+        })
+        .collect();
+
+    Ok(KubeconfigSummary {
+        files: paths.iter().map(|p| p.display().to_string()).collect(),
+        current_context: current,
+        contexts,
+        clusters,
+        users,
+    })
+}
+
+fn auth_method(auth: Option<&AuthInfo>) -> &'static str {
+    let Some(auth) = auth else {
+        return "none";
+    };
+
+    if auth.exec.is_some() {
+        "exec"
+    } else if auth.auth_provider.is_some() {
+        "authProvider"
+    } else if auth.token.is_some() || auth.token_file.is_some() {
+        "token"
+    } else if auth.client_certificate.is_some() || auth.client_certificate_data.is_some() {
+        "clientCertificate"
+    } else if auth.username.is_some() {
+        "basic"
+    } else {
+        "none"
+    }
+}
+
+/// Read a kubeconfig file as raw YAML, so edits preserve fields kube's
+/// typed model does not know about
+fn read_yaml(path: &Path) -> K8sResult<serde_yaml::Value> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| K8sError::Config(format!("{}: {}", path.display(), e)))?;
+    serde_yaml::from_str(&text).map_err(|e| K8sError::Config(format!("{}: {}", path.display(), e)))
+}
+
+/// Write a kubeconfig file, through the symlink if it is one, as dotfile
+/// managers set up
+fn write_yaml(path: &Path, value: &serde_yaml::Value) -> K8sResult<()> {
+    let text = serde_yaml::to_string(value).map_err(|e| K8sError::Config(e.to_string()))?;
+    let target = std::fs::canonicalize(path)
+        .map_err(|e| K8sError::Config(format!("{}: {}", path.display(), e)))?;
+    write_atomic(&target, text.as_bytes())
+        .map_err(|e| K8sError::Config(format!("{}: {}", path.display(), e)))
+}
+
+/// Find the named context entry in a raw kubeconfig document
+fn context_entry<'a>(
+    doc: &'a mut serde_yaml::Value,
+    name: &str,
+) -> Option<&'a mut serde_yaml::Value> {
+    doc.get_mut("contexts")?
+        .as_sequence_mut()?
+        .iter_mut()
+        .find(|entry| entry.get("name").and_then(|n| n.as_str()) == Some(name))
+}
+
+/// Make `context` the current context
+///
+/// Like kubectl, this writes to the first file that sets `current-context`,
+/// or to the first file if none does.
+pub fn use_context(context: &str) -> K8sResult<()> {
+    use_context_in(&kubeconfig_paths(), context)
+}
+
+fn use_context_in(paths: &[PathBuf], context: &str) -> K8sResult<()> {
+    let _guard = WRITE_LOCK.lock().unwrap();
+
+    let config = load_from(paths)?;
+    if !config.contexts.iter().any(|c| c.name == context) {
+        return Err(K8sError::Config(format!(
+            "no context named \"{}\"",
+            context
+        )));
+    }
+
+    let mut target = None;
+    for path in paths {
+        let doc = read_yaml(path)?;
+        if doc.get("current-context").is_some_and(|v| !v.is_null()) {
+            target = Some((path.clone(), doc));
+            break;
+        }
+    }
+    let (path, mut doc) = match target {
+        Some(found) => found,
+        None => {
+            let path = paths
+                .first()
+                .cloned()
+                .ok_or_else(|| K8sError::Config("no kubeconfig file found".to_string()))?;
+            let doc = read_yaml(&path)?;
+            (path, doc)
+        }
+    };
+
+    let map = doc
+        .as_mapping_mut()
+        .ok_or_else(|| K8sError::Config(format!("{}: not a mapping", path.display())))?;
+    map.insert("current-context".into(), context.into());
+    write_yaml(&path, &doc)?;
+
+    info!("Switched current context to {} in {:?}", context, path);
+    Ok(())
+}
+
+/// Set the default namespace of a context, in the file that defines it
+pub fn set_namespace(context: &str, namespace: &str) -> K8sResult<()> {
+    set_namespace_in(&kubeconfig_paths(), context, namespace)
+}
+
+fn set_namespace_in(paths: &[PathBuf], context: &str, namespace: &str) -> K8sResult<()> {
+    let _guard = WRITE_LOCK.lock().unwrap();
+
+    for path in paths {
+        let mut doc = read_yaml(path)?;
+        let Some(entry) = context_entry(&mut doc, context) else {
+            continue;
+        };
+
+        let details = entry
+            .as_mapping_mut()
+            .ok_or_else(|| K8sError::Config(format!("malformed context \"{}\"", context)))?
+            .entry("context".into())
+            .or_insert_with(|| serde_yaml::Value::Mapping(Default::default()));
+        let details = details
+            .as_mapping_mut()
+            .ok_or_else(|| K8sError::Config(format!("malformed context \"{}\"", context)))?;
+        details.insert("namespace".into(), namespace.into());
+
+        write_yaml(path, &doc)?;
+        info!(
+            "Set namespace of context {} to {} in {:?}",
+            context, namespace, path
+        );
+        return Ok(());
+    }
+
+    Err(K8sError::Config(format!(
+        "no context named \"{}\"",
+        context
+    )))
+}
+
+/// Tell every window that the active context or namespace changed
+fn broadcast_change(app: &AppHandle, context: &str) -> K8sResult<()> {
+    let config = load()?;
+    let namespace = config
+        .contexts
+        .iter()
+        .find(|c| c.name == context)
+        .and_then(|c| c.context.as_ref())
+        .and_then(|c| c.namespace.clone());
+
+    app.emit(
+        KUBE_CONTEXT_CHANGED_EVENT,
+        KubeContextChanged {
+            context: context.to_string(),
+            namespace,
+        },
+    )
+    .map_err(|e| K8sError::Request(format!("Failed to emit context change: {}", e)))
+}
+
+/// Tauri command: Get contexts, clusters and users from the kubeconfig
+#[tauri::command]
+pub async fn get_kubeconfig() -> Result<KubeconfigSummary, String> {
+    summarize().map_err(|e| format!("Failed to read kubeconfig: {}", e))
+}
+
+/// Tauri command: Switch the current context
+#[tauri::command]
+pub async fn use_kube_context(app: AppHandle, context: String) -> Result<(), String> {
+    use_context(&context).map_err(|e| format!("Failed to switch context: {}", e))?;
+    if let Some(timeline) = app.try_state::<EventTimeline>() {
+        timeline.context_changed();
+    }
+    broadcast_change(&app, &context).map_err(String::from)
+}
+
+/// Tauri command: Set the default namespace of a context (the current one if
+/// no context is given)
+#[tauri::command]
+pub async fn set_kube_namespace(
+    app: AppHandle,
+    clients: State<'_, KubeClients>,
+    namespace: String,
+    context: Option<String>,
+) -> Result<(), String> {
+    let context = match context {
+        Some(context) => context,
+        None => load()
+            .map_err(String::from)?
+            .current_context
+            .ok_or_else(|| "no current context is set".to_string())?,
+    };
+
+    set_namespace(&context, &namespace).map_err(|e| format!("Failed to set namespace: {}", e))?;
+
+    // Clients capture the context's default namespace when they are built
+    clients.invalidate(&context).await;
+    broadcast_change(&app, &context).map_err(String::from)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    const FIRST: &str = "
+apiVersion: v1
+kind: Config
+clusters:
+- name: dev
+  cluster:
+    server: https://dev.example.com
+contexts:
+- name: dev
+  context:
+    cluster: dev
+    user: dev-admin
+    namespace: web
+users:
+- name: dev-admin
+  user:
+    token: dev-secret-token
+";
+
+    const SECOND: &str = "
+apiVersion: v1
+kind: Config
+current-context: prod
+clusters:
+- name: prod
+  cluster:
+    server: https://prod.example.com
+contexts:
+- name: dev
+  context:
+    cluster: prod
+    user: prod-admin
+    namespace: shadowed
+- name: prod
+  context:
+    cluster: prod
+    user: prod-admin
+users:
+- name: prod-admin
+  user:
+    client-certificate-data: Q0VSVA==
+    client-key-data: S0VZREFUQQ==
+    username: admin
+    password: hunter22
+";
+
+    #[test]
+    fn test_merge_and_use_context() {
+        let dir = std::env::temp_dir().join(format!("kui-kubeconfig-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("dotfiles")).unwrap();
+        let first = dir.join("first");
+        std::fs::write(&first, FIRST).unwrap();
+        // The second file is a symlink, as dotfile managers leave it
+        let second = dir.join("second");
+        let second_target = dir.join("dotfiles").join("second");
+        std::fs::write(&second_target, SECOND).unwrap();
+        std::os::unix::fs::symlink(&second_target, &second).unwrap();
+        let paths = [first.clone(), second.clone()];
+
+        // The first file to define a context wins
+        let summary = summarize_from(&paths).unwrap();
+        assert_eq!(summary.files.len(), 2);
+        assert_eq!(summary.current_context.as_deref(), Some("prod"));
+        let dev = summary.contexts.iter().find(|c| c.name == "dev").unwrap();
+        assert_eq!(dev.namespace.as_deref(), Some("web"));
+        assert_eq!(dev.cluster, "dev");
+        assert_eq!(summary.contexts.len(), 2);
+
+        // Credentials are reduced to how the user authenticates
+        let auth: Vec<&str> = summary
+            .users
+            .iter()
+            .map(|u| u.auth_method.as_str())
+            .collect();
+        assert_eq!(auth, ["token", "clientCertificate"]);
+        let json = serde_json::to_string(&summary).unwrap();
+        for secret in ["dev-secret-token", "S0VZREFUQQ==", "Q0VSVA==", "hunter22"] {
+            assert!(!json.contains(secret), "summary leaks {}", secret);
+        }
+
+        // Written to the file that sets current-context, through the symlink
+        use_context_in(&paths, "dev").unwrap();
+        let config = load_from(&paths).unwrap();
+        assert_eq!(config.current_context.as_deref(), Some("dev"));
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), FIRST);
+        assert!(std::fs::symlink_metadata(&second)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        let written = read_yaml(&second_target).unwrap();
+        assert_eq!(written["current-context"].as_str(), Some("dev"));
+        assert!(use_context_in(&paths, "staging").is_err());
+
+        // Namespaces are set in the file defining the context
+        set_namespace_in(&paths, "prod", "shop").unwrap();
+        let config = load_from(&paths).unwrap();
+        let prod = config.contexts.iter().find(|c| c.name == "prod").unwrap();
+        assert_eq!(
+            prod.context.as_ref().unwrap().namespace.as_deref(),
+            Some("shop")
+        );
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), FIRST);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,616 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pod log streaming
+//!
+//! A log stream reads one or more pod/container logs concurrently and emits
+//! their lines, interleaved in arrival order and tagged with their source,
+//! on its own event channel, `kube-logs:<streamId>`. Streams end by
+//! themselves when every source is exhausted (always, unless following) and
+//! can be stopped at any time by the window that opened them.
+
+use super::client::KubeClients;
+use super::{K8sError, K8sResult};
+use crate::redact::SecretVault;
+use chrono::{DateTime, Utc};
+use futures::{AsyncBufReadExt, StreamExt};
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, ListParams, LogParams};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, EventTarget, Manager};
+use tokio::sync::mpsc;
+use tokio::task::{AbortHandle, JoinSet};
+
+/// Prefix of per-stream event names
+pub const KUBE_LOGS_EVENT_PREFIX: &str = "kube-logs";
+
+/// Most pod/container logs one stream may read at once
+const MAX_LOG_SOURCES: usize = 50;
+
+/// Most lines sent in one event
+const MAX_LINES_PER_EVENT: usize = 500;
+
+/// Annotation naming the container `kubectl logs` picks by default
+const DEFAULT_CONTAINER_ANNOTATION: &str = "kubectl.kubernetes.io/default-container";
+
+/// Arguments of the `logs` method
+///
+/// Either `name` or `labelSelector` selects the pods.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsRequest {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub label_selector: Option<String>,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Defaults to the pod's default container
+    #[serde(default)]
+    pub container: Option<String>,
+    /// Read every init and regular container of each pod
+    #[serde(default)]
+    pub all_containers: bool,
+    #[serde(default)]
+    pub follow: bool,
+    /// Read the log of the previous container instance
+    #[serde(default)]
+    pub previous: bool,
+    #[serde(default)]
+    pub since_seconds: Option<i64>,
+    /// RFC 3339 timestamp
+    #[serde(default)]
+    pub since_time: Option<String>,
+    #[serde(default)]
+    pub tail_lines: Option<i64>,
+    /// Prefix each line with its RFC 3339 timestamp
+    #[serde(default)]
+    pub timestamps: bool,
+}
+
+/// Returned by the `logs` method
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogSubscription {
+    pub stream_id: String,
+    /// Event name the lines are emitted on
+    pub event: String,
+    /// Every pod/container being read
+    pub sources: Vec<LogSource>,
+}
+
+/// A pod/container whose log is being read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogSource {
+    pub pod: String,
+    pub container: String,
+}
+
+/// A log line tagged with its source
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogLine {
+    pod: String,
+    container: String,
+    line: String,
+}
+
+/// A message on a log channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "UPPERCASE")]
+enum LogMessage {
+    Lines {
+        lines: Vec<LogLine>,
+    },
+    /// One source failed; the others keep going
+    Error {
+        pod: String,
+        container: String,
+        message: String,
+    },
+    /// Every source is exhausted
+    End,
+}
+
+/// Payload of `kube-logs:<streamId>` events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogPayload {
+    stream_id: String,
+    #[serde(flatten)]
+    message: LogMessage,
+}
+
+/// Bookkeeping for a running stream
+struct LogStream {
+    window_label: String,
+    task: AbortHandle,
+}
+
+/// Tracks the log streams of all windows
+pub struct LogManager {
+    streams: Arc<Mutex<HashMap<String, LogStream>>>,
+    next_id: AtomicUsize,
+}
+
+impl Default for LogManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogManager {
+    pub fn new() -> Self {
+        LogManager {
+            streams: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicUsize::new(1),
+        }
+    }
+
+    /// Start streaming logs to the given window
+    pub async fn logs(
+        &self,
+        app: &AppHandle,
+        clients: &KubeClients,
+        window_label: &str,
+        request: LogsRequest,
+    ) -> K8sResult<LogSubscription> {
+        let params = log_params(&request)?;
+        let client = clients.client(request.context.as_deref()).await?;
+        let namespace = request
+            .namespace
+            .clone()
+            .unwrap_or_else(|| client.default_namespace().to_string());
+        let api: Api<Pod> = Api::namespaced(client.client.clone(), &namespace);
+
+        let pods = match (&request.name, &request.label_selector) {
+            (Some(name), _) => vec![api.get(name).await?],
+            (None, Some(selector)) => {
+                api.list(&ListParams::default().labels(selector))
+                    .await?
+                    .items
+            }
+            (None, None) => {
+                return Err(K8sError::InvalidRequest(
+                    "logs requires a pod name or a label selector".to_string(),
+                ))
+            }
+        };
+
+        let mut sources = Vec::new();
+        for pod in &pods {
+            let pod_name = pod.metadata.name.clone().unwrap_or_default();
+            for container in
+                containers_of(pod, request.container.as_deref(), request.all_containers)?
+            {
+                sources.push(LogSource {
+                    pod: pod_name.clone(),
+                    container,
+                });
+            }
+        }
+        if sources.is_empty() {
+            return Err(K8sError::InvalidRequest(format!(
+                "no matching pods or containers in namespace {}",
+                namespace
+            )));
+        }
+        if sources.len() > MAX_LOG_SOURCES {
+            return Err(K8sError::InvalidRequest(format!(
+                "{} logs selected, at most {} can be streamed at once",
+                sources.len(),
+                MAX_LOG_SOURCES
+            )));
+        }
+
+        let stream_id = format!("logs-{}", self.next_id.fetch_add(1, Ordering::SeqCst));
+        let event = format!("{}:{}", KUBE_LOGS_EVENT_PREFIX, stream_id);
+
+        // Hold the lock across the spawn so the task cannot finish and
+        // deregister itself before it was registered
+        let mut streams = self.streams.lock().unwrap();
+        let registry = Arc::clone(&self.streams);
+        let sink = WindowSink {
+            app: app.clone(),
+            window_label: window_label.to_string(),
+            stream_id: stream_id.clone(),
+            event: event.clone(),
+        };
+        let task = tokio::spawn({
+            let sources = sources.clone();
+            let id = stream_id.clone();
+            async move {
+                pump_logs(&sink, api, params, sources).await;
+                debug!("Log stream {} ended", id);
+                registry.lock().unwrap().remove(&id);
+            }
+        });
+        streams.insert(
+            stream_id.clone(),
+            LogStream {
+                window_label: window_label.to_string(),
+                task: task.abort_handle(),
+            },
+        );
+
+        info!(
+            "Started {} with {} sources for {}",
+            stream_id,
+            sources.len(),
+            window_label
+        );
+        Ok(LogSubscription {
+            stream_id,
+            event,
+            sources,
+        })
+    }
+
+    /// Stop a stream
+    pub fn stop(&self, stream_id: &str) -> K8sResult<()> {
+        let stream = self
+            .streams
+            .lock()
+            .unwrap()
+            .remove(stream_id)
+            .ok_or_else(|| {
+                K8sError::InvalidRequest(format!("Unknown log stream: {}", stream_id))
+            })?;
+
+        info!("Stopping {}", stream_id);
+        stream.task.abort();
+        Ok(())
+    }
+
+    /// Stop every stream of a window that is going away
+    pub fn close_window(&self, window_label: &str) {
+        self.streams.lock().unwrap().retain(|id, stream| {
+            if stream.window_label != window_label {
+                return true;
+            }
+            debug!("Stopping {} of closed window {}", id, window_label);
+            stream.task.abort();
+            false
+        });
+    }
+}
+
+/// Translate a request to API log parameters
+fn log_params(request: &LogsRequest) -> K8sResult<LogParams> {
+    let since_time = request
+        .since_time
+        .as_deref()
+        .map(|s| {
+            DateTime::parse_from_rfc3339(s)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| K8sError::InvalidRequest(format!("invalid sinceTime {}: {}", s, e)))
+        })
+        .transpose()?;
+
+    Ok(LogParams {
+        follow: request.follow,
+        previous: request.previous,
+        since_seconds: request.since_seconds,
+        since_time,
+        tail_lines: request.tail_lines,
+        timestamps: request.timestamps,
+        ..Default::default()
+    })
+}
+
+/// Containers of a pod to read, following `kubectl logs` defaults
+fn containers_of(pod: &Pod, container: Option<&str>, all: bool) -> K8sResult<Vec<String>> {
+    let pod_name = pod.metadata.name.as_deref().unwrap_or_default();
+    let spec = pod
+        .spec
+        .as_ref()
+        .ok_or_else(|| K8sError::InvalidRequest(format!("pod {} has no spec", pod_name)))?;
+
+    if all {
+        let init = spec.init_containers.iter().flatten();
+        return Ok(init
+            .chain(spec.containers.iter())
+            .map(|c| c.name.clone())
+            .collect());
+    }
+
+    if let Some(container) = container {
+        // A selector may match pods without this container; skip those
+        return Ok(spec
+            .containers
+            .iter()
+            .chain(spec.init_containers.iter().flatten())
+            .filter(|c| c.name == container)
+            .map(|c| c.name.clone())
+            .collect());
+    }
+
+    let default = pod
+        .metadata
+        .annotations
+        .as_ref()
+        .and_then(|a| a.get(DEFAULT_CONTAINER_ANNOTATION))
+        .filter(|name| spec.containers.iter().any(|c| &c.name == *name))
+        .cloned();
+    Ok(default
+        .or_else(|| spec.containers.first().map(|c| c.name.clone()))
+        .into_iter()
+        .collect())
+}
+
+/// Where the messages of a log stream go
+trait LogSink: Send + Sync {
+    fn send(&self, message: LogMessage);
+}
+
+/// Sends messages of one stream to its window
+struct WindowSink {
+    app: AppHandle,
+    window_label: String,
+    stream_id: String,
+    event: String,
+}
+
+impl LogSink for WindowSink {
+    fn send(&self, message: LogMessage) {
+        if let Err(e) = self.app.emit_to(
+            EventTarget::webview_window(self.window_label.as_str()),
+            self.event.as_str(),
+            self.app.state::<SecretVault>().redact_payload(LogPayload {
+                stream_id: self.stream_id.clone(),
+                message,
+            }),
+        ) {
+            error!("Failed to emit {} event: {}", self.event, e);
+        }
+    }
+}
+
+/// Read every source concurrently and forward their lines until all end
+async fn pump_logs(sink: &dyn LogSink, api: Api<Pod>, params: LogParams, sources: Vec<LogSource>) {
+    let (tx, mut rx) = mpsc::channel(1024);
+
+    // Dropping the set when the task is aborted aborts the readers too
+    let mut readers = JoinSet::new();
+    for source in sources {
+        let mut params = params.clone();
+        params.container = Some(source.container.clone());
+        readers.spawn(read_source(api.clone(), params, source, tx.clone()));
+    }
+    drop(tx);
+
+    let mut lines = Vec::new();
+    while let Some(message) = rx.recv().await {
+        let mut next = Some(message);
+        while let Some(message) = next.take() {
+            match message {
+                LogMessage::Lines { lines: mut batch } => lines.append(&mut batch),
+                other => {
+                    // Keep the lines a source printed before it failed ahead
+                    // of its error
+                    if !lines.is_empty() {
+                        sink.send(LogMessage::Lines {
+                            lines: std::mem::take(&mut lines),
+                        });
+                    }
+                    sink.send(other);
+                }
+            }
+            if lines.len() < MAX_LINES_PER_EVENT {
+                next = rx.try_recv().ok();
+            }
+        }
+
+        if !lines.is_empty() {
+            sink.send(LogMessage::Lines {
+                lines: std::mem::take(&mut lines),
+            });
+        }
+    }
+
+    sink.send(LogMessage::End);
+}
+
+/// Read one container log line by line
+async fn read_source(
+    api: Api<Pod>,
+    params: LogParams,
+    source: LogSource,
+    tx: mpsc::Sender<LogMessage>,
+) {
+    let result = async {
+        let reader = api.log_stream(&source.pod, &params).await?;
+        let lines = reader.lines();
+        futures::pin_mut!(lines);
+
+        while let Some(line) = lines.next().await {
+            let line = LogLine {
+                pod: source.pod.clone(),
+                container: source.container.clone(),
+                line: line.map_err(kube::Error::ReadEvents)?,
+            };
+            if tx
+                .send(LogMessage::Lines { lines: vec![line] })
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+        Ok::<_, kube::Error>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        warn!("Log of {}/{} failed: {}", source.pod, source.container, e);
+        let _ = tx
+            .send(LogMessage::Error {
+                pod: source.pod,
+                container: source.container,
+                message: K8sError::from(e).to_string(),
+            })
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::mock;
+    use http::StatusCode;
+    use k8s_openapi::api::core::v1::{Container, PodSpec};
+    use std::collections::BTreeMap;
+
+    /// Summarizes messages, one entry per line
+    #[derive(Default)]
+    struct Collected(Mutex<Vec<String>>);
+
+    impl LogSink for Collected {
+        fn send(&self, message: LogMessage) {
+            let mut output = self.0.lock().unwrap();
+            match message {
+                LogMessage::Lines { lines } => output.extend(
+                    lines
+                        .into_iter()
+                        .map(|l| format!("{}/{} {}", l.pod, l.container, l.line)),
+                ),
+                LogMessage::Error { pod, container, .. } => {
+                    output.push(format!("ERROR {}/{}", pod, container))
+                }
+                LogMessage::End => output.push("END".to_string()),
+            }
+        }
+    }
+
+    /// Stream the logs of `sources` from a mock server answering with `log`
+    async fn stream<F>(sources: &[(&str, &str)], log: F) -> Vec<String>
+    where
+        F: Fn(&str, &str) -> mock::MockResponse + Send + Sync + 'static,
+    {
+        let (client, _) = mock::client(move |request| {
+            let pod = request.path.split('/').nth_back(1).unwrap_or_default();
+            let container = request
+                .query
+                .split('&')
+                .find_map(|p| p.strip_prefix("container="))
+                .unwrap_or_default();
+            log(pod, container)
+        });
+        let sources = sources
+            .iter()
+            .map(|(pod, container)| LogSource {
+                pod: pod.to_string(),
+                container: container.to_string(),
+            })
+            .collect();
+
+        let sink = Collected::default();
+        let api: Api<Pod> = Api::namespaced(client, "shop");
+        pump_logs(&sink, api, LogParams::default(), sources).await;
+        sink.0.into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_error_follows_earlier_lines() {
+        // The third line is not UTF-8, which fails the read
+        let output = stream(&[("web-0", "web")], |_, _| mock::text(b"l1\nl2\n\xff\n")).await;
+        assert_eq!(
+            output,
+            ["web-0/web l1", "web-0/web l2", "ERROR web-0/web", "END"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_keeps_order_per_source() {
+        let sources = [("web-0", "web"), ("web-1", "web"), ("web-1", "sidecar")];
+        let output = stream(&sources, |pod, container| match container {
+            "web" => {
+                let log: String = (1..=300).map(|i| format!("{} {}\n", pod, i)).collect();
+                mock::text(log.as_bytes())
+            }
+            _ => mock::failure(
+                StatusCode::BAD_REQUEST,
+                "BadRequest",
+                "container is waiting",
+            )
+            .into(),
+        })
+        .await;
+
+        for pod in ["web-0", "web-1"] {
+            let prefix = format!("{}/web ", pod);
+            let lines: Vec<_> = output.iter().filter(|l| l.starts_with(&prefix)).collect();
+            let expected: Vec<_> = (1..=300)
+                .map(|i| format!("{}{} {}", prefix, pod, i))
+                .collect();
+            assert_eq!(lines, expected.iter().collect::<Vec<_>>());
+        }
+        assert_eq!(
+            output
+                .iter()
+                .filter(|l| l.starts_with("ERROR"))
+                .collect::<Vec<_>>(),
+            ["ERROR web-1/sidecar"]
+        );
+        assert_eq!(output.last().map(String::as_str), Some("END"));
+        assert_eq!(output.len(), 2 * 300 + 2);
+    }
+
+    fn pod(containers: &[&str], init: &[&str], default: Option<&str>) -> Pod {
+        let container = |name: &&str| Container {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let mut pod = Pod {
+            spec: Some(PodSpec {
+                containers: containers.iter().map(container).collect(),
+                init_containers: Some(init.iter().map(container).collect()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        if let Some(default) = default {
+            pod.metadata.annotations = Some(BTreeMap::from([(
+                DEFAULT_CONTAINER_ANNOTATION.to_string(),
+                default.to_string(),
+            )]));
+        }
+        pod
+    }
+
+    #[test]
+    fn test_containers_of() {
+        let p = pod(&["app", "sidecar"], &["migrate"], None);
+        assert_eq!(containers_of(&p, None, false).unwrap(), vec!["app"]);
+        assert_eq!(
+            containers_of(&p, None, true).unwrap(),
+            vec!["migrate", "app", "sidecar"]
+        );
+        assert_eq!(
+            containers_of(&p, Some("migrate"), false).unwrap(),
+            vec!["migrate"]
+        );
+        assert!(containers_of(&p, Some("db"), false).unwrap().is_empty());
+
+        let p = pod(&["app", "sidecar"], &[], Some("sidecar"));
+        assert_eq!(containers_of(&p, None, false).unwrap(), vec!["sidecar"]);
+    }
+}
@@ -0,0 +1,587 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pod and node metrics from `metrics.k8s.io`, with recent history
+//!
+//! A poller per context fetches the usage of all pods and nodes every
+//! `POLL_INTERVAL` and keeps the last `HISTORY_LEN` samples of each object
+//! in memory, enough for sparklines without a Prometheus. Pollers start on
+//! the first read for a context and stop once nothing has read them for
+//! `IDLE_TIMEOUT`.
+//!
+//! Users who may not list pod metrics cluster-wide get those of the
+//! namespaces they read instead.
+
+use super::client::KubeClients;
+use super::{K8sError, K8sResult};
+use kube::Client;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+/// How often metrics are fetched; metrics-server refreshes every 15s too
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Samples kept per object: 30 minutes at `POLL_INTERVAL`
+const HISTORY_LEN: usize = 120;
+
+/// A poller nobody read from for this long stops
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+const POD_METRICS_PATH: &str = "/apis/metrics.k8s.io/v1beta1/pods";
+const NODE_METRICS_PATH: &str = "/apis/metrics.k8s.io/v1beta1/nodes";
+
+fn namespace_pod_metrics_path(namespace: &str) -> String {
+    format!("/apis/metrics.k8s.io/v1beta1/namespaces/{}/pods", namespace)
+}
+
+/// Type of object metrics are reported for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsKind {
+    Pod,
+    Node,
+}
+
+/// Resource usage at one point in time
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsSample {
+    /// When metrics-server collected the sample
+    pub timestamp: String,
+    /// CPU usage in millicores, summed over containers for pods
+    pub cpu_millicores: f64,
+    /// Memory working set in bytes, summed over containers for pods
+    pub memory_bytes: f64,
+    #[serde(skip)]
+    observed: Instant,
+}
+
+/// Latest usage of one object
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsValue {
+    pub namespace: Option<String>,
+    pub name: String,
+    #[serde(flatten)]
+    pub sample: MetricsSample,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct MetricsKey {
+    kind: MetricsKind,
+    namespace: Option<String>,
+    name: String,
+}
+
+/// Recent samples of every object of a context
+#[derive(Default)]
+struct MetricsHistory {
+    series: HashMap<MetricsKey, VecDeque<MetricsSample>>,
+    /// Why the last poll failed, if it did
+    error: Option<String>,
+}
+
+impl MetricsHistory {
+    /// Add the items of a PodMetricsList or NodeMetricsList
+    fn record(&mut self, kind: MetricsKind, list: &Value, now: Instant) {
+        for item in list["items"].as_array().into_iter().flatten() {
+            let Some(name) = item["metadata"]["name"].as_str() else {
+                continue;
+            };
+            let key = MetricsKey {
+                kind,
+                namespace: item["metadata"]["namespace"].as_str().map(String::from),
+                name: name.to_string(),
+            };
+
+            let usages: Vec<&Value> = match kind {
+                MetricsKind::Pod => item["containers"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|c| &c["usage"])
+                    .collect(),
+                MetricsKind::Node => vec![&item["usage"]],
+            };
+            let total = |resource: &str| -> f64 {
+                usages
+                    .iter()
+                    .filter_map(|usage| usage[resource].as_str().and_then(parse_quantity))
+                    .sum()
+            };
+            let sample = MetricsSample {
+                timestamp: item["timestamp"].as_str().unwrap_or_default().to_string(),
+                cpu_millicores: total("cpu") * 1000.0,
+                memory_bytes: total("memory"),
+                observed: now,
+            };
+
+            let series = self.series.entry(key).or_default();
+            // metrics-server hasn't scraped since the last poll
+            if series
+                .back()
+                .is_some_and(|last| last.timestamp == sample.timestamp)
+            {
+                continue;
+            }
+            if series.len() == HISTORY_LEN {
+                series.pop_front();
+            }
+            series.push_back(sample);
+        }
+    }
+
+    /// Drop objects that have not reported for the whole history window,
+    /// e.g. deleted pods
+    fn prune(&mut self, now: Instant) {
+        let window = POLL_INTERVAL * HISTORY_LEN as u32;
+        self.series.retain(|_, series| {
+            series
+                .back()
+                .is_some_and(|last| now.duration_since(last.observed) < window)
+        });
+    }
+}
+
+/// Metrics of one context and the state of its poller
+struct ContextMetrics {
+    history: Mutex<MetricsHistory>,
+    last_read: Mutex<Instant>,
+    /// Namespaces pod metrics were read for
+    namespaces: Mutex<BTreeSet<String>>,
+    /// Whether pod metrics may be listed in all namespaces at once; cleared
+    /// on the first 403
+    cluster_wide: AtomicBool,
+}
+
+impl ContextMetrics {
+    fn new() -> Self {
+        ContextMetrics {
+            history: Mutex::new(MetricsHistory::default()),
+            last_read: Mutex::new(Instant::now()),
+            namespaces: Mutex::new(BTreeSet::new()),
+            cluster_wide: AtomicBool::new(true),
+        }
+    }
+}
+
+/// Per-context metrics pollers
+pub struct MetricsPoller {
+    contexts: Arc<Mutex<HashMap<String, Arc<ContextMetrics>>>>,
+}
+
+impl Default for MetricsPoller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsPoller {
+    pub fn new() -> Self {
+        MetricsPoller {
+            contexts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Get the metrics of a context, starting its poller if needed
+    ///
+    /// A `namespace` is polled from then on if pod metrics can't be listed
+    /// cluster-wide.
+    async fn metrics(
+        &self,
+        clients: &KubeClients,
+        context: Option<&str>,
+        namespace: Option<&str>,
+    ) -> K8sResult<Arc<ContextMetrics>> {
+        let client = clients.client(context).await?;
+        let running = self.contexts.lock().unwrap().get(&client.context).cloned();
+        if let Some(metrics) = running {
+            *metrics.last_read.lock().unwrap() = Instant::now();
+            let added = namespace
+                .is_some_and(|ns| metrics.namespaces.lock().unwrap().insert(ns.to_string()));
+            // Don't leave the first read of a namespace without data
+            if added && !metrics.cluster_wide.load(Ordering::Relaxed) {
+                poll(&client.client, &metrics).await;
+            }
+            return Ok(metrics);
+        }
+
+        // Poll once before returning, so the first read has data
+        let metrics = Arc::new(ContextMetrics::new());
+        if let Some(namespace) = namespace {
+            metrics
+                .namespaces
+                .lock()
+                .unwrap()
+                .insert(namespace.to_string());
+        }
+        poll(&client.client, &metrics).await;
+
+        let mut contexts = self.contexts.lock().unwrap();
+        if let Some(existing) = contexts.get(&client.context) {
+            // Another read started a poller meanwhile
+            return Ok(Arc::clone(existing));
+        }
+        contexts.insert(client.context.clone(), Arc::clone(&metrics));
+        info!("Polling metrics of context {}", client.context);
+
+        let registry = Arc::clone(&self.contexts);
+        let polled = Arc::clone(&metrics);
+        tauri::async_runtime::spawn(async move {
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                if polled.last_read.lock().unwrap().elapsed() > IDLE_TIMEOUT {
+                    break;
+                }
+                poll(&client.client, &polled).await;
+            }
+            registry.lock().unwrap().remove(&client.context);
+            debug!("Stopped polling metrics of context {}", client.context);
+        });
+
+        Ok(metrics)
+    }
+}
+
+/// Fetch pod and node metrics once
+async fn poll(client: &Client, metrics: &ContextMetrics) {
+    let namespaces: Vec<String> = metrics.namespaces.lock().unwrap().iter().cloned().collect();
+    let mut lists = Vec::new();
+
+    if metrics.cluster_wide.load(Ordering::Relaxed) || namespaces.is_empty() {
+        let list = fetch(client, POD_METRICS_PATH).await;
+        if matches!(list, Err(kube::Error::Api(ref e)) if e.code == 403) && !namespaces.is_empty() {
+            info!("Pod metrics are forbidden cluster-wide, polling them per namespace");
+            metrics.cluster_wide.store(false, Ordering::Relaxed);
+        } else {
+            lists.push((MetricsKind::Pod, list));
+        }
+    }
+    if !metrics.cluster_wide.load(Ordering::Relaxed) {
+        for namespace in &namespaces {
+            let path = namespace_pod_metrics_path(namespace);
+            lists.push((MetricsKind::Pod, fetch(client, &path).await));
+        }
+    }
+    lists.push((MetricsKind::Node, fetch(client, NODE_METRICS_PATH).await));
+
+    let mut history = metrics.history.lock().unwrap();
+    let mut error = None;
+    for (kind, list) in lists {
+        match list {
+            Ok(list) => history.record(kind, &list, Instant::now()),
+            Err(e) => error = Some(K8sError::from(e).to_string()),
+        }
+    }
+    history.prune(Instant::now());
+    history.error = error;
+}
+
+async fn fetch(client: &Client, path: &str) -> Result<Value, kube::Error> {
+    let request = http::Request::get(path).body(Vec::new()).unwrap();
+    client.request::<Value>(request).await
+}
+
+/// Parse a Kubernetes quantity (`250m`, `1.5`, `128Mi`, `1e3`) into base
+/// units
+fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let split = quantity
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '+' || c == '-'))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+    let number: f64 = number.parse().ok()?;
+
+    let multiplier = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024.0_f64.powi(2),
+        "Gi" => 1024.0_f64.powi(3),
+        "Ti" => 1024.0_f64.powi(4),
+        "Pi" => 1024.0_f64.powi(5),
+        "Ei" => 1024.0_f64.powi(6),
+        exponent => {
+            let exponent: i32 = exponent.strip_prefix(['e', 'E'])?.parse().ok()?;
+            10f64.powi(exponent)
+        }
+    };
+    Some(number * multiplier)
+}
+
+/// Error to return for a read that found no samples
+fn no_data(metrics: &ContextMetrics) -> String {
+    match metrics.history.lock().unwrap().error {
+        Some(ref error) => format!("Metrics are not available: {}", error),
+        None => "No metrics reported yet".to_string(),
+    }
+}
+
+/// Tauri command: Get the latest usage of all pods (optionally of one
+/// namespace) or all nodes
+#[tauri::command]
+pub async fn get_resource_metrics(
+    clients: State<'_, KubeClients>,
+    poller: State<'_, MetricsPoller>,
+    kind: MetricsKind,
+    namespace: Option<String>,
+    context: Option<String>,
+) -> Result<Vec<MetricsValue>, String> {
+    let pod_namespace = namespace.as_deref().filter(|_| kind == MetricsKind::Pod);
+    let metrics = poller
+        .metrics(&clients, context.as_deref(), pod_namespace)
+        .await
+        .map_err(|e| format!("Failed to get metrics: {}", e))?;
+
+    let history = metrics.history.lock().unwrap();
+    let values: Vec<MetricsValue> = history
+        .series
+        .iter()
+        .filter(|(key, _)| key.kind == kind && (namespace.is_none() || key.namespace == namespace))
+        .filter_map(|(key, series)| {
+            Some(MetricsValue {
+                namespace: key.namespace.clone(),
+                name: key.name.clone(),
+                sample: series.back()?.clone(),
+            })
+        })
+        .collect();
+    drop(history);
+
+    if values.is_empty() {
+        return Err(no_data(&metrics));
+    }
+    Ok(values)
+}
+
+/// Tauri command: Get the recent usage of one pod or node, oldest first
+#[tauri::command]
+pub async fn get_metrics_history(
+    clients: State<'_, KubeClients>,
+    poller: State<'_, MetricsPoller>,
+    kind: MetricsKind,
+    name: String,
+    namespace: Option<String>,
+    context: Option<String>,
+) -> Result<Vec<MetricsSample>, String> {
+    let namespace = namespace.filter(|_| kind == MetricsKind::Pod);
+    let metrics = poller
+        .metrics(&clients, context.as_deref(), namespace.as_deref())
+        .await
+        .map_err(|e| format!("Failed to get metrics: {}", e))?;
+
+    let key = MetricsKey {
+        kind,
+        namespace,
+        name,
+    };
+    let series = metrics.history.lock().unwrap().series.get(&key).cloned();
+    match series {
+        Some(series) => Ok(series.into()),
+        None => Err(no_data(&metrics)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::mock;
+    use http::StatusCode;
+    use serde_json::json;
+
+    /// PodMetricsList as served by metrics-server
+    fn pod_metrics(timestamp: &str, cpu: &str) -> Value {
+        json!({
+            "kind": "PodMetricsList",
+            "apiVersion": "metrics.k8s.io/v1beta1",
+            "items": [{
+                "metadata": {"name": "web-0", "namespace": "shop"},
+                "timestamp": timestamp,
+                "window": "15s",
+                "containers": [
+                    {"name": "web", "usage": {"cpu": cpu, "memory": "64Mi"}},
+                    {"name": "proxy", "usage": {"cpu": "250000n", "memory": "16384Ki"}}
+                ]
+            }]
+        })
+    }
+
+    #[test]
+    fn test_history_from_metrics_api() {
+        let mut history = MetricsHistory::default();
+        let now = Instant::now();
+        history.record(
+            MetricsKind::Pod,
+            &pod_metrics("2025-03-01T10:00:00Z", "100m"),
+            now,
+        );
+        // Same scrape polled twice
+        history.record(
+            MetricsKind::Pod,
+            &pod_metrics("2025-03-01T10:00:00Z", "100m"),
+            now,
+        );
+        for i in 1..=HISTORY_LEN {
+            let timestamp = format!("2025-03-01T11:{:02}:{:02}Z", i / 4, (i % 4) * 15);
+            history.record(MetricsKind::Pod, &pod_metrics(&timestamp, "1"), now);
+        }
+
+        let key = MetricsKey {
+            kind: MetricsKind::Pod,
+            namespace: Some("shop".to_string()),
+            name: "web-0".to_string(),
+        };
+        let series = &history.series[&key];
+        assert_eq!(series.len(), HISTORY_LEN);
+        assert_eq!(series[0].timestamp, "2025-03-01T11:00:15Z");
+        assert!((series[0].cpu_millicores - 1000.25).abs() < 1e-9);
+        assert_eq!(series[0].memory_bytes, 80.0 * 1024.0 * 1024.0);
+
+        history.prune(now + POLL_INTERVAL * HISTORY_LEN as u32);
+        assert!(history.series.is_empty());
+
+        assert_eq!(parse_quantity("1e3"), Some(1000.0));
+        assert_eq!(
+            parse_quantity("1.5Gi"),
+            Some(1.5 * 1024.0 * 1024.0 * 1024.0)
+        );
+        assert_eq!(parse_quantity("12x"), None);
+    }
+
+    #[tokio::test]
+    async fn test_poll() {
+        let available = Arc::new(AtomicBool::new(true));
+        let serving = Arc::clone(&available);
+        let (client, requests) = mock::client(move |request| {
+            if !serving.load(Ordering::SeqCst) {
+                return mock::failure(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "ServiceUnavailable",
+                    "the server is currently unable to handle the request",
+                );
+            }
+            match request.path.as_str() {
+                POD_METRICS_PATH => (StatusCode::OK, pod_metrics("2025-03-01T10:00:00Z", "100m")),
+                NODE_METRICS_PATH => {
+                    let nodes = json!({
+                        "kind": "NodeMetricsList",
+                        "apiVersion": "metrics.k8s.io/v1beta1",
+                        "items": [{
+                            "metadata": {"name": "node-1"},
+                            "timestamp": "2025-03-01T10:00:00Z",
+                            "window": "15s",
+                            "usage": {"cpu": "1500m", "memory": "2Gi"}
+                        }]
+                    });
+                    (StatusCode::OK, nodes)
+                }
+                _ => mock::failure(StatusCode::NOT_FOUND, "NotFound", "not found"),
+            }
+        });
+        let metrics = ContextMetrics::new();
+
+        poll(&client, &metrics).await;
+        {
+            let history = metrics.history.lock().unwrap();
+            assert_eq!(history.error, None);
+            assert_eq!(history.series.len(), 2);
+            let node = MetricsKey {
+                kind: MetricsKind::Node,
+                namespace: None,
+                name: "node-1".to_string(),
+            };
+            assert_eq!(history.series[&node][0].cpu_millicores, 1500.0);
+        }
+        let paths: Vec<_> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.path.clone())
+            .collect();
+        assert_eq!(paths, [POD_METRICS_PATH, NODE_METRICS_PATH]);
+
+        // metrics-server going away keeps the history but reports why
+        available.store(false, Ordering::SeqCst);
+        poll(&client, &metrics).await;
+        {
+            let history = metrics.history.lock().unwrap();
+            let error = history.error.as_deref().unwrap();
+            assert!(error.starts_with("ServiceUnavailable (503)"), "{}", error);
+            assert_eq!(history.series.len(), 2);
+        }
+
+        available.store(true, Ordering::SeqCst);
+        poll(&client, &metrics).await;
+        assert_eq!(metrics.history.lock().unwrap().error, None);
+    }
+
+    #[tokio::test]
+    async fn test_poll_per_namespace_when_forbidden() {
+        let (client, requests) = mock::client(|request| {
+            if request.path == namespace_pod_metrics_path("shop") {
+                (StatusCode::OK, pod_metrics("2025-03-01T10:00:00Z", "100m"))
+            } else {
+                mock::failure(StatusCode::FORBIDDEN, "Forbidden", "forbidden")
+            }
+        });
+        let metrics = ContextMetrics::new();
+        metrics
+            .namespaces
+            .lock()
+            .unwrap()
+            .insert("shop".to_string());
+
+        poll(&client, &metrics).await;
+        poll(&client, &metrics).await;
+        assert!(!metrics.cluster_wide.load(Ordering::Relaxed));
+        let key = MetricsKey {
+            kind: MetricsKind::Pod,
+            namespace: Some("shop".to_string()),
+            name: "web-0".to_string(),
+        };
+        assert!(metrics.history.lock().unwrap().series.contains_key(&key));
+
+        let paths: Vec<_> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.path.clone())
+            .collect();
+        let shop = namespace_pod_metrics_path("shop");
+        assert_eq!(
+            paths,
+            [
+                POD_METRICS_PATH,
+                &shop,
+                NODE_METRICS_PATH,
+                &shop,
+                NODE_METRICS_PATH
+            ]
+        );
+    }
+}
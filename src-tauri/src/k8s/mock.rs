@@ -0,0 +1,157 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mock API server for tests
+//!
+//! Requests made through the returned `kube::Client` are answered in-process
+//! by a handler, and recorded so tests can check what was sent.
+
+use super::client::ContextClient;
+use super::discovery::ApiResourceInfo;
+use super::resources::Target;
+use http::header::{ACCEPT, CONTENT_TYPE};
+use http::{Request, Response, StatusCode};
+use kube::client::Body;
+use kube::Client;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+/// A request received by the mock server
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    /// Raw query string, still percent-encoded
+    pub query: String,
+    pub accept: Option<String>,
+    /// JSON body, `Null` if there was none
+    pub body: Value,
+}
+
+/// Requests received so far, in order
+pub type Requests = Arc<Mutex<Vec<MockRequest>>>;
+
+/// A response of the mock server
+pub struct MockResponse {
+    status: StatusCode,
+    body: Vec<u8>,
+}
+
+impl From<(StatusCode, Value)> for MockResponse {
+    fn from((status, value): (StatusCode, Value)) -> Self {
+        MockResponse {
+            status,
+            body: serde_json::to_vec(&value).unwrap(),
+        }
+    }
+}
+
+/// Build a client whose requests are answered by `respond`
+pub fn client<F, R>(respond: F) -> (Client, Requests)
+where
+    F: Fn(&MockRequest) -> R + Send + Sync + 'static,
+    R: Into<MockResponse>,
+{
+    let respond = Arc::new(respond);
+    let requests: Requests = Arc::default();
+    let received = Arc::clone(&requests);
+
+    let service = tower::service_fn(move |request: Request<Body>| {
+        let respond = Arc::clone(&respond);
+        let received = Arc::clone(&received);
+        async move {
+            let (parts, body) = request.into_parts();
+            let body = body.collect_bytes().await?;
+            let request = MockRequest {
+                method: parts.method.to_string(),
+                path: parts.uri.path().to_string(),
+                query: parts.uri.query().unwrap_or_default().to_string(),
+                accept: parts
+                    .headers
+                    .get(ACCEPT)
+                    .and_then(|v| v.to_str().ok())
+                    .map(String::from),
+                body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+            };
+
+            let response: MockResponse = respond(&request).into();
+            received.lock().unwrap().push(request);
+            let response = Response::builder()
+                .status(response.status)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(response.body))
+                .unwrap();
+            Ok::<_, kube::Error>(response)
+        }
+    });
+
+    (Client::new(service, "default"), requests)
+}
+
+/// A `Status` failure response, as the API server sends for errors
+pub fn failure(status: StatusCode, reason: &str, message: &str) -> (StatusCode, Value) {
+    let body = json!({
+        "kind": "Status",
+        "apiVersion": "v1",
+        "metadata": {},
+        "status": "Failure",
+        "message": message,
+        "reason": reason,
+        "code": status.as_u16(),
+    });
+    (status, body)
+}
+
+/// A watch response: one JSON event per line, then the end of the stream
+pub fn watch_events(events: &[Value]) -> MockResponse {
+    let mut body = Vec::new();
+    for event in events {
+        serde_json::to_writer(&mut body, event).unwrap();
+        body.push(b'\n');
+    }
+    MockResponse {
+        status: StatusCode::OK,
+        body,
+    }
+}
+
+/// A plain text response, e.g. a container log
+pub fn text(body: &[u8]) -> MockResponse {
+    MockResponse {
+        status: StatusCode::OK,
+        body: body.to_vec(),
+    }
+}
+
+/// Pods in namespace `shop` of context `test`
+pub fn pods(client: Client) -> Target {
+    let verbs = ["get", "list", "watch", "delete"];
+    Target {
+        client: ContextClient {
+            context: "test".to_string(),
+            client,
+        },
+        info: ApiResourceInfo {
+            group: String::new(),
+            version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            plural: "pods".to_string(),
+            singular: "pod".to_string(),
+            short_names: vec!["po".to_string()],
+            namespaced: true,
+            verbs: verbs.iter().map(|v| v.to_string()).collect(),
+        },
+        namespace: Some("shop".to_string()),
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native Kubernetes API access for Kui
+//!
+//! This module backs the `kubectl` exec_invoke module. Instead of forking a
+//! kubectl binary and scraping its output, requests go straight to the API
+//! server using the user's kubeconfig, and results come back as JSON the
+//! renderer can display as-is.
+
+use crate::ipc::parse_args;
+use log::debug;
+use std::fmt;
+use tauri::{AppHandle, Manager};
+
+pub mod apply;
+pub mod audit;
+pub mod client;
+pub mod discovery;
+pub mod events;
+pub mod exec;
+pub mod fanout;
+pub mod graph;
+pub mod informer;
+pub mod kubeconfig;
+pub mod logs;
+pub mod metrics;
+#[cfg(test)]
+mod mock;
+pub mod portforward;
+pub mod rbac;
+pub mod resources;
+pub mod schema;
+pub mod validate;
+pub mod watch;
+
+pub use client::KubeClients;
+pub use events::EventTimeline;
+pub use informer::InformerCache;
+pub use logs::LogManager;
+pub use metrics::MetricsPoller;
+pub use portforward::PortForwardManager;
+pub use watch::WatchManager;
+
+/// Error type for Kubernetes operations
+#[derive(Debug)]
+pub enum K8sError {
+    /// Kubeconfig could not be loaded or a client could not be built
+    Config(String),
+    /// The API server rejected the request
+    Api {
+        code: u16,
+        reason: String,
+        message: String,
+    },
+    /// A kind/resource name did not match anything in discovery
+    UnknownResource(String),
+    /// The request itself was malformed
+    InvalidRequest(String),
+    /// Transport or decoding failure
+    Request(String),
+    /// An offline lookup missed the response cache
+    Uncached(String),
+}
+
+impl fmt::Display for K8sError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(msg) => write!(f, "Kubernetes configuration error: {}", msg),
+            Self::Api {
+                code,
+                reason,
+                message,
+            } => write!(f, "{} ({}): {}", reason, code, message),
+            Self::UnknownResource(name) => {
+                write!(f, "the server doesn't have a resource type \"{}\"", name)
+            }
+            Self::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
+            Self::Request(msg) => write!(f, "Kubernetes request failed: {}", msg),
+            Self::Uncached(path) => write!(f, "{} has not been fetched from the cluster yet", path),
+        }
+    }
+}
+
+impl std::error::Error for K8sError {}
+
+impl From<kube::Error> for K8sError {
+    fn from(err: kube::Error) -> Self {
+        match err {
+            kube::Error::Api(response) => K8sError::Api {
+                code: response.code,
+                reason: response.reason,
+                message: response.message,
+            },
+            kube::Error::BuildRequest(e) => K8sError::InvalidRequest(e.to_string()),
+            other => K8sError::Request(other.to_string()),
+        }
+    }
+}
+
+impl From<kube::core::request::Error> for K8sError {
+    fn from(err: kube::core::request::Error) -> Self {
+        K8sError::InvalidRequest(err.to_string())
+    }
+}
+
+impl From<K8sError> for String {
+    fn from(err: K8sError) -> Self {
+        err.to_string()
+    }
+}
+
+/// Result type for Kubernetes operations
+pub type K8sResult<T> = Result<T, K8sError>;
+
+/// Arguments of the `unwatch` method
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WatchIdArgs {
+    watch_id: String,
+}
+
+/// Arguments of the `cache_release` method
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheIdArgs {
+    cache_id: String,
+}
+
+/// Arguments of the `stop_logs` method
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamIdArgs {
+    stream_id: String,
+}
+
+/// Serialize an operation result for exec_invoke
+fn to_value<T: serde::Serialize>(value: T) -> Result<serde_json::Value, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+/// Dispatch a `kubectl` exec_invoke method
+pub async fn handle_operation(
+    app: &AppHandle,
+    window_label: &str,
+    method: &str,
+    args: &[serde_json::Value],
+) -> Result<serde_json::Value, String> {
+    debug!("Kubernetes operation: {}", method);
+
+    let clients = app.state::<KubeClients>();
+    match method {
+        "get" | "list" => {
+            let query: resources::ResourceQuery = parse_args(args)?;
+            if query.is_multi_context() {
+                to_value(fanout::list(&clients, &query).await?)
+            } else if method == "get" {
+                to_value(resources::get(&clients, &query).await?)
+            } else {
+                to_value(resources::list(&clients, &query).await?)
+            }
+        }
+        "describe" => {
+            let query: resources::ResourceQuery = parse_args(args)?;
+            to_value(resources::describe(&clients, &query).await?)
+        }
+        "delete" => {
+            let request: resources::DeleteRequest = parse_args(args)?;
+            let result = resources::delete(&clients, &request).await;
+            audit::record_delete(app, &request, &result).await;
+            to_value(result?)
+        }
+        "diff" => {
+            let request: apply::ApplyRequest = parse_args(args)?;
+            to_value(apply::diff(&clients, &request).await?)
+        }
+        "apply" => {
+            let request: apply::ApplyRequest = parse_args(args)?;
+            let result = apply::apply(&clients, &request).await;
+            audit::record_apply(app, &request, &result).await;
+            to_value(result?)
+        }
+        "graph" => {
+            let request: graph::GraphRequest = parse_args(args)?;
+            to_value(graph::graph(&clients, &request).await?)
+        }
+        "validate" => {
+            let request: validate::ValidateRequest = parse_args(args)?;
+            to_value(validate::validate(&clients, &request).await?)
+        }
+        "access" => {
+            let request: rbac::AccessRequest = parse_args(args)?;
+            to_value(rbac::access(&clients, &request).await?)
+        }
+        "watch" => {
+            let request: watch::WatchRequest = parse_args(args)?;
+            let watches = app.state::<WatchManager>();
+            to_value(watches.watch(app, &clients, window_label, request).await?)
+        }
+        "unwatch" => {
+            let args: WatchIdArgs = parse_args(args)?;
+            app.state::<WatchManager>().unwatch(&args.watch_id)?;
+            Ok(serde_json::Value::Null)
+        }
+        "cache_acquire" => {
+            let request: informer::CacheRequest = parse_args(args)?;
+            let cache = app.state::<InformerCache>();
+            to_value(cache.acquire(&clients, window_label, &request).await?)
+        }
+        "cache_list" => {
+            let request: informer::CacheRequest = parse_args(args)?;
+            to_value(
+                app.state::<InformerCache>()
+                    .list(&clients, &request)
+                    .await?,
+            )
+        }
+        "cache_release" => {
+            let args: CacheIdArgs = parse_args(args)?;
+            app.state::<InformerCache>()
+                .release(&args.cache_id, window_label)?;
+            Ok(serde_json::Value::Null)
+        }
+        "logs" => {
+            let request: logs::LogsRequest = parse_args(args)?;
+            let streams = app.state::<LogManager>();
+            to_value(streams.logs(app, &clients, window_label, request).await?)
+        }
+        "stop_logs" => {
+            let args: StreamIdArgs = parse_args(args)?;
+            app.state::<LogManager>().stop(&args.stream_id)?;
+            Ok(serde_json::Value::Null)
+        }
+        _ => Err(format!("Unknown kubectl method: {}", method)),
+    }
+}
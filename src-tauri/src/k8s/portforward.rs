@@ -0,0 +1,958 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Long-lived port-forwards owned by the backend
+//!
+//! A forward listens on a local port and tunnels every accepted connection
+//! to a pod over the API server's portforward subresource. The pod is
+//! resolved per connection, so when the target pod is replaced (a rollout,
+//! an eviction) new connections go to its successor: for a service, any
+//! running pod behind it; for a pod, a running pod with the same
+//! controller-assigned labels.
+//!
+//! Forwards are recorded in the command palette database. Those started
+//! with `restore` are started again on the next launch; the others are
+//! dropped from the registry then.
+
+use super::audit::{self, AuditEvent};
+use super::client::KubeClients;
+use super::{K8sError, K8sResult};
+use crate::command_palette::{CommandPaletteDb, SavedPortForward};
+use chrono::Utc;
+use k8s_openapi::api::core::v1::{Pod, Service};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::api::{Api, ListParams};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{AbortHandle, JoinSet};
+
+/// Event broadcast to all windows whenever a forward changes status
+pub const PORT_FORWARD_EVENT: &str = "port-forward-changed";
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1";
+
+/// Labels that differ between a pod and its replacement
+const POD_INSTANCE_LABELS: [&str; 4] = [
+    "pod-template-hash",
+    "controller-revision-hash",
+    "statefulset.kubernetes.io/pod-name",
+    "apps.kubernetes.io/pod-index",
+];
+
+/// What a forward points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    Pod,
+    Service,
+}
+
+impl TargetKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            TargetKind::Pod => "pod",
+            TargetKind::Service => "service",
+        }
+    }
+}
+
+/// Arguments of `start_port_forward`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortForwardRequest {
+    pub kind: TargetKind,
+    pub name: String,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Local address to listen on, `127.0.0.1` by default
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Picked by the OS if absent
+    #[serde(default)]
+    pub local_port: Option<u16>,
+    /// Container port for pods, service port for services
+    pub remote_port: u16,
+    /// Start the forward again on the next launch
+    #[serde(default)]
+    pub restore: bool,
+}
+
+/// Lifecycle of a forward
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardStatus {
+    /// Listening, no pod resolved yet
+    Listening,
+    /// Connections go to `pod`
+    Active,
+    /// The last pod went away and no replacement was found yet
+    Reconnecting,
+    Stopped,
+}
+
+/// A forward as reported to the renderer
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortForwardInfo {
+    pub id: String,
+    pub kind: TargetKind,
+    pub name: String,
+    pub namespace: String,
+    pub context: String,
+    pub address: String,
+    pub local_port: u16,
+    pub remote_port: u16,
+    pub restore: bool,
+    pub status: ForwardStatus,
+    /// Pod connections currently go to
+    pub pod: Option<String>,
+    /// Last resolution or connection error
+    pub error: Option<String>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub active_connections: usize,
+}
+
+/// Mutable part of a forward
+struct ForwardState {
+    status: ForwardStatus,
+    /// Resolved pod and the container port to forward to
+    pod: Option<(String, u16)>,
+    error: Option<String>,
+}
+
+/// State shared between a forward's tasks and the manager
+struct Forward {
+    id: String,
+    request: PortForwardRequest,
+    namespace: String,
+    context: String,
+    local_port: u16,
+    /// Registry row, if it was persisted
+    db_id: Option<i64>,
+    /// Selector finding the replacement of a forwarded pod
+    replacement_selector: Option<String>,
+    state: Mutex<ForwardState>,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    active_connections: AtomicUsize,
+}
+
+impl Forward {
+    fn info(&self) -> PortForwardInfo {
+        let state = self.state.lock().unwrap();
+        PortForwardInfo {
+            id: self.id.clone(),
+            kind: self.request.kind,
+            name: self.request.name.clone(),
+            namespace: self.namespace.clone(),
+            context: self.context.clone(),
+            address: self
+                .request
+                .address
+                .clone()
+                .unwrap_or_else(|| DEFAULT_ADDRESS.to_string()),
+            local_port: self.local_port,
+            remote_port: self.request.remote_port,
+            restore: self.request.restore,
+            status: state.status,
+            pod: state.pod.as_ref().map(|(pod, _)| pod.clone()),
+            error: state.error.clone(),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Tracks the running forwards of the app
+pub struct PortForwardManager {
+    forwards: Mutex<HashMap<String, (Arc<Forward>, AbortHandle)>>,
+    next_id: AtomicUsize,
+}
+
+impl Default for PortForwardManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PortForwardManager {
+    pub fn new() -> Self {
+        PortForwardManager {
+            forwards: Mutex::new(HashMap::new()),
+            next_id: AtomicUsize::new(1),
+        }
+    }
+
+    /// Start listening and forwarding
+    ///
+    /// `db_id` is the registry row of a restored forward; new forwards are
+    /// added to the registry.
+    pub async fn start(
+        &self,
+        app: &AppHandle,
+        clients: &KubeClients,
+        request: PortForwardRequest,
+        db_id: Option<i64>,
+    ) -> K8sResult<PortForwardInfo> {
+        let event = AuditEvent::new("port-forward", request.context.as_deref()).object(
+            Some(request.kind.as_str()),
+            request.namespace.as_deref(),
+            Some(&request.name),
+        );
+        let result = self.listen(app, clients, request, db_id).await;
+        let event = match result {
+            Ok(ref info) => AuditEvent::new("port-forward", Some(&info.context))
+                .object(
+                    Some(info.kind.as_str()),
+                    Some(&info.namespace),
+                    Some(&info.name),
+                )
+                .detail(format!(
+                    "{}:{} -> {}",
+                    info.address, info.local_port, info.remote_port
+                )),
+            Err(_) => event,
+        };
+        audit::record(app, event.result(&result)).await;
+        result
+    }
+
+    /// `start` without the audit entry
+    async fn listen(
+        &self,
+        app: &AppHandle,
+        clients: &KubeClients,
+        request: PortForwardRequest,
+        db_id: Option<i64>,
+    ) -> K8sResult<PortForwardInfo> {
+        let client = clients.client(request.context.as_deref()).await?;
+        let namespace = request
+            .namespace
+            .clone()
+            .unwrap_or_else(|| client.default_namespace().to_string());
+        let pods: Api<Pod> = Api::namespaced(client.client.clone(), &namespace);
+
+        // Fail early on targets that do not exist
+        let replacement_selector = match request.kind {
+            TargetKind::Pod => replacement_selector(&pods.get(&request.name).await?),
+            TargetKind::Service => {
+                let services: Api<Service> = Api::namespaced(client.client.clone(), &namespace);
+                services.get(&request.name).await?;
+                None
+            }
+        };
+
+        let address = request.address.as_deref().unwrap_or(DEFAULT_ADDRESS);
+        let listener = TcpListener::bind((address, request.local_port.unwrap_or(0)))
+            .await
+            .map_err(|e| K8sError::Request(format!("Failed to listen on {}: {}", address, e)))?;
+        let local_port = listener
+            .local_addr()
+            .map_err(|e| K8sError::Request(e.to_string()))?
+            .port();
+
+        let db_id = match db_id {
+            Some(db_id) => Some(db_id),
+            None => {
+                register(
+                    app,
+                    &request,
+                    &namespace,
+                    client.context.as_str(),
+                    local_port,
+                )
+                .await
+            }
+        };
+
+        let forward = Arc::new(Forward {
+            id: format!("pf-{}", self.next_id.fetch_add(1, Ordering::SeqCst)),
+            request,
+            namespace: namespace.clone(),
+            context: client.context.clone(),
+            local_port,
+            db_id,
+            replacement_selector,
+            state: Mutex::new(ForwardState {
+                status: ForwardStatus::Listening,
+                pod: None,
+                error: None,
+            }),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            active_connections: AtomicUsize::new(0),
+        });
+
+        let task = tokio::spawn(serve(
+            app.clone(),
+            Arc::clone(&forward),
+            listener,
+            pods,
+            Api::namespaced(client.client.clone(), &namespace),
+        ));
+        self.forwards.lock().unwrap().insert(
+            forward.id.clone(),
+            (Arc::clone(&forward), task.abort_handle()),
+        );
+
+        let info = forward.info();
+        info!(
+            "Forwarding {}:{} to {} {}/{}:{}",
+            info.address,
+            local_port,
+            info.kind.as_str(),
+            namespace,
+            info.name,
+            info.remote_port
+        );
+        broadcast(app, info.clone());
+        Ok(info)
+    }
+
+    /// Every running forward
+    pub fn list(&self) -> Vec<PortForwardInfo> {
+        let mut forwards: Vec<_> = self
+            .forwards
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(forward, _)| forward.info())
+            .collect();
+        forwards.sort_by_key(|f| f.local_port);
+        forwards
+    }
+
+    /// Stop a forward and drop it from the registry
+    pub async fn stop(&self, app: &AppHandle, id: &str) -> K8sResult<()> {
+        let (forward, task) = self
+            .forwards
+            .lock()
+            .unwrap()
+            .remove(id)
+            .ok_or_else(|| K8sError::InvalidRequest(format!("Unknown port-forward: {}", id)))?;
+
+        // Aborting the listener task also drops its open connections
+        task.abort();
+        if let Some(db_id) = forward.db_id {
+            unregister(app, db_id).await;
+        }
+
+        forward.state.lock().unwrap().status = ForwardStatus::Stopped;
+        info!("Stopped port-forward {}", id);
+        audit::record(
+            app,
+            AuditEvent::new("stop-port-forward", Some(&forward.context)).object(
+                Some(forward.request.kind.as_str()),
+                Some(&forward.namespace),
+                Some(&forward.request.name),
+            ),
+        )
+        .await;
+        broadcast(app, forward.info());
+        Ok(())
+    }
+
+    /// Restart the forwards registered with `restore` and forget the others
+    pub async fn restore(&self, app: &AppHandle, clients: &KubeClients) {
+        let saved = match app.state::<CommandPaletteDb>().get_port_forwards() {
+            Ok(saved) => saved,
+            Err(e) => {
+                warn!("Failed to read port-forward registry: {}", e);
+                return;
+            }
+        };
+
+        for forward in saved {
+            if !forward.restore {
+                unregister(app, forward.id).await;
+                continue;
+            }
+
+            let kind = match forward.target_kind.as_str() {
+                "service" => TargetKind::Service,
+                _ => TargetKind::Pod,
+            };
+            let request = PortForwardRequest {
+                kind,
+                name: forward.target_name,
+                namespace: Some(forward.namespace),
+                context: forward.context,
+                address: Some(forward.address),
+                local_port: Some(forward.local_port),
+                remote_port: forward.remote_port,
+                restore: true,
+            };
+
+            // Keep the entry on failure, the cluster may just be unreachable
+            if let Err(e) = self.start(app, clients, request, Some(forward.id)).await {
+                warn!("Failed to restore port-forward {}: {}", forward.id, e);
+            }
+        }
+    }
+}
+
+/// Persist a new forward, returning its registry id
+async fn register(
+    app: &AppHandle,
+    request: &PortForwardRequest,
+    namespace: &str,
+    context: &str,
+    local_port: u16,
+) -> Option<i64> {
+    let saved = SavedPortForward {
+        id: 0,
+        context: Some(context.to_string()),
+        namespace: namespace.to_string(),
+        target_kind: request.kind.as_str().to_string(),
+        target_name: request.name.clone(),
+        address: request
+            .address
+            .clone()
+            .unwrap_or_else(|| DEFAULT_ADDRESS.to_string()),
+        local_port,
+        remote_port: request.remote_port,
+        restore: request.restore,
+        created_at: Utc::now().to_rfc3339(),
+    };
+
+    app.state::<CommandPaletteDb>()
+        .save_port_forward(&saved)
+        .await
+        .map_err(|e| warn!("Failed to save port-forward: {}", e))
+        .ok()
+}
+
+async fn unregister(app: &AppHandle, db_id: i64) {
+    if let Err(e) = app
+        .state::<CommandPaletteDb>()
+        .delete_port_forward(db_id)
+        .await
+    {
+        warn!(
+            "Failed to remove port-forward {} from registry: {}",
+            db_id, e
+        );
+    }
+}
+
+fn broadcast(app: &AppHandle, info: PortForwardInfo) {
+    if let Err(e) = app.emit(PORT_FORWARD_EVENT, info) {
+        error!("Failed to emit {} event: {}", PORT_FORWARD_EVENT, e);
+    }
+}
+
+/// Label selector matching the replacements of a pod, if it has a controller
+fn replacement_selector(pod: &Pod) -> Option<String> {
+    pod.metadata.owner_references.as_ref()?;
+
+    let labels: BTreeMap<_, _> = pod
+        .metadata
+        .labels
+        .as_ref()?
+        .iter()
+        .filter(|(key, _)| !POD_INSTANCE_LABELS.contains(&key.as_str()))
+        .collect();
+    if labels.is_empty() {
+        return None;
+    }
+
+    Some(
+        labels
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
+/// Whether a pod can accept forwarded connections
+fn is_running(pod: &Pod) -> bool {
+    pod.metadata.deletion_timestamp.is_none()
+        && pod.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running")
+}
+
+/// Container port a service port is served on by a pod
+fn target_port(service: &Service, pod: &Pod, port: u16) -> K8sResult<u16> {
+    let service_port = service
+        .spec
+        .as_ref()
+        .and_then(|s| s.ports.as_ref())
+        .and_then(|ports| ports.iter().find(|p| p.port == i32::from(port)))
+        .ok_or_else(|| {
+            K8sError::InvalidRequest(format!(
+                "service {} has no port {}",
+                service.metadata.name.as_deref().unwrap_or_default(),
+                port
+            ))
+        })?;
+
+    let target = match service_port.target_port {
+        Some(IntOrString::Int(target)) => return Ok(target as u16),
+        Some(IntOrString::String(ref name)) => name,
+        None => return Ok(port),
+    };
+
+    pod.spec
+        .iter()
+        .flat_map(|spec| spec.containers.iter())
+        .flat_map(|c| c.ports.iter().flatten())
+        .find(|p| p.name.as_deref() == Some(target.as_str()))
+        .map(|p| p.container_port as u16)
+        .ok_or_else(|| {
+            K8sError::InvalidRequest(format!(
+                "pod {} has no port named {}",
+                pod.metadata.name.as_deref().unwrap_or_default(),
+                target
+            ))
+        })
+}
+
+/// Find the pod and container port connections should go to
+async fn resolve(
+    forward: &Forward,
+    pods: &Api<Pod>,
+    services: &Api<Service>,
+) -> K8sResult<(String, u16)> {
+    let cached = forward.state.lock().unwrap().pod.clone();
+    if let Some((pod, port)) = cached {
+        if pods.get_opt(&pod).await?.is_some_and(|p| is_running(&p)) {
+            return Ok((pod, port));
+        }
+        debug!("Port-forward {} lost pod {}", forward.id, pod);
+    }
+
+    let request = &forward.request;
+    let (selector, service) = match request.kind {
+        TargetKind::Pod => {
+            if pods
+                .get_opt(&request.name)
+                .await?
+                .is_some_and(|p| is_running(&p))
+            {
+                return Ok((request.name.clone(), request.remote_port));
+            }
+            match forward.replacement_selector {
+                Some(ref selector) => (selector.clone(), None),
+                None => {
+                    return Err(K8sError::InvalidRequest(format!(
+                        "pod {} is not running",
+                        request.name
+                    )))
+                }
+            }
+        }
+        TargetKind::Service => {
+            let service = services.get(&request.name).await?;
+            let selector = service
+                .spec
+                .as_ref()
+                .and_then(|s| s.selector.as_ref())
+                .filter(|s| !s.is_empty())
+                .map(|s| {
+                    s.iter()
+                        .map(|(k, v)| format!("{}={}", k, v))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .ok_or_else(|| {
+                    K8sError::InvalidRequest(format!("service {} has no selector", request.name))
+                })?;
+            (selector, Some(service))
+        }
+    };
+
+    let pod = pods
+        .list(&ListParams::default().labels(&selector))
+        .await?
+        .items
+        .into_iter()
+        .find(is_running)
+        .ok_or_else(|| K8sError::InvalidRequest(format!("no running pods match {}", selector)))?;
+
+    let port = match service {
+        Some(ref service) => target_port(service, &pod, request.remote_port)?,
+        None => request.remote_port,
+    };
+    Ok((pod.metadata.name.unwrap_or_default(), port))
+}
+
+/// Record the outcome of a resolution, broadcasting status changes
+fn update_state(app: &AppHandle, forward: &Forward, result: &K8sResult<(String, u16)>) {
+    let changed = {
+        let mut state = forward.state.lock().unwrap();
+        let before = (state.status, state.pod.clone());
+        match result {
+            Ok(target) => {
+                state.status = ForwardStatus::Active;
+                state.pod = Some(target.clone());
+                state.error = None;
+            }
+            Err(e) => {
+                state.status = ForwardStatus::Reconnecting;
+                state.pod = None;
+                state.error = Some(e.to_string());
+            }
+        }
+        before != (state.status, state.pod.clone())
+    };
+
+    if changed {
+        broadcast(app, forward.info());
+    }
+}
+
+/// Accept connections until the forward is stopped
+async fn serve(
+    app: AppHandle,
+    forward: Arc<Forward>,
+    listener: TcpListener,
+    pods: Api<Pod>,
+    services: Api<Service>,
+) {
+    // Dropping the set when the task is aborted closes every connection
+    let mut connections = JoinSet::new();
+
+    loop {
+        let socket = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    warn!("Port-forward {} failed to accept: {}", forward.id, e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            Some(_) = connections.join_next() => continue,
+        };
+
+        // Resolve in the connection's task, so a slow API server doesn't
+        // hold up accepting the connections that follow
+        let app = app.clone();
+        let forward = Arc::clone(&forward);
+        let pods = pods.clone();
+        let services = services.clone();
+        connections.spawn(async move {
+            let result = resolve(&forward, &pods, &services).await;
+            update_state(&app, &forward, &result);
+            let Ok((pod, port)) = result else {
+                return;
+            };
+
+            forward.active_connections.fetch_add(1, Ordering::Relaxed);
+            if let Err(e) = tunnel(&forward, &pods, &pod, port, socket).await {
+                debug!("Port-forward {} connection ended: {}", forward.id, e);
+            }
+            forward.active_connections.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+/// Tunnel one local connection to a pod port
+async fn tunnel(
+    forward: &Forward,
+    pods: &Api<Pod>,
+    pod: &str,
+    port: u16,
+    mut socket: TcpStream,
+) -> K8sResult<()> {
+    let mut forwarder = pods.portforward(pod, &[port]).await?;
+    let upstream = forwarder
+        .take_stream(port)
+        .ok_or_else(|| K8sError::Request(format!("no stream for port {}", port)))?;
+
+    let (mut local_read, mut local_write) = socket.split();
+    let (mut remote_read, mut remote_write) = tokio::io::split(upstream);
+    let result = tokio::try_join!(
+        copy_counting(&mut local_read, &mut remote_write, &forward.bytes_sent),
+        copy_counting(&mut remote_read, &mut local_write, &forward.bytes_received),
+    );
+
+    forwarder.abort();
+    result
+        .map(|_| ())
+        .map_err(|e| K8sError::Request(e.to_string()))
+}
+
+/// Copy until EOF, adding the bytes copied to `counter` as they pass
+async fn copy_counting<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    counter: &AtomicU64,
+) -> std::io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = [0u8; 16384];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return writer.shutdown().await;
+        }
+        writer.write_all(&buf[..n]).await?;
+        counter.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+/// Tauri command: Start a port-forward
+#[tauri::command]
+pub async fn start_port_forward(
+    app: AppHandle,
+    forwards: State<'_, PortForwardManager>,
+    clients: State<'_, KubeClients>,
+    request: PortForwardRequest,
+) -> Result<PortForwardInfo, String> {
+    forwards
+        .start(&app, &clients, request, None)
+        .await
+        .map_err(|e| format!("Failed to start port-forward: {}", e))
+}
+
+/// Tauri command: List running port-forwards
+#[tauri::command]
+pub async fn list_port_forwards(
+    forwards: State<'_, PortForwardManager>,
+) -> Result<Vec<PortForwardInfo>, String> {
+    Ok(forwards.list())
+}
+
+/// Tauri command: Stop a port-forward
+#[tauri::command]
+pub async fn stop_port_forward(
+    app: AppHandle,
+    forwards: State<'_, PortForwardManager>,
+    id: String,
+) -> Result<(), String> {
+    forwards.stop(&app, &id).await.map_err(String::from)
+}
+
+/// Restore registered forwards in the background after startup
+pub fn restore_on_startup(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let forwards = app.state::<PortForwardManager>();
+        let clients = app.state::<KubeClients>();
+        forwards.restore(&app, &clients).await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::mock;
+    use http::StatusCode;
+    use k8s_openapi::api::core::v1::{Container, ContainerPort, PodSpec, ServicePort, ServiceSpec};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+    use serde_json::{json, Value};
+
+    fn forward(kind: TargetKind, name: &str, replacement_selector: Option<&str>) -> Forward {
+        Forward {
+            id: "pf-1".to_string(),
+            request: PortForwardRequest {
+                kind,
+                name: name.to_string(),
+                namespace: Some("shop".to_string()),
+                context: None,
+                address: None,
+                local_port: None,
+                remote_port: 80,
+                restore: false,
+            },
+            namespace: "shop".to_string(),
+            context: "test".to_string(),
+            local_port: 8080,
+            db_id: None,
+            replacement_selector: replacement_selector.map(String::from),
+            state: Mutex::new(ForwardState {
+                status: ForwardStatus::Listening,
+                pod: None,
+                error: None,
+            }),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            active_connections: AtomicUsize::new(0),
+        }
+    }
+
+    fn pod(name: &str, phase: &str, deleting: bool) -> Value {
+        let mut pod = json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {"name": name, "namespace": "shop", "labels": {"app": "web"}},
+            "spec": {"containers": [{
+                "name": "web",
+                "ports": [{"name": "http", "containerPort": 8080}]
+            }]},
+            "status": {"phase": phase}
+        });
+        if deleting {
+            pod["metadata"]["deletionTimestamp"] = json!("2025-03-01T10:00:00Z");
+        }
+        pod
+    }
+
+    fn pod_list(items: Vec<Value>) -> Value {
+        json!({"apiVersion": "v1", "kind": "PodList", "metadata": {}, "items": items})
+    }
+
+    #[test]
+    fn test_replacement_selector_drops_instance_labels() {
+        let mut pod = Pod::default();
+        pod.metadata.labels = Some(BTreeMap::from([
+            ("app".to_string(), "web".to_string()),
+            ("pod-template-hash".to_string(), "5d4f8".to_string()),
+        ]));
+        assert_eq!(replacement_selector(&pod), None);
+
+        pod.metadata.owner_references = Some(vec![OwnerReference::default()]);
+        assert_eq!(replacement_selector(&pod).as_deref(), Some("app=web"));
+    }
+
+    #[test]
+    fn test_target_port() {
+        let service = Service {
+            spec: Some(ServiceSpec {
+                ports: Some(vec![
+                    ServicePort {
+                        port: 80,
+                        target_port: Some(IntOrString::String("http".to_string())),
+                        ..Default::default()
+                    },
+                    ServicePort {
+                        port: 443,
+                        target_port: Some(IntOrString::Int(8443)),
+                        ..Default::default()
+                    },
+                ]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let pod = Pod {
+            spec: Some(PodSpec {
+                containers: vec![Container {
+                    ports: Some(vec![ContainerPort {
+                        name: Some("http".to_string()),
+                        container_port: 8080,
+                        ..Default::default()
+                    }]),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(target_port(&service, &pod, 80).unwrap(), 8080);
+        assert_eq!(target_port(&service, &pod, 443).unwrap(), 8443);
+        assert!(target_port(&service, &pod, 22).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_follows_replacement_pod() {
+        let (client, requests) = mock::client(|request| match request.path.as_str() {
+            "/api/v1/namespaces/shop/pods/web-abc" => {
+                (StatusCode::OK, pod("web-abc", "Running", true))
+            }
+            "/api/v1/namespaces/shop/pods/web-def" => {
+                (StatusCode::OK, pod("web-def", "Running", false))
+            }
+            "/api/v1/namespaces/shop/pods" => (
+                StatusCode::OK,
+                pod_list(vec![
+                    pod("web-abc", "Running", true),
+                    pod("web-xyz", "Pending", false),
+                    pod("web-def", "Running", false),
+                ]),
+            ),
+            _ => mock::failure(StatusCode::NOT_FOUND, "NotFound", "not found"),
+        });
+        let pods: Api<Pod> = Api::namespaced(client.clone(), "shop");
+        let services: Api<Service> = Api::namespaced(client, "shop");
+
+        // The forwarded pod is terminating; a running sibling takes over
+        let forward = forward(TargetKind::Pod, "web-abc", Some("app=web"));
+        let target = resolve(&forward, &pods, &services).await.unwrap();
+        assert_eq!(target, ("web-def".to_string(), 80));
+        assert!(requests.lock().unwrap()[1]
+            .query
+            .contains("labelSelector=app%3Dweb"));
+
+        // Later connections reuse the running pod without listing again
+        forward.state.lock().unwrap().pod = Some(target);
+        requests.lock().unwrap().clear();
+        let target = resolve(&forward, &pods, &services).await.unwrap();
+        assert_eq!(target, ("web-def".to_string(), 80));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        // Without a controller there is nothing to fall back to
+        let forward = self::forward(TargetKind::Pod, "web-abc", None);
+        assert!(resolve(&forward, &pods, &services).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_service() {
+        let (client, _) = mock::client(|request| match request.path.as_str() {
+            "/api/v1/namespaces/shop/services/web" => {
+                let service = json!({
+                    "apiVersion": "v1",
+                    "kind": "Service",
+                    "metadata": {"name": "web", "namespace": "shop"},
+                    "spec": {
+                        "selector": {"app": "web"},
+                        "ports": [{"port": 80, "targetPort": "http"}]
+                    }
+                });
+                (StatusCode::OK, service)
+            }
+            "/api/v1/namespaces/shop/pods" => (
+                StatusCode::OK,
+                pod_list(vec![pod("web-def", "Running", false)]),
+            ),
+            _ => mock::failure(StatusCode::NOT_FOUND, "NotFound", "not found"),
+        });
+        let pods: Api<Pod> = Api::namespaced(client.clone(), "shop");
+        let services: Api<Service> = Api::namespaced(client, "shop");
+
+        let forward = forward(TargetKind::Service, "web", None);
+        let target = resolve(&forward, &pods, &services).await.unwrap();
+        assert_eq!(target, ("web-def".to_string(), 8080));
+    }
+
+    #[tokio::test]
+    async fn test_copy_counting() {
+        let (mut local, mut remote) = tokio::io::duplex(64);
+        local.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        drop(local);
+
+        let counter = AtomicU64::new(0);
+        let mut copied = Vec::new();
+        copy_counting(&mut remote, &mut copied, &counter)
+            .await
+            .unwrap();
+        assert_eq!(copied, b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(counter.load(Ordering::Relaxed), copied.len() as u64);
+    }
+}
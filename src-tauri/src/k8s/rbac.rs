@@ -0,0 +1,288 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! What the current user may do, like `kubectl auth can-i --list`
+//!
+//! One SelfSubjectRulesReview gives the rules that apply in a namespace,
+//! from which the whole verb × resource matrix is computed. Authorizers
+//! other than RBAC (webhooks, for example) can't always enumerate their
+//! rules; the review then reports itself incomplete, and the cells the
+//! rules deny are checked one by one with SelfSubjectAccessReviews.
+
+use super::client::KubeClients;
+use super::discovery::ApiResourceInfo;
+use super::resources;
+use super::K8sResult;
+use futures::StreamExt;
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, ResourceRule, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+    SelfSubjectRulesReview, SelfSubjectRulesReviewSpec,
+};
+use kube::api::{Api, PostParams};
+use kube::Client;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+/// Verbs in the matrix when the request doesn't name any
+const DEFAULT_VERBS: [&str; 8] = [
+    "get",
+    "list",
+    "watch",
+    "create",
+    "update",
+    "patch",
+    "delete",
+    "deletecollection",
+];
+
+/// Access reviews made to complete an incomplete rules review
+const MAX_REVIEWS: usize = 256;
+
+/// Access reviews in flight at once
+const CONCURRENT_REVIEWS: usize = 16;
+
+/// Arguments of the `access` method
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessRequest {
+    /// Defaults to the context's namespace
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Defaults to the current context
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Resource types as typed by the user; defaults to all discovered types
+    #[serde(default)]
+    pub resources: Option<Vec<String>>,
+    /// Defaults to the standard verbs
+    #[serde(default)]
+    pub verbs: Option<Vec<String>>,
+}
+
+/// Permissions on one resource type
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessRow {
+    pub resource: ApiResourceInfo,
+    /// One entry per verb of the matrix; `null` where the resource doesn't
+    /// support the verb
+    pub allowed: Vec<Option<bool>>,
+}
+
+/// Verb × resource permissions of the current user in a namespace
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessMatrix {
+    pub context: String,
+    pub namespace: String,
+    pub verbs: Vec<String>,
+    pub rows: Vec<AccessRow>,
+    /// Whether some denials could not be confirmed, so the matrix may
+    /// understate what is allowed
+    pub incomplete: bool,
+}
+
+/// Compute the permission matrix of the current user
+pub async fn access(clients: &KubeClients, request: &AccessRequest) -> K8sResult<AccessMatrix> {
+    let client = clients.client(request.context.as_deref()).await?;
+    let namespace = request
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+    let verbs: Vec<String> = match request.verbs {
+        Some(ref verbs) => verbs.clone(),
+        None => DEFAULT_VERBS.iter().map(|v| v.to_string()).collect(),
+    };
+
+    let infos: Vec<ApiResourceInfo> = match request.resources {
+        Some(ref names) => {
+            let mut infos = Vec::new();
+            for name in names {
+                infos.push(resources::resolve_kind(clients, &client, name).await?);
+            }
+            infos
+        }
+        None => clients.discovery(&client, false).await?.resources.clone(),
+    };
+
+    let review = SelfSubjectRulesReview {
+        spec: SelfSubjectRulesReviewSpec {
+            namespace: Some(namespace.clone()),
+        },
+        ..Default::default()
+    };
+    let status = Api::<SelfSubjectRulesReview>::all(client.client.clone())
+        .create(&PostParams::default(), &review)
+        .await?
+        .status
+        .unwrap_or_default();
+    if let Some(ref error) = status.evaluation_error {
+        debug!("Rules review in {} is partial: {}", namespace, error);
+    }
+
+    let mut rows: Vec<AccessRow> = infos
+        .into_iter()
+        .map(|info| {
+            let allowed = verbs
+                .iter()
+                .map(|verb| {
+                    info.supports(verb)
+                        .then(|| rules_allow(&status.resource_rules, &info, verb))
+                })
+                .collect();
+            AccessRow {
+                resource: info,
+                allowed,
+            }
+        })
+        .collect();
+
+    // Confirm denials with the authorizers that couldn't list their rules
+    let mut incomplete = false;
+    if status.incomplete {
+        let denied: Vec<(usize, usize)> = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| {
+                row.allowed
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, allowed)| **allowed == Some(false))
+                    .map(move |(v, _)| (r, v))
+            })
+            .collect();
+        incomplete = denied.len() > MAX_REVIEWS;
+
+        let reviews = denied.into_iter().take(MAX_REVIEWS).map(|(r, v)| {
+            let attributes = resource_attributes(&rows[r].resource, &verbs[v], &namespace);
+            let client = client.client.clone();
+            async move { ((r, v), can_i(client, attributes).await) }
+        });
+        let results: Vec<_> = futures::stream::iter(reviews)
+            .buffer_unordered(CONCURRENT_REVIEWS)
+            .collect()
+            .await;
+        for ((r, v), result) in results {
+            match result {
+                Ok(allowed) => rows[r].allowed[v] = Some(allowed),
+                Err(e) => {
+                    warn!("Access review failed: {}", e);
+                    incomplete = true;
+                }
+            }
+        }
+    }
+
+    Ok(AccessMatrix {
+        context: client.context.clone(),
+        namespace,
+        verbs,
+        rows,
+        incomplete,
+    })
+}
+
+/// Whether rules allow a verb on every object of a resource type
+///
+/// Rules limited to named objects don't count, as they don't allow the verb
+/// in general.
+fn rules_allow(rules: &[ResourceRule], info: &ApiResourceInfo, verb: &str) -> bool {
+    let matches = |values: &Option<Vec<String>>, wanted: &str| {
+        values
+            .iter()
+            .flatten()
+            .any(|value| value == "*" || value == wanted)
+    };
+
+    rules.iter().any(|rule| {
+        rule.verbs.iter().any(|v| v == "*" || v == verb)
+            && matches(&rule.api_groups, &info.group)
+            && matches(&rule.resources, &info.plural)
+            && rule
+                .resource_names
+                .as_ref()
+                .is_none_or(|names| names.is_empty())
+    })
+}
+
+fn resource_attributes(info: &ApiResourceInfo, verb: &str, namespace: &str) -> ResourceAttributes {
+    ResourceAttributes {
+        group: Some(info.group.clone()),
+        version: Some(info.version.clone()),
+        resource: Some(info.plural.clone()),
+        verb: Some(verb.to_string()),
+        namespace: info.namespaced.then(|| namespace.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Ask the API server whether the current user may do something
+async fn can_i(client: Client, attributes: ResourceAttributes) -> K8sResult<bool> {
+    let review = SelfSubjectAccessReview {
+        spec: SelfSubjectAccessReviewSpec {
+            resource_attributes: Some(attributes),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let review = Api::<SelfSubjectAccessReview>::all(client)
+        .create(&PostParams::default(), &review)
+        .await?;
+    Ok(review.status.is_some_and(|status| status.allowed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(verbs: &[&str], groups: &[&str], resources: &[&str], names: &[&str]) -> ResourceRule {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        ResourceRule {
+            verbs: strings(verbs),
+            api_groups: Some(strings(groups)),
+            resources: Some(strings(resources)),
+            resource_names: (!names.is_empty()).then(|| strings(names)),
+        }
+    }
+
+    #[test]
+    fn test_rules_allow() {
+        let pods = ApiResourceInfo {
+            group: String::new(),
+            version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            plural: "pods".to_string(),
+            singular: "pod".to_string(),
+            short_names: vec!["po".to_string()],
+            namespaced: true,
+            verbs: vec!["get".to_string(), "list".to_string(), "delete".to_string()],
+        };
+        let deployments = ApiResourceInfo {
+            group: "apps".to_string(),
+            kind: "Deployment".to_string(),
+            plural: "deployments".to_string(),
+            ..pods.clone()
+        };
+
+        let rules = vec![
+            rule(&["get", "list"], &[""], &["pods"], &[]),
+            rule(&["delete"], &[""], &["pods"], &["web-0"]),
+            rule(&["*"], &["apps"], &["*"], &[]),
+        ];
+        assert!(rules_allow(&rules, &pods, "list"));
+        assert!(!rules_allow(&rules, &pods, "delete"));
+        assert!(rules_allow(&rules, &deployments, "delete"));
+        assert!(!rules_allow(&rules[..2], &deployments, "get"));
+    }
+}
@@ -0,0 +1,663 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! get/list/describe/delete for arbitrary resource types
+//!
+//! Lists are requested in the API server's Table format, the same data
+//! `kubectl get` prints, so the renderer gets column definitions and cells
+//! without having to know anything about the resource type.
+
+use super::client::{ContextClient, KubeClients};
+use super::discovery::ApiResourceInfo;
+use super::{K8sError, K8sResult};
+use http::header::{HeaderValue, ACCEPT};
+use k8s_openapi::api::core::v1::Event;
+use kube::api::{Api, DeleteParams, DynamicObject, ListParams, PropagationPolicy};
+use kube::Resource;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+/// Accept header asking the API server to render a Table
+const TABLE_ACCEPT: &str = "application/json;as=Table;v=v1;g=meta.k8s.io,application/json";
+
+/// Identifies the resources an operation applies to
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceQuery {
+    /// Resource type as typed by the user: `pods`, `po`, `deploy.apps`, ...
+    pub kind: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Defaults to the context's namespace for namespaced resources
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Defaults to the current context
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Contexts to run a `get` or `list` across, instead of `context`
+    #[serde(default)]
+    pub contexts: Option<Vec<String>>,
+    /// Glob over context names to run a `get` or `list` across, e.g. `prod-*`
+    #[serde(default)]
+    pub context_glob: Option<String>,
+    /// Timeout for each cluster of a multi-context query
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub all_namespaces: bool,
+    #[serde(default)]
+    pub label_selector: Option<String>,
+    #[serde(default)]
+    pub field_selector: Option<String>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+    /// Continue token from a previous page
+    #[serde(default, rename = "continue")]
+    pub continue_token: Option<String>,
+    /// `table` (default for lists) or `json` (default for single objects)
+    #[serde(default)]
+    pub output: Option<OutputFormat>,
+}
+
+impl ResourceQuery {
+    /// Whether the query runs across several contexts
+    pub fn is_multi_context(&self) -> bool {
+        self.contexts.is_some() || self.context_glob.is_some()
+    }
+}
+
+/// Output format of get/list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+/// Arguments of the `delete` method
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteRequest {
+    #[serde(flatten)]
+    pub query: ResourceQuery,
+    /// `Foreground`, `Background` or `Orphan`
+    #[serde(default)]
+    pub propagation_policy: Option<String>,
+    #[serde(default)]
+    pub grace_period_seconds: Option<u32>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Column of a resource table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableColumn {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: String,
+    #[serde(default)]
+    pub format: String,
+    #[serde(default)]
+    pub description: String,
+    /// 0 for columns shown by default, higher for `-o wide` columns
+    #[serde(default)]
+    pub priority: i32,
+}
+
+/// Row of a resource table
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableRow {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub cells: Vec<serde_json::Value>,
+    /// Object metadata for the row, as returned by the server
+    pub object: serde_json::Value,
+}
+
+/// A list of resources, ready for the table renderer
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTable {
+    pub context: String,
+    pub api_version: String,
+    pub kind: String,
+    pub resource: String,
+    pub namespaced: bool,
+    pub columns: Vec<TableColumn>,
+    pub rows: Vec<TableRow>,
+    pub resource_version: Option<String>,
+    #[serde(rename = "continue")]
+    pub continue_token: Option<String>,
+}
+
+/// Summary of an event attached to a described object
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventSummary {
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+    pub count: Option<i32>,
+    pub source: Option<String>,
+    pub first_timestamp: Option<String>,
+    pub last_timestamp: Option<String>,
+}
+
+/// Aggregated view of an object, like `kubectl describe`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Description {
+    pub context: String,
+    pub resource: ApiResourceInfo,
+    pub object: serde_json::Value,
+    pub events: Vec<EventSummary>,
+}
+
+/// Outcome of a delete
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteOutcome {
+    pub context: String,
+    pub kind: String,
+    pub name: String,
+    pub namespace: Option<String>,
+    /// `deleted` if the object is gone, `deleting` if finalizers are pending
+    pub status: String,
+    pub dry_run: bool,
+}
+
+/// Raw Table response from the API server
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Table {
+    #[serde(default)]
+    column_definitions: Vec<TableColumn>,
+    #[serde(default)]
+    rows: Vec<RawTableRow>,
+    #[serde(default)]
+    metadata: TableMeta,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawTableRow {
+    #[serde(default)]
+    cells: Vec<serde_json::Value>,
+    #[serde(default)]
+    object: serde_json::Value,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TableMeta {
+    resource_version: Option<String>,
+    #[serde(rename = "continue")]
+    continue_token: Option<String>,
+}
+
+/// A query resolved against a cluster
+pub struct Target {
+    pub client: ContextClient,
+    pub info: ApiResourceInfo,
+    /// Namespace to operate in, `None` for cluster-scoped resources or
+    /// all-namespaces queries
+    pub namespace: Option<String>,
+}
+
+impl Target {
+    /// Dynamic API handle for the target
+    pub fn api(&self) -> Api<DynamicObject> {
+        let ar = self.info.api_resource();
+        match self.namespace {
+            Some(ref ns) => Api::namespaced_with(self.client.client.clone(), ns, &ar),
+            None => Api::all_with(self.client.client.clone(), &ar),
+        }
+    }
+
+    /// URL path of the resource collection
+    pub fn url_path(&self) -> String {
+        DynamicObject::url_path(&self.info.api_resource(), self.namespace.as_deref())
+    }
+}
+
+/// Resolve a query to a client, a resource type and a namespace
+pub async fn resolve(clients: &KubeClients, query: &ResourceQuery) -> K8sResult<Target> {
+    let client = clients.client(query.context.as_deref()).await?;
+    let info = resolve_kind(clients, &client, &query.kind).await?;
+
+    let namespace = if !info.namespaced || (query.all_namespaces && query.name.is_none()) {
+        None
+    } else {
+        Some(
+            query
+                .namespace
+                .clone()
+                .unwrap_or_else(|| client.default_namespace().to_string()),
+        )
+    };
+
+    Ok(Target {
+        client,
+        info,
+        namespace,
+    })
+}
+
+/// Resolve a resource name, re-running discovery once on a miss in case the
+/// type was installed since the last discovery
+pub async fn resolve_kind(
+    clients: &KubeClients,
+    client: &ContextClient,
+    kind: &str,
+) -> K8sResult<ApiResourceInfo> {
+    for refresh in [false, true] {
+        let discovery = clients.discovery(client, refresh).await?;
+        if let Some(info) = discovery.resolve(kind) {
+            return Ok(info.clone());
+        }
+    }
+    Err(K8sError::UnknownResource(kind.to_string()))
+}
+
+pub fn list_params(query: &ResourceQuery) -> ListParams {
+    let mut lp = ListParams::default();
+    if let Some(ref labels) = query.label_selector {
+        lp = lp.labels(labels);
+    }
+    if let Some(ref fields) = query.field_selector {
+        lp = lp.fields(fields);
+    }
+    if let Some(limit) = query.limit {
+        lp = lp.limit(limit);
+    }
+    if let Some(ref token) = query.continue_token {
+        lp = lp.continue_token(token);
+    }
+    lp
+}
+
+/// Fetch a page of a resource collection as a Table
+pub async fn list_table(target: &Target, lp: &ListParams) -> K8sResult<ResourceTable> {
+    let mut request = kube::core::Request::new(target.url_path()).list(lp)?;
+    request
+        .headers_mut()
+        .insert(ACCEPT, HeaderValue::from_static(TABLE_ACCEPT));
+
+    let table: Table = target.client.client.request(request).await?;
+    debug!(
+        "Listed {} {} in {}",
+        table.rows.len(),
+        target.info.plural,
+        target.client.context
+    );
+
+    let rows = table
+        .rows
+        .into_iter()
+        .map(|row| {
+            let metadata = row.object.get("metadata");
+            let field = |key: &str| {
+                metadata
+                    .and_then(|m| m.get(key))
+                    .and_then(|v| v.as_str())
+                    .map(String::from)
+            };
+            TableRow {
+                name: field("name").unwrap_or_default(),
+                namespace: field("namespace"),
+                cells: row.cells,
+                object: row.object,
+            }
+        })
+        .collect();
+
+    Ok(ResourceTable {
+        context: target.client.context.clone(),
+        api_version: target.info.api_version(),
+        kind: target.info.kind.clone(),
+        resource: target.info.plural.clone(),
+        namespaced: target.info.namespaced,
+        columns: table.column_definitions,
+        rows,
+        resource_version: table.metadata.resource_version,
+        continue_token: table.metadata.continue_token.filter(|t| !t.is_empty()),
+    })
+}
+
+/// List resources, as a Table by default or as raw objects with `output: json`
+pub async fn list(clients: &KubeClients, query: &ResourceQuery) -> K8sResult<serde_json::Value> {
+    let target = resolve(clients, query).await?;
+    let lp = list_params(query);
+
+    if query.output == Some(OutputFormat::Json) {
+        let list = target.api().list(&lp).await?;
+        return serde_json::to_value(list).map_err(|e| K8sError::Request(e.to_string()));
+    }
+
+    let table = list_table(&target, &lp).await?;
+    serde_json::to_value(table).map_err(|e| K8sError::Request(e.to_string()))
+}
+
+/// Get a single object, as raw JSON by default or as a one-row Table with
+/// `output: table`
+pub async fn get(clients: &KubeClients, query: &ResourceQuery) -> K8sResult<serde_json::Value> {
+    let Some(ref name) = query.name else {
+        return list(clients, query).await;
+    };
+    let target = resolve(clients, query).await?;
+
+    if query.output == Some(OutputFormat::Table) {
+        let lp = ListParams::default().fields(&format!("metadata.name={}", name));
+        let table = list_table(&target, &lp).await?;
+        return serde_json::to_value(table).map_err(|e| K8sError::Request(e.to_string()));
+    }
+
+    let object = target.api().get(name).await?;
+    serde_json::to_value(object).map_err(|e| K8sError::Request(e.to_string()))
+}
+
+/// Get an object together with the events that reference it
+pub async fn describe(clients: &KubeClients, query: &ResourceQuery) -> K8sResult<Description> {
+    let name = query
+        .name
+        .as_deref()
+        .ok_or_else(|| K8sError::InvalidRequest("describe requires a name".to_string()))?;
+    let target = resolve(clients, query).await?;
+    describe_target(&target, name).await
+}
+
+/// Get an object of a resolved target together with its events
+async fn describe_target(target: &Target, name: &str) -> K8sResult<Description> {
+    let object = target.api().get(name).await?;
+
+    let events = match object.metadata.uid {
+        Some(ref uid) => events_for(target, uid).await?,
+        None => Vec::new(),
+    };
+
+    Ok(Description {
+        context: target.client.context.clone(),
+        resource: target.info.clone(),
+        object: serde_json::to_value(object).map_err(|e| K8sError::Request(e.to_string()))?,
+        events,
+    })
+}
+
+/// Core events whose involved object has the given uid, oldest first
+async fn events_for(target: &Target, uid: &str) -> K8sResult<Vec<EventSummary>> {
+    let client = target.client.client.clone();
+    let api: Api<Event> = match target.namespace {
+        Some(ref ns) => Api::namespaced(client, ns),
+        None => Api::all(client),
+    };
+
+    let lp = ListParams::default().fields(&format!("involvedObject.uid={}", uid));
+    let mut events = api.list(&lp).await?.items;
+    events.sort_by_key(|e| {
+        e.last_timestamp
+            .as_ref()
+            .map(|t| t.0)
+            .or_else(|| e.event_time.as_ref().map(|t| t.0))
+    });
+
+    Ok(events
+        .into_iter()
+        .map(|e| EventSummary {
+            event_type: e.type_,
+            reason: e.reason,
+            message: e.message,
+            count: e.count,
+            source: e.source.and_then(|s| s.component).or(e.reporting_component),
+            first_timestamp: e.first_timestamp.map(|t| t.0.to_string()),
+            last_timestamp: e
+                .last_timestamp
+                .map(|t| t.0.to_string())
+                .or_else(|| e.event_time.map(|t| t.0.to_string())),
+        })
+        .collect())
+}
+
+/// Delete an object
+pub async fn delete(clients: &KubeClients, request: &DeleteRequest) -> K8sResult<DeleteOutcome> {
+    let query = &request.query;
+    let name = query
+        .name
+        .as_deref()
+        .ok_or_else(|| K8sError::InvalidRequest("delete requires a name".to_string()))?;
+    let target = resolve(clients, query).await?;
+    delete_target(&target, name, request).await
+}
+
+/// Delete an object of a resolved target
+async fn delete_target(
+    target: &Target,
+    name: &str,
+    request: &DeleteRequest,
+) -> K8sResult<DeleteOutcome> {
+    if !target.info.supports("delete") {
+        return Err(K8sError::InvalidRequest(format!(
+            "{} cannot be deleted",
+            target.info.plural
+        )));
+    }
+
+    let propagation_policy = match request.propagation_policy.as_deref() {
+        None => None,
+        Some("Foreground") => Some(PropagationPolicy::Foreground),
+        Some("Background") => Some(PropagationPolicy::Background),
+        Some("Orphan") => Some(PropagationPolicy::Orphan),
+        Some(other) => {
+            return Err(K8sError::InvalidRequest(format!(
+                "unknown propagation policy: {}",
+                other
+            )))
+        }
+    };
+
+    let dp = DeleteParams {
+        dry_run: request.dry_run,
+        grace_period_seconds: request.grace_period_seconds,
+        propagation_policy,
+        preconditions: None,
+    };
+
+    let result = target.api().delete(name, &dp).await?;
+    // The server returns the object while finalizers are still pending, and
+    // a Status once it is gone
+    let status = result.either(|_| "deleting", |_| "deleted");
+
+    Ok(DeleteOutcome {
+        context: target.client.context.clone(),
+        kind: target.info.kind.clone(),
+        name: name.to_string(),
+        namespace: target.namespace.clone(),
+        status: status.to_string(),
+        dry_run: request.dry_run,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::mock::{self, MockRequest, Requests};
+    use http::StatusCode;
+    use serde_json::{json, Value};
+
+    /// Pods in namespace `shop`, served by a mock API server
+    fn pods<F>(respond: F) -> (Target, Requests)
+    where
+        F: Fn(&MockRequest) -> (StatusCode, Value) + Send + Sync + 'static,
+    {
+        let (client, requests) = mock::client(respond);
+        (mock::pods(client), requests)
+    }
+
+    fn pod(name: &str, uid: &str) -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": name, "namespace": "shop", "uid": uid },
+        })
+    }
+
+    fn event(reason: &str, last_timestamp: &str) -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "Event",
+            "metadata": { "name": format!("web-0.{}", reason), "namespace": "shop" },
+            "involvedObject": { "kind": "Pod", "name": "web-0", "uid": "abc" },
+            "type": "Normal",
+            "reason": reason,
+            "message": format!("{} web-0", reason),
+            "count": 1,
+            "source": { "component": "kubelet" },
+            "lastTimestamp": last_timestamp,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_list_table() {
+        let (target, requests) = pods(|_| {
+            let table = json!({
+                "kind": "Table",
+                "apiVersion": "meta.k8s.io/v1",
+                "metadata": { "resourceVersion": "42", "continue": "" },
+                "columnDefinitions": [
+                    { "name": "Name", "type": "string", "format": "name", "priority": 0 },
+                    { "name": "Status", "type": "string", "priority": 0 },
+                    { "name": "IP", "type": "string", "priority": 1 },
+                ],
+                "rows": [{
+                    "cells": ["web-0", "Running", "10.0.0.7"],
+                    "object": {
+                        "kind": "PartialObjectMetadata",
+                        "apiVersion": "meta.k8s.io/v1",
+                        "metadata": { "name": "web-0", "namespace": "shop" },
+                    },
+                }],
+            });
+            (StatusCode::OK, table)
+        });
+
+        let lp = ListParams::default().labels("app=web");
+        let table = list_table(&target, &lp).await.unwrap();
+        assert_eq!(table.context, "test");
+        assert_eq!(table.api_version, "v1");
+        assert_eq!(table.resource, "pods");
+        assert_eq!(table.columns.len(), 3);
+        assert_eq!(table.columns[2].priority, 1);
+        assert_eq!(table.rows.len(), 1);
+        assert_eq!(table.rows[0].name, "web-0");
+        assert_eq!(table.rows[0].namespace.as_deref(), Some("shop"));
+        assert_eq!(table.rows[0].cells[1], "Running");
+        assert_eq!(table.resource_version.as_deref(), Some("42"));
+        assert_eq!(table.continue_token, None);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].path, "/api/v1/namespaces/shop/pods");
+        assert!(requests[0].query.contains("labelSelector=app%3Dweb"));
+        assert_eq!(requests[0].accept.as_deref(), Some(TABLE_ACCEPT));
+    }
+
+    #[tokio::test]
+    async fn test_describe_with_events() {
+        let (target, requests) = pods(|request| match request.path.as_str() {
+            "/api/v1/namespaces/shop/pods/web-0" => (StatusCode::OK, pod("web-0", "abc")),
+            "/api/v1/namespaces/shop/events" => {
+                let events = json!({
+                    "apiVersion": "v1",
+                    "kind": "EventList",
+                    "metadata": {},
+                    "items": [
+                        event("Started", "2025-03-01T10:02:00Z"),
+                        event("Scheduled", "2025-03-01T10:00:00Z"),
+                    ],
+                });
+                (StatusCode::OK, events)
+            }
+            _ => mock::failure(StatusCode::NOT_FOUND, "NotFound", "not found"),
+        });
+
+        let description = describe_target(&target, "web-0").await.unwrap();
+        assert_eq!(description.context, "test");
+        assert_eq!(description.object["metadata"]["uid"], "abc");
+        let reasons: Vec<_> = description
+            .events
+            .iter()
+            .map(|e| e.reason.as_deref().unwrap())
+            .collect();
+        assert_eq!(reasons, ["Scheduled", "Started"]);
+        assert_eq!(description.events[0].source.as_deref(), Some("kubelet"));
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1]
+            .query
+            .contains("fieldSelector=involvedObject.uid%3Dabc"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_with_propagation_policy() {
+        let (target, requests) = pods(|request| {
+            if request.body["propagationPolicy"] == "Foreground" {
+                // Dependents are still being removed
+                let mut object = pod("web-0", "abc");
+                object["metadata"]["finalizers"] = json!(["foregroundDeletion"]);
+                (StatusCode::OK, object)
+            } else {
+                let status = json!({
+                    "kind": "Status",
+                    "apiVersion": "v1",
+                    "metadata": {},
+                    "status": "Success",
+                    "details": { "name": "web-0", "kind": "pods", "uid": "abc" },
+                });
+                (StatusCode::OK, status)
+            }
+        });
+        let request = |policy: Option<&str>| DeleteRequest {
+            query: ResourceQuery {
+                kind: "pods".to_string(),
+                name: Some("web-0".to_string()),
+                ..Default::default()
+            },
+            propagation_policy: policy.map(String::from),
+            grace_period_seconds: None,
+            dry_run: false,
+        };
+
+        let outcome = delete_target(&target, "web-0", &request(Some("Foreground")))
+            .await
+            .unwrap();
+        assert_eq!(outcome.status, "deleting");
+        assert_eq!(outcome.namespace.as_deref(), Some("shop"));
+
+        let outcome = delete_target(&target, "web-0", &request(Some("Orphan")))
+            .await
+            .unwrap();
+        assert_eq!(outcome.status, "deleted");
+
+        assert!(delete_target(&target, "web-0", &request(Some("Later")))
+            .await
+            .is_err());
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "DELETE");
+        assert_eq!(requests[0].path, "/api/v1/namespaces/shop/pods/web-0");
+        assert_eq!(requests[1].body["propagationPolicy"], "Orphan");
+    }
+}
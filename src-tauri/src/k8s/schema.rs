@@ -0,0 +1,434 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Disk cache of discovery and OpenAPI v3 schemas
+//!
+//! API server responses are stored per context together with their ETag.
+//! Later requests send `If-None-Match`, so an unchanged cluster answers with
+//! an empty 304, and the cached copy is still there when the cluster can't
+//! be reached at all. Schemas of CRDs are served by the same OpenAPI v3
+//! endpoints as built-in types, so they are cached the same way.
+
+use super::client::KubeClients;
+use super::{K8sError, K8sResult};
+use crate::fs::write_atomic;
+use http::{header, Request, StatusCode};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Status;
+use kube::client::Body;
+use kube::Client;
+use log::{debug, warn};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+/// Index of the OpenAPI v3 documents served by the cluster
+const OPENAPI_ROOT: &str = "/openapi/v3";
+
+/// Prefix of schema references within an OpenAPI v3 document
+const SCHEMA_REF_PREFIX: &str = "#/components/schemas/";
+
+/// Directory of the current cache layout under the cache root; earlier
+/// layouts named files ambiguously and are removed
+const CACHE_LAYOUT: &str = "v2";
+
+/// API server responses of one context, cached on disk
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    /// Cache of `context` under the `root` cache directory
+    pub fn new(root: &Path, context: &str) -> Self {
+        ResponseCache {
+            dir: root.join(CACHE_LAYOUT).join(file_key(context)),
+        }
+    }
+
+    /// Remove whatever earlier cache layouts left under `root`
+    pub fn remove_stale(root: &Path) {
+        let Ok(entries) = std::fs::read_dir(root) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name() == CACHE_LAYOUT {
+                continue;
+            }
+            let path = entry.path();
+            let removed = match entry.file_type() {
+                Ok(kind) if kind.is_dir() => std::fs::remove_dir_all(&path),
+                _ => std::fs::remove_file(&path),
+            };
+            match removed {
+                Ok(()) => debug!("Removed stale cache {:?}", path),
+                Err(e) => warn!("Failed to remove stale cache {:?}: {}", path, e),
+            }
+        }
+    }
+
+    /// Get `path` from the API server, revalidating the cached copy
+    ///
+    /// Without a client, only the cached copy is returned. With one, the
+    /// cached copy is also returned when the request fails to reach the
+    /// server.
+    pub async fn get(&self, client: Option<&Client>, path: &str) -> K8sResult<Vec<u8>> {
+        // OpenAPI document URLs carry a content hash as a query; the ETag
+        // covers that, so one cache entry per path is enough
+        let key = file_key(path.split('?').next().unwrap_or(path));
+        let body_path = self.dir.join(format!("{}.json", key));
+        let etag_path = self.dir.join(format!("{}.etag", key));
+        let cached = std::fs::read(&body_path).ok();
+
+        let Some(client) = client else {
+            return cached.ok_or_else(|| K8sError::Uncached(path.to_string()));
+        };
+
+        let mut request = Request::get(path);
+        if cached.is_some() {
+            if let Ok(etag) = std::fs::read_to_string(&etag_path) {
+                request = request.header(header::IF_NONE_MATCH, etag.trim());
+            }
+        }
+        let request = request
+            .body(Body::empty())
+            .map_err(|e| K8sError::InvalidRequest(e.to_string()))?;
+
+        let response = match client.send(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                return match cached {
+                    Some(body) => {
+                        warn!("Using cached {}, the server is unreachable: {}", path, e);
+                        Ok(body)
+                    }
+                    None => Err(e.into()),
+                }
+            }
+        };
+
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(body) = cached {
+                debug!("{} is unchanged", path);
+                return Ok(body);
+            }
+        }
+
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        let body = response.into_body().collect_bytes().await?.to_vec();
+        if !status.is_success() {
+            return Err(api_error(status, &body));
+        }
+
+        if let Err(e) = self.store(&body_path, &body, &etag_path, etag.as_deref()) {
+            warn!("Failed to cache {}: {}", path, e);
+        }
+        Ok(body)
+    }
+
+    /// Get `path` and parse it as JSON
+    pub async fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        client: Option<&Client>,
+        path: &str,
+    ) -> K8sResult<T> {
+        let body = self.get(client, path).await?;
+        serde_json::from_slice(&body).map_err(|e| K8sError::Request(e.to_string()))
+    }
+
+    /// Read a value stored with `put`
+    pub fn read<T: serde::de::DeserializeOwned>(&self, name: &str) -> Option<T> {
+        let body = std::fs::read(self.dir.join(format!("{}.json", name))).ok()?;
+        serde_json::from_slice(&body).ok()
+    }
+
+    /// Store a value derived from cached responses, e.g. a discovery result
+    pub fn put<T: serde::Serialize>(&self, name: &str, value: &T) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        write_atomic(
+            &self.dir.join(format!("{}.json", name)),
+            &serde_json::to_vec(value)?,
+        )
+    }
+
+    fn store(
+        &self,
+        body_path: &Path,
+        body: &[u8],
+        etag_path: &Path,
+        etag: Option<&str>,
+    ) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        write_atomic(body_path, body)?;
+        match etag {
+            Some(etag) => write_atomic(etag_path, etag.as_bytes()),
+            None => match std::fs::remove_file(etag_path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+/// File name for a context name or API path
+///
+/// Percent-encoding keeps distinct names distinct. A leading dot is encoded
+/// too, so `.` and `..` don't name the directory or its parent; `%` alone
+/// stands for the empty name, as no encoding produces it.
+fn file_key(name: &str) -> String {
+    let key = urlencoding::encode(name);
+    match key.strip_prefix('.') {
+        Some(rest) => format!("%2E{}", rest),
+        None if key.is_empty() => "%".to_string(),
+        None => key.into_owned(),
+    }
+}
+
+/// Error for a non-success response, using the Status body if there is one
+fn api_error(status: StatusCode, body: &[u8]) -> K8sError {
+    match serde_json::from_slice::<Status>(body) {
+        Ok(s) => K8sError::Api {
+            code: status.as_u16(),
+            reason: s.reason.unwrap_or_default(),
+            message: s.message.unwrap_or_default(),
+        },
+        Err(_) => K8sError::Api {
+            code: status.as_u16(),
+            reason: status.canonical_reason().unwrap_or_default().to_string(),
+            message: String::from_utf8_lossy(body).into_owned(),
+        },
+    }
+}
+
+/// Path of the OpenAPI v3 document for an `apiVersion`, as listed in the
+/// OpenAPI index
+fn openapi_path(api_version: &str) -> String {
+    match api_version.split_once('/') {
+        Some((group, version)) => format!("apis/{}/{}", group, version),
+        None => format!("api/{}", api_version),
+    }
+}
+
+/// Get the OpenAPI v3 document describing an `apiVersion`
+///
+/// Returns `None` if the cluster doesn't serve the version.
+pub async fn fetch_document(
+    cache: &ResponseCache,
+    client: Option<&Client>,
+    api_version: &str,
+) -> K8sResult<Option<Value>> {
+    let index: Value = cache.get_json(client, OPENAPI_ROOT).await?;
+    let pointer = format!(
+        "/paths/{}/serverRelativeURL",
+        openapi_path(api_version)
+            .replace('~', "~0")
+            .replace('/', "~1")
+    );
+    match index.pointer(&pointer).and_then(Value::as_str) {
+        Some(url) => Ok(Some(cache.get_json(client, url).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Fetch every OpenAPI v3 document of the cluster into the cache, so that
+/// schemas are available offline
+///
+/// Documents that haven't changed since the last run cost a 304 each.
+pub async fn prefetch(cache: &ResponseCache, client: &Client) -> K8sResult<()> {
+    let index: Value = cache.get_json(Some(client), OPENAPI_ROOT).await?;
+    let urls: Vec<&str> = index["paths"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(path, _)| path.starts_with("api/") || path.starts_with("apis/"))
+        .filter_map(|(_, entry)| entry["serverRelativeURL"].as_str())
+        .collect();
+
+    for url in &urls {
+        if let Err(e) = cache.get(Some(client), url).await {
+            debug!("Failed to prefetch {}: {}", url, e);
+        }
+    }
+    debug!("Prefetched {} OpenAPI documents", urls.len());
+    Ok(())
+}
+
+/// Find the schema of a kind in an OpenAPI v3 document
+///
+/// The returned schema is self-contained: references to other schemas in
+/// the document are inlined.
+pub fn find_schema(document: &Value, api_version: &str, kind: &str) -> Option<Value> {
+    let (group, version) = api_version.split_once('/').unwrap_or(("", api_version));
+    let schemas = document.pointer("/components/schemas")?.as_object()?;
+
+    let (name, schema) = schemas.iter().find(|(_, schema)| {
+        schema
+            .get("x-kubernetes-group-version-kind")
+            .and_then(Value::as_array)
+            .is_some_and(|gvks| {
+                gvks.iter().any(|gvk| {
+                    gvk["group"].as_str().unwrap_or_default() == group
+                        && gvk["version"] == version
+                        && gvk["kind"] == kind
+                })
+            })
+    })?;
+
+    let mut stack = vec![name.clone()];
+    Some(inline_refs(schema, schemas, &mut stack))
+}
+
+/// Replace `$ref`s with the schemas they point to
+///
+/// `stack` holds the schemas being inlined; a reference back into one of
+/// them (e.g. `JSONSchemaProps`) is replaced by an unconstrained schema
+/// instead of recursing forever.
+fn inline_refs(value: &Value, schemas: &Map<String, Value>, stack: &mut Vec<String>) -> Value {
+    match value {
+        Value::Object(object) => {
+            if let Some(name) = object
+                .get("$ref")
+                .and_then(Value::as_str)
+                .and_then(|r| r.strip_prefix(SCHEMA_REF_PREFIX))
+            {
+                if stack.iter().any(|s| s == name) {
+                    return Value::Object(Map::new());
+                }
+                let Some(target) = schemas.get(name) else {
+                    return value.clone();
+                };
+                stack.push(name.to_string());
+                let inlined = inline_refs(target, schemas, stack);
+                stack.pop();
+                return inlined;
+            }
+
+            Value::Object(
+                object
+                    .iter()
+                    .map(|(key, value)| (key.clone(), inline_refs(value, schemas, stack)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| inline_refs(item, schemas, stack))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// Tauri command: Get the OpenAPI schema of a kind
+///
+/// Served from the cache when the cluster can't be reached. Returns `null`
+/// if the cluster doesn't serve the type.
+#[tauri::command]
+pub async fn get_resource_schema(
+    clients: State<'_, KubeClients>,
+    api_version: String,
+    kind: String,
+    context: Option<String>,
+) -> Result<Option<Value>, String> {
+    clients
+        .schema(context.as_deref(), &api_version, &kind, true)
+        .await
+        .map_err(|e| format!("Failed to get schema: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_file_key() {
+        let names = [
+            "", ".", "..", "%", "%2E", "a/b", "a_b", "a%2Fb", "a.b", "a b", "/api/v1", "api/v1",
+        ];
+        let keys: Vec<String> = names.iter().map(|name| file_key(name)).collect();
+        for (i, key) in keys.iter().enumerate() {
+            assert!(!key.is_empty() && !key.starts_with('.') && !key.contains('/'));
+            assert!(!keys[..i].contains(key), "{:?} collides", names[i]);
+        }
+        assert_eq!(file_key("kind-kind"), "kind-kind");
+    }
+
+    #[test]
+    fn test_remove_stale() {
+        let root = std::env::temp_dir().join(format!("kui-schema-{}", std::process::id()));
+        let cache = ResponseCache::new(&root, "a/b");
+        cache.put("discovery", &json!({})).unwrap();
+        std::fs::create_dir_all(root.join("a_b")).unwrap();
+        std::fs::write(root.join("a_b").join("discovery.json"), "{}").unwrap();
+
+        ResponseCache::remove_stale(&root);
+        assert!(!root.join("a_b").exists());
+        assert!(cache.read::<Value>("discovery").is_some());
+        assert!(ResponseCache::new(&root, "a_b")
+            .read::<Value>("discovery")
+            .is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_find_schema_inlines_refs() {
+        let document = json!({
+            "components": {
+                "schemas": {
+                    "io.k8s.api.apps.v1.Deployment": {
+                        "type": "object",
+                        "properties": {
+                            "spec": {
+                                "allOf": [{"$ref": "#/components/schemas/io.k8s.api.apps.v1.DeploymentSpec"}]
+                            }
+                        },
+                        "x-kubernetes-group-version-kind": [
+                            {"group": "apps", "kind": "Deployment", "version": "v1"}
+                        ]
+                    },
+                    "io.k8s.api.apps.v1.DeploymentSpec": {
+                        "type": "object",
+                        "properties": {"replicas": {"type": "integer"}}
+                    },
+                    "io.k8s.apiextensions.v1.JSONSchemaProps": {
+                        "type": "object",
+                        "properties": {
+                            "not": {"$ref": "#/components/schemas/io.k8s.apiextensions.v1.JSONSchemaProps"}
+                        },
+                        "x-kubernetes-group-version-kind": [
+                            {"group": "apiextensions.k8s.io", "kind": "JSONSchemaProps", "version": "v1"}
+                        ]
+                    }
+                }
+            }
+        });
+
+        let deployment = find_schema(&document, "apps/v1", "Deployment").unwrap();
+        assert_eq!(
+            deployment.pointer("/properties/spec/allOf/0/properties/replicas/type"),
+            Some(&json!("integer"))
+        );
+
+        let props = find_schema(&document, "apiextensions.k8s.io/v1", "JSONSchemaProps").unwrap();
+        assert_eq!(props.pointer("/properties/not"), Some(&json!({})));
+
+        assert!(find_schema(&document, "apps/v1beta1", "Deployment").is_none());
+    }
+}
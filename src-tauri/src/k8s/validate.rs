@@ -0,0 +1,794 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Offline validation of YAML manifests
+//!
+//! Each document is checked against the OpenAPI schema of its
+//! apiVersion/kind from the response cache, without contacting the cluster.
+//! The checks are the structural ones the API server's strict field
+//! validation makes: types, required and unknown fields, and enums.
+//! Issues are reported with a JSON pointer and a line/column in the input.
+
+use super::client::KubeClients;
+use super::schema::{self, ResponseCache};
+use super::{K8sError, K8sResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// An API version that is or will be no longer served
+struct DeprecatedApi {
+    api_version: &'static str,
+    /// Kind the entry is limited to, all kinds of the version if `None`
+    kind: Option<&'static str>,
+    /// Kubernetes release that stops serving it
+    removed_in: &'static str,
+    replacement: Option<&'static str>,
+}
+
+const fn deprecated(
+    api_version: &'static str,
+    kind: Option<&'static str>,
+    removed_in: &'static str,
+    replacement: Option<&'static str>,
+) -> DeprecatedApi {
+    DeprecatedApi {
+        api_version,
+        kind,
+        removed_in,
+        replacement,
+    }
+}
+
+/// Deprecated API versions, from the Kubernetes deprecation guide; the first
+/// matching entry applies
+const DEPRECATED_APIS: &[DeprecatedApi] = &[
+    deprecated(
+        "extensions/v1beta1",
+        Some("Ingress"),
+        "1.22",
+        Some("networking.k8s.io/v1"),
+    ),
+    deprecated(
+        "extensions/v1beta1",
+        Some("NetworkPolicy"),
+        "1.16",
+        Some("networking.k8s.io/v1"),
+    ),
+    deprecated(
+        "extensions/v1beta1",
+        Some("PodSecurityPolicy"),
+        "1.16",
+        Some("policy/v1beta1"),
+    ),
+    deprecated("extensions/v1beta1", None, "1.16", Some("apps/v1")),
+    deprecated("apps/v1beta1", None, "1.16", Some("apps/v1")),
+    deprecated("apps/v1beta2", None, "1.16", Some("apps/v1")),
+    deprecated(
+        "networking.k8s.io/v1beta1",
+        None,
+        "1.22",
+        Some("networking.k8s.io/v1"),
+    ),
+    deprecated(
+        "rbac.authorization.k8s.io/v1beta1",
+        None,
+        "1.22",
+        Some("rbac.authorization.k8s.io/v1"),
+    ),
+    deprecated(
+        "apiextensions.k8s.io/v1beta1",
+        None,
+        "1.22",
+        Some("apiextensions.k8s.io/v1"),
+    ),
+    deprecated(
+        "admissionregistration.k8s.io/v1beta1",
+        None,
+        "1.22",
+        Some("admissionregistration.k8s.io/v1"),
+    ),
+    deprecated(
+        "apiregistration.k8s.io/v1beta1",
+        None,
+        "1.22",
+        Some("apiregistration.k8s.io/v1"),
+    ),
+    deprecated(
+        "certificates.k8s.io/v1beta1",
+        None,
+        "1.22",
+        Some("certificates.k8s.io/v1"),
+    ),
+    deprecated(
+        "coordination.k8s.io/v1beta1",
+        None,
+        "1.22",
+        Some("coordination.k8s.io/v1"),
+    ),
+    deprecated(
+        "scheduling.k8s.io/v1beta1",
+        None,
+        "1.22",
+        Some("scheduling.k8s.io/v1"),
+    ),
+    deprecated(
+        "storage.k8s.io/v1beta1",
+        Some("CSIStorageCapacity"),
+        "1.27",
+        Some("storage.k8s.io/v1"),
+    ),
+    deprecated(
+        "storage.k8s.io/v1beta1",
+        None,
+        "1.22",
+        Some("storage.k8s.io/v1"),
+    ),
+    deprecated("batch/v1beta1", None, "1.25", Some("batch/v1")),
+    deprecated(
+        "discovery.k8s.io/v1beta1",
+        None,
+        "1.25",
+        Some("discovery.k8s.io/v1"),
+    ),
+    deprecated(
+        "events.k8s.io/v1beta1",
+        None,
+        "1.25",
+        Some("events.k8s.io/v1"),
+    ),
+    deprecated("autoscaling/v2beta1", None, "1.25", Some("autoscaling/v2")),
+    deprecated("autoscaling/v2beta2", None, "1.26", Some("autoscaling/v2")),
+    deprecated("policy/v1beta1", Some("PodSecurityPolicy"), "1.25", None),
+    deprecated("policy/v1beta1", None, "1.25", Some("policy/v1")),
+    deprecated("node.k8s.io/v1beta1", None, "1.25", Some("node.k8s.io/v1")),
+    deprecated(
+        "flowcontrol.apiserver.k8s.io/v1beta1",
+        None,
+        "1.26",
+        Some("flowcontrol.apiserver.k8s.io/v1"),
+    ),
+    deprecated(
+        "flowcontrol.apiserver.k8s.io/v1beta2",
+        None,
+        "1.29",
+        Some("flowcontrol.apiserver.k8s.io/v1"),
+    ),
+    deprecated(
+        "flowcontrol.apiserver.k8s.io/v1beta3",
+        None,
+        "1.32",
+        Some("flowcontrol.apiserver.k8s.io/v1"),
+    ),
+];
+
+/// Arguments of the `validate` method
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidateRequest {
+    /// One or more YAML documents; `List` documents are expanded
+    pub yaml: String,
+    /// Context whose cached schemas to use; defaults to the current context
+    #[serde(default)]
+    pub context: Option<String>,
+}
+
+/// How serious an issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in one document
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// Index of the document in the input, from 0
+    pub document: usize,
+    /// JSON pointer to the offending node within the document
+    pub path: String,
+    pub message: String,
+    /// Position in the input, from 1
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Result of validating a manifest
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    pub context: String,
+    /// Whether no errors were found; warnings don't count
+    pub valid: bool,
+    pub issues: Vec<ValidationIssue>,
+}
+
+/// An issue before it is placed in the input
+type Finding = (Severity, String, String);
+
+/// Validate YAML documents against the cached schemas of a context
+pub async fn validate(
+    clients: &KubeClients,
+    request: &ValidateRequest,
+) -> K8sResult<ValidationReport> {
+    let context = match request.context.clone() {
+        Some(context) => context,
+        None => clients.current_context()?,
+    };
+    let mut schemas = SchemaSource::new(clients.response_cache(&context));
+    let mut issues = Vec::new();
+
+    for (index, document) in split_documents(&request.yaml).into_iter().enumerate() {
+        let mut findings = Vec::new();
+        match serde_yaml::from_str::<Value>(document.text) {
+            Ok(value) => {
+                let is_list = value["kind"].as_str().is_some_and(|k| k.ends_with("List"))
+                    && value["items"].is_array();
+                if is_list {
+                    for (i, item) in value["items"].as_array().into_iter().flatten().enumerate() {
+                        let base = format!("/items/{}", i);
+                        validate_object(&mut schemas, item, &base, &mut findings).await;
+                    }
+                } else if !value.is_null() {
+                    validate_object(&mut schemas, &value, "", &mut findings).await;
+                }
+            }
+            Err(e) => {
+                // YAML syntax errors carry their own position
+                let (line, column) = e
+                    .location()
+                    .map(|l| (l.line(), l.column()))
+                    .unwrap_or((1, 1));
+                issues.push(ValidationIssue {
+                    severity: Severity::Error,
+                    document: index,
+                    path: String::new(),
+                    message: e.to_string(),
+                    line: document.first_line + line,
+                    column,
+                });
+                continue;
+            }
+        }
+
+        let positions = yaml_positions(document.text);
+        for (severity, path, message) in findings {
+            let (line, column) = locate(&positions, &path);
+            issues.push(ValidationIssue {
+                severity,
+                document: index,
+                path,
+                message,
+                line: document.first_line + line + 1,
+                column: column + 1,
+            });
+        }
+    }
+
+    Ok(ValidationReport {
+        context,
+        valid: issues.iter().all(|i| i.severity != Severity::Error),
+        issues,
+    })
+}
+
+/// OpenAPI documents from the response cache, read once per validation
+struct SchemaSource {
+    cache: Option<ResponseCache>,
+    /// Documents by apiVersion; `None` if the cluster doesn't serve it
+    documents: HashMap<String, Option<Value>>,
+}
+
+impl SchemaSource {
+    fn new(cache: Option<ResponseCache>) -> Self {
+        SchemaSource {
+            cache,
+            documents: HashMap::new(),
+        }
+    }
+
+    /// Schema of a kind; `Ok(None)` if the cluster doesn't serve it
+    async fn schema(&mut self, api_version: &str, kind: &str) -> K8sResult<Option<Value>> {
+        if !self.documents.contains_key(api_version) {
+            let cache = self
+                .cache
+                .as_ref()
+                .ok_or_else(|| K8sError::Uncached(api_version.to_string()))?;
+            let document = schema::fetch_document(cache, None, api_version).await?;
+            self.documents.insert(api_version.to_string(), document);
+        }
+
+        Ok(self.documents[api_version]
+            .as_ref()
+            .and_then(|document| schema::find_schema(document, api_version, kind)))
+    }
+}
+
+/// Check one object, appending findings with pointers under `base`
+async fn validate_object(
+    schemas: &mut SchemaSource,
+    object: &Value,
+    base: &str,
+    findings: &mut Vec<Finding>,
+) {
+    let api_version = object["apiVersion"].as_str().unwrap_or_default();
+    let kind = object["kind"].as_str().unwrap_or_default();
+    if api_version.is_empty() || kind.is_empty() {
+        findings.push((
+            Severity::Error,
+            base.to_string(),
+            "apiVersion and kind must be set".to_string(),
+        ));
+        return;
+    }
+
+    if let Some(message) = deprecation(api_version, kind) {
+        findings.push((Severity::Warning, format!("{}/apiVersion", base), message));
+    }
+
+    match schemas.schema(api_version, kind).await {
+        Ok(Some(schema)) => check(object, &schema, base, findings),
+        Ok(None) => findings.push((
+            Severity::Error,
+            format!("{}/kind", base),
+            format!(
+                "no kind \"{}\" is served in version \"{}\"",
+                kind, api_version
+            ),
+        )),
+        Err(K8sError::Uncached(_)) => findings.push((
+            Severity::Warning,
+            format!("{}/kind", base),
+            format!(
+                "no cached schema for {} {}; connect to the cluster once to validate it offline",
+                api_version, kind
+            ),
+        )),
+        Err(e) => findings.push((Severity::Warning, base.to_string(), e.to_string())),
+    }
+}
+
+/// Deprecation warning for an apiVersion/kind, if it is deprecated
+fn deprecation(api_version: &str, kind: &str) -> Option<String> {
+    let entry = DEPRECATED_APIS
+        .iter()
+        .find(|d| d.api_version == api_version && d.kind.is_none_or(|k| k == kind))?;
+
+    Some(match entry.replacement {
+        Some(replacement) => format!(
+            "{} {} is deprecated and not served since Kubernetes {}; use {}",
+            api_version, kind, entry.removed_in, replacement
+        ),
+        None => format!(
+            "{} {} is deprecated and not served since Kubernetes {}, with no replacement",
+            api_version, kind, entry.removed_in
+        ),
+    })
+}
+
+/// Check a value against an OpenAPI v3 schema
+///
+/// Nulls are accepted anywhere, as the API server treats them as unset.
+/// Combinators other than `allOf` are not evaluated.
+fn check(value: &Value, schema: &Value, path: &str, findings: &mut Vec<Finding>) {
+    if value.is_null() {
+        return;
+    }
+
+    for sub in schema["allOf"].as_array().into_iter().flatten() {
+        check(value, sub, path, findings);
+    }
+
+    let int_or_string =
+        schema["x-kubernetes-int-or-string"] == true || schema["format"] == "int-or-string";
+    if int_or_string {
+        if !value.is_string() && !is_integer(value) {
+            findings.push((
+                Severity::Error,
+                path.to_string(),
+                format!("expected integer or string, got {}", type_name(value)),
+            ));
+        }
+        return;
+    }
+
+    if let Some(expected) = schema["type"].as_str() {
+        let matches = match expected {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => is_integer(value),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            _ => true,
+        };
+        if !matches {
+            findings.push((
+                Severity::Error,
+                path.to_string(),
+                format!("expected {}, got {}", expected, type_name(value)),
+            ));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            findings.push((
+                Severity::Error,
+                path.to_string(),
+                format!(
+                    "unsupported value {}, expected one of {}",
+                    value,
+                    allowed.join(", ")
+                ),
+            ));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            for field in schema["required"].as_array().into_iter().flatten() {
+                if let Some(field) = field.as_str() {
+                    if object.get(field).is_none_or(Value::is_null) {
+                        findings.push((
+                            Severity::Error,
+                            path.to_string(),
+                            format!("missing required field \"{}\"", field),
+                        ));
+                    }
+                }
+            }
+
+            let properties = schema["properties"].as_object();
+            let additional = &schema["additionalProperties"];
+            let preserve_unknown = schema["x-kubernetes-preserve-unknown-fields"] == true;
+            for (key, child) in object {
+                let child_path = format!("{}/{}", path, escape_pointer(key));
+                if let Some(property) = properties.and_then(|p| p.get(key)) {
+                    check(child, property, &child_path, findings);
+                } else if additional.is_object() {
+                    check(child, additional, &child_path, findings);
+                } else if properties.is_some() && !preserve_unknown && *additional != true {
+                    findings.push((
+                        Severity::Error,
+                        child_path,
+                        format!("unknown field \"{}\"", key),
+                    ));
+                }
+            }
+        }
+        Value::Array(items) if schema["items"].is_object() => {
+            for (i, item) in items.iter().enumerate() {
+                check(item, &schema["items"], &format!("{}/{}", path, i), findings);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_integer(value: &Value) -> bool {
+    value.is_i64() || value.is_u64()
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// One YAML document of the input
+struct Document<'a> {
+    text: &'a str,
+    /// Lines of the input before the document
+    first_line: usize,
+}
+
+/// Split input into YAML documents on `---` lines, keeping track of where
+/// each starts; documents with no content are dropped
+fn split_documents(yaml: &str) -> Vec<Document<'_>> {
+    let mut documents = Vec::new();
+    let mut start = 0;
+    let mut first_line = 0;
+    let mut offset = 0;
+
+    let mut push = |start: usize, end: usize, first_line: usize| {
+        let text = &yaml[start..end];
+        let has_content = text.lines().any(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#') && line != "..."
+        });
+        if has_content {
+            documents.push(Document { text, first_line });
+        }
+    };
+
+    for (number, line) in yaml.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim_end();
+        let separator =
+            trimmed == "---" || trimmed.starts_with("--- ") || trimmed.starts_with("---\t");
+        if separator {
+            push(start, offset, first_line);
+            start = offset + line.len();
+            first_line = number + 1;
+        }
+        offset += line.len();
+    }
+    push(start, yaml.len(), first_line);
+
+    documents
+}
+
+/// Position of a node within a document, 0-based line and column
+type Position = (usize, usize);
+
+/// Find the position of the node at `pointer`, or of its nearest ancestor
+/// with a known position
+fn locate(positions: &HashMap<String, Position>, pointer: &str) -> Position {
+    let mut pointer = pointer;
+    loop {
+        if let Some(position) = positions.get(pointer) {
+            return *position;
+        }
+        match pointer.rsplit_once('/') {
+            Some((parent, _)) => pointer = parent,
+            None => return positions.get("").copied().unwrap_or((0, 0)),
+        }
+    }
+}
+
+/// Map JSON pointers of the nodes of a block-style YAML document to the
+/// position of their key or sequence dash
+///
+/// This is a line scanner, not a YAML parser: flow collections and
+/// multi-line plain scalars are not descended into, and nodes it can't
+/// place are reported at their nearest ancestor by `locate`.
+fn yaml_positions(text: &str) -> HashMap<String, Position> {
+    struct Frame {
+        column: usize,
+        pointer: String,
+        sequence: bool,
+        next_index: usize,
+    }
+
+    let mut positions = HashMap::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    // Node whose value starts on a following line
+    let mut pending = String::new();
+    // Column of the key whose block scalar is being skipped
+    let mut block_scalar: Option<usize> = None;
+
+    for (number, line) in text.lines().enumerate() {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let content = line[indent..].trim_end();
+
+        if let Some(column) = block_scalar {
+            if content.is_empty() || indent > column {
+                continue;
+            }
+            block_scalar = None;
+        }
+        if content.is_empty() || content.starts_with('#') || content == "..." {
+            continue;
+        }
+        positions.entry(String::new()).or_insert((number, indent));
+
+        let mut column = indent;
+        let mut rest = content;
+        loop {
+            if rest == "-" || rest.starts_with("- ") {
+                while stack.last().is_some_and(|f| f.column > column) {
+                    stack.pop();
+                }
+                let index = match stack.last_mut() {
+                    Some(frame) if frame.sequence && frame.column == column => {
+                        frame.next_index += 1;
+                        frame.next_index - 1
+                    }
+                    _ => {
+                        stack.push(Frame {
+                            column,
+                            pointer: pending.clone(),
+                            sequence: true,
+                            next_index: 1,
+                        });
+                        0
+                    }
+                };
+                let parent = &stack.last().unwrap().pointer;
+                pending = format!("{}/{}", parent, index);
+                positions.insert(pending.clone(), (number, column));
+
+                let item = rest[1..].trim_start();
+                column += rest.len() - item.len();
+                rest = item;
+                if rest.is_empty() {
+                    break;
+                }
+                continue;
+            }
+
+            let Some((key, value)) = split_key(rest) else {
+                break;
+            };
+
+            while stack.last().is_some_and(|f| f.column > column) {
+                stack.pop();
+            }
+            if stack
+                .last()
+                .is_some_and(|f| f.sequence && f.column == column)
+            {
+                stack.pop();
+            }
+            let parent = match stack.last() {
+                Some(frame) if !frame.sequence && frame.column == column => frame.pointer.clone(),
+                _ => {
+                    stack.push(Frame {
+                        column,
+                        pointer: pending.clone(),
+                        sequence: false,
+                        next_index: 0,
+                    });
+                    pending.clone()
+                }
+            };
+
+            let pointer = format!("{}/{}", parent, escape_pointer(&key));
+            positions.insert(pointer.clone(), (number, column));
+            if value.starts_with('|') || value.starts_with('>') {
+                block_scalar = Some(column);
+            }
+            pending = pointer;
+            break;
+        }
+    }
+
+    positions
+}
+
+/// Split `key: value` into the unquoted key and the value without comment
+fn split_key(text: &str) -> Option<(String, &str)> {
+    let (key, rest) = if let Some(quote @ ('"' | '\'')) = text.chars().next() {
+        let end = text[1..].find(quote)? + 1;
+        (text[1..end].to_string(), text[end + 1..].strip_prefix(':')?)
+    } else if text.starts_with(['{', '[']) {
+        return None;
+    } else {
+        let end = text
+            .find(": ")
+            .or_else(|| text.strip_suffix(':').map(str::len))?;
+        (text[..end].to_string(), &text[end + 1..])
+    };
+
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let value = match rest.find(" #") {
+        Some(comment) => &rest[..comment],
+        None => rest,
+    };
+    Some((key, value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_reports_positions() {
+        let yaml = "\
+apiVersion: apps/v1
+kind: Deployment
+---
+apiVersion: v1
+kind: Pod
+metadata:
+  name: web
+spec:
+  containers:
+  - name: web
+    image: nginx
+    ports:
+    - containerPort: \"eighty\"
+  restartPolicy: Sometimes
+  nodeSelecter:
+    disk: ssd
+";
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "apiVersion": {"type": "string"},
+                "kind": {"type": "string"},
+                "metadata": {"type": "object", "additionalProperties": true},
+                "spec": {
+                    "type": "object",
+                    "required": ["containers"],
+                    "properties": {
+                        "containers": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "required": ["name"],
+                                "properties": {
+                                    "name": {"type": "string"},
+                                    "image": {"type": "string"},
+                                    "ports": {
+                                        "type": "array",
+                                        "items": {
+                                            "type": "object",
+                                            "properties": {"containerPort": {"type": "integer"}}
+                                        }
+                                    }
+                                }
+                            }
+                        },
+                        "restartPolicy": {"type": "string", "enum": ["Always", "OnFailure", "Never"]},
+                        "nodeSelector": {"type": "object", "additionalProperties": {"type": "string"}}
+                    }
+                }
+            }
+        });
+
+        let documents = split_documents(yaml);
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[1].first_line, 3);
+
+        let pod: Value = serde_yaml::from_str(documents[1].text).unwrap();
+        let mut findings = Vec::new();
+        check(&pod, &schema, "", &mut findings);
+        let mut paths: Vec<&str> = findings.iter().map(|(_, path, _)| path.as_str()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "/spec/containers/0/ports/0/containerPort",
+                "/spec/nodeSelecter",
+                "/spec/restartPolicy",
+            ]
+        );
+
+        let positions = yaml_positions(documents[1].text);
+        assert_eq!(
+            locate(&positions, "/spec/containers/0/ports/0/containerPort"),
+            (9, 6)
+        );
+        assert_eq!(locate(&positions, "/spec/nodeSelecter"), (11, 2));
+        assert_eq!(
+            locate(&positions, "/spec/nodeSelecter/disk/missing"),
+            (12, 4)
+        );
+        assert_eq!(locate(&positions, "/spec/containers/0"), (6, 2));
+
+        assert!(deprecation("extensions/v1beta1", "Ingress")
+            .unwrap()
+            .contains("networking.k8s.io/v1"));
+        assert!(deprecation("apps/v1", "Deployment").is_none());
+    }
+}
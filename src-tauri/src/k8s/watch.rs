@@ -0,0 +1,600 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resource watches pushed to the webview
+//!
+//! Each subscription runs an API watch in a background task and emits the
+//! resulting deltas on its own event channel, `kube-watch:<watchId>`, to the
+//! window that opened it. The watch resumes from the last seen
+//! resourceVersion (kept fresh by bookmarks) when the server closes the
+//! stream, and falls back to a full re-list when that version has expired
+//! (410 Gone). The re-list is sent as a single `SYNC` message so the
+//! renderer can replace its rows wholesale.
+
+use super::client::KubeClients;
+use super::resources::{self, ResourceQuery, Target};
+use super::{K8sError, K8sResult};
+use crate::redact::SecretVault;
+use futures::StreamExt;
+use kube::api::{ListParams, WatchEvent, WatchParams};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, EventTarget, Manager};
+use tokio::task::AbortHandle;
+
+/// Prefix of per-subscription event names
+pub const KUBE_WATCH_EVENT_PREFIX: &str = "kube-watch";
+
+/// Server-side timeout of a single watch request; the stream is resumed
+/// transparently when it expires
+const WATCH_TIMEOUT_SECS: u32 = 290;
+
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Arguments of the `watch` method
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchRequest {
+    #[serde(flatten)]
+    pub query: ResourceQuery,
+    /// Version to start from, e.g. the `resourceVersion` of a table the
+    /// renderer already shows; the watch starts with a `SYNC` if absent
+    #[serde(default)]
+    pub resource_version: Option<String>,
+}
+
+/// Returned by the `watch` method
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchSubscription {
+    pub watch_id: String,
+    /// Event name the deltas are emitted on
+    pub event: String,
+}
+
+/// A message on a watch channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "UPPERCASE")]
+enum WatchMessage {
+    Added {
+        object: serde_json::Value,
+    },
+    Modified {
+        object: serde_json::Value,
+    },
+    Deleted {
+        object: serde_json::Value,
+    },
+    /// Full state after a (re-)list
+    Sync {
+        objects: Vec<serde_json::Value>,
+        #[serde(rename = "resourceVersion")]
+        resource_version: String,
+    },
+    /// The watch hit an error; `fatal` errors end the subscription
+    Error {
+        message: String,
+        fatal: bool,
+    },
+}
+
+/// Payload of `kube-watch:<watchId>` events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WatchPayload {
+    watch_id: String,
+    #[serde(flatten)]
+    message: WatchMessage,
+}
+
+/// Where the messages of a watch go
+trait WatchSink: Send + Sync + 'static {
+    fn send(&self, message: WatchMessage);
+}
+
+/// Sends a watch's messages to the window that opened it
+struct WindowSink {
+    app: AppHandle,
+    window_label: String,
+    watch_id: String,
+    event: String,
+}
+
+impl WatchSink for WindowSink {
+    fn send(&self, message: WatchMessage) {
+        let payload = WatchPayload {
+            watch_id: self.watch_id.clone(),
+            message,
+        };
+        if let Err(e) = self.app.emit_to(
+            EventTarget::webview_window(self.window_label.as_str()),
+            self.event.as_str(),
+            self.app.state::<SecretVault>().redact_payload(payload),
+        ) {
+            error!("Failed to emit {} event: {}", self.event, e);
+        }
+    }
+}
+
+/// Bookkeeping for a running subscription
+struct Subscription {
+    window_label: String,
+    task: AbortHandle,
+}
+
+/// Tracks the watch subscriptions of all windows
+pub struct WatchManager {
+    subscriptions: Arc<Mutex<HashMap<String, Subscription>>>,
+    next_id: AtomicUsize,
+}
+
+impl Default for WatchManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WatchManager {
+    pub fn new() -> Self {
+        WatchManager {
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicUsize::new(1),
+        }
+    }
+
+    /// Start a watch whose deltas go to the given window
+    pub async fn watch(
+        &self,
+        app: &AppHandle,
+        clients: &KubeClients,
+        window_label: &str,
+        request: WatchRequest,
+    ) -> K8sResult<WatchSubscription> {
+        let target = resources::resolve(clients, &request.query).await?;
+        if !target.info.supports("watch") {
+            return Err(K8sError::InvalidRequest(format!(
+                "{} cannot be watched",
+                target.info.plural
+            )));
+        }
+
+        let watch_id = format!("watch-{}", self.next_id.fetch_add(1, Ordering::SeqCst));
+        let event = format!("{}:{}", KUBE_WATCH_EVENT_PREFIX, watch_id);
+
+        let stream = WatchStream {
+            sink: Arc::new(WindowSink {
+                app: app.clone(),
+                window_label: window_label.to_string(),
+                watch_id: watch_id.clone(),
+                event: event.clone(),
+            }),
+            watch_id: watch_id.clone(),
+            target,
+            label_selector: request.query.label_selector.clone(),
+            field_selector: field_selector(&request.query),
+        };
+
+        // Hold the lock across the spawn so the task cannot finish and
+        // deregister itself before it was registered
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let registry = Arc::clone(&self.subscriptions);
+        let id = watch_id.clone();
+        let task = tokio::spawn(async move {
+            stream.run(request.resource_version).await;
+            registry.lock().unwrap().remove(&id);
+        });
+        subscriptions.insert(
+            watch_id.clone(),
+            Subscription {
+                window_label: window_label.to_string(),
+                task: task.abort_handle(),
+            },
+        );
+
+        info!("Started {} on {} for {}", watch_id, event, window_label);
+        Ok(WatchSubscription { watch_id, event })
+    }
+
+    /// Stop a subscription
+    pub fn unwatch(&self, watch_id: &str) -> K8sResult<()> {
+        let subscription = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .remove(watch_id)
+            .ok_or_else(|| K8sError::InvalidRequest(format!("Unknown watch: {}", watch_id)))?;
+
+        info!("Stopping {}", watch_id);
+        subscription.task.abort();
+        Ok(())
+    }
+
+    /// Stop every subscription of a window that is going away
+    pub fn close_window(&self, window_label: &str) {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .retain(|id, subscription| {
+                if subscription.window_label != window_label {
+                    return true;
+                }
+                debug!("Stopping {} of closed window {}", id, window_label);
+                subscription.task.abort();
+                false
+            });
+    }
+}
+
+/// Field selector of a query, narrowed to the named object if there is one
+fn field_selector(query: &ResourceQuery) -> Option<String> {
+    let name = query.name.as_ref().map(|n| format!("metadata.name={}", n));
+    match (name, query.field_selector.clone()) {
+        (Some(name), Some(fields)) => Some(format!("{},{}", name, fields)),
+        (name, fields) => name.or(fields),
+    }
+}
+
+/// Whether an error means our resourceVersion is too old to resume from
+fn is_gone(err: &kube::Error) -> bool {
+    matches!(err, kube::Error::Api(response) if response.code == 410)
+}
+
+/// Whether retrying cannot help, e.g. the user may not watch this resource
+fn is_fatal(err: &kube::Error) -> bool {
+    matches!(err, kube::Error::Api(response) if matches!(response.code, 401 | 403 | 404))
+}
+
+/// State of one running watch
+struct WatchStream {
+    sink: Arc<dyn WatchSink>,
+    watch_id: String,
+    target: Target,
+    label_selector: Option<String>,
+    field_selector: Option<String>,
+}
+
+impl WatchStream {
+    /// Watch until a fatal error; cancellation aborts the task
+    async fn run(&self, resource_version: Option<String>) {
+        let api = self.target.api();
+        let mut delay = MIN_RETRY_DELAY;
+        let mut resource_version = resource_version;
+
+        loop {
+            let version = match resource_version.take() {
+                Some(version) => version,
+                None => match self.relist().await {
+                    Ok(version) => version,
+                    Err(e) if is_fatal(&e) => return self.fail(e),
+                    Err(e) => {
+                        warn!("Re-list for {} failed: {}", self.watch_id, e);
+                        self.send_error(e, false);
+                        tokio::time::sleep(delay).await;
+                        delay = (delay * 2).min(MAX_RETRY_DELAY);
+                        continue;
+                    }
+                },
+            };
+
+            let stream = match api.watch(&self.watch_params(), &version).await {
+                Ok(stream) => stream,
+                Err(e) if is_gone(&e) => {
+                    debug!("{} expired, re-listing", self.watch_id);
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    continue;
+                }
+                Err(e) if is_fatal(&e) => return self.fail(e),
+                Err(e) => {
+                    warn!("Watch {} failed to connect: {}", self.watch_id, e);
+                    self.send_error(e, false);
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    resource_version = Some(version);
+                    continue;
+                }
+            };
+            futures::pin_mut!(stream);
+
+            // Resume from the newest version seen unless the server says it
+            // has expired, in which case the loop re-lists
+            let mut latest = Some(version);
+            let mut failure = None;
+            while let Some(event) = stream.next().await {
+                let message = match event {
+                    Ok(WatchEvent::Added(object)) => {
+                        latest = object.metadata.resource_version.clone().or(latest);
+                        WatchMessage::Added {
+                            object: to_json(object),
+                        }
+                    }
+                    Ok(WatchEvent::Modified(object)) => {
+                        latest = object.metadata.resource_version.clone().or(latest);
+                        WatchMessage::Modified {
+                            object: to_json(object),
+                        }
+                    }
+                    Ok(WatchEvent::Deleted(object)) => {
+                        latest = object.metadata.resource_version.clone().or(latest);
+                        WatchMessage::Deleted {
+                            object: to_json(object),
+                        }
+                    }
+                    Ok(WatchEvent::Bookmark(bookmark)) => {
+                        latest = Some(bookmark.metadata.resource_version);
+                        delay = MIN_RETRY_DELAY;
+                        continue;
+                    }
+                    Ok(WatchEvent::Error(response)) if response.code == 410 => {
+                        debug!("{} expired, re-listing", self.watch_id);
+                        latest = None;
+                        break;
+                    }
+                    // An expired version can also come back as the status of
+                    // the watch request itself
+                    Err(e) if is_gone(&e) => {
+                        debug!("{} expired, re-listing", self.watch_id);
+                        latest = None;
+                        break;
+                    }
+                    Ok(WatchEvent::Error(response)) => {
+                        failure = Some(kube::Error::Api(response));
+                        break;
+                    }
+                    Err(e) => {
+                        failure = Some(e);
+                        break;
+                    }
+                };
+
+                delay = MIN_RETRY_DELAY;
+                self.send(message);
+            }
+
+            resource_version = latest;
+            match failure {
+                Some(e) if is_fatal(&e) => return self.fail(e),
+                Some(e) => {
+                    warn!("Watch {} stream error: {}", self.watch_id, e);
+                    self.send_error(e, false);
+                }
+                None => debug!("Watch {} stream ended, resuming", self.watch_id),
+            }
+            // The delay is reset by events only, so a proxy that drops every
+            // watch at once can't make us reconnect in a tight loop
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+
+    fn watch_params(&self) -> WatchParams {
+        let mut wp = WatchParams::default().timeout(WATCH_TIMEOUT_SECS);
+        if let Some(ref labels) = self.label_selector {
+            wp = wp.labels(labels);
+        }
+        if let Some(ref fields) = self.field_selector {
+            wp = wp.fields(fields);
+        }
+        wp
+    }
+
+    /// List the current state, send it as a `SYNC` and return its version
+    async fn relist(&self) -> Result<String, kube::Error> {
+        let mut lp = ListParams::default();
+        if let Some(ref labels) = self.label_selector {
+            lp = lp.labels(labels);
+        }
+        if let Some(ref fields) = self.field_selector {
+            lp = lp.fields(fields);
+        }
+
+        let list = self.target.api().list(&lp).await?;
+        let resource_version = list.metadata.resource_version.unwrap_or_default();
+        self.send(WatchMessage::Sync {
+            objects: list.items.into_iter().map(to_json).collect(),
+            resource_version: resource_version.clone(),
+        });
+        Ok(resource_version)
+    }
+
+    fn fail(&self, err: kube::Error) {
+        error!("Watch {} stopped: {}", self.watch_id, err);
+        self.send_error(err, true);
+    }
+
+    fn send_error(&self, err: kube::Error, fatal: bool) {
+        let message = K8sError::from(err).to_string();
+        self.send(WatchMessage::Error { message, fatal });
+    }
+
+    fn send(&self, message: WatchMessage) {
+        self.sink.send(message);
+    }
+}
+
+fn to_json<T: Serialize>(object: T) -> serde_json::Value {
+    serde_json::to_value(object).unwrap_or(serde_json::Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::k8s::mock;
+    use http::StatusCode;
+    use serde_json::{json, Value};
+
+    #[derive(Default)]
+    struct Collected(Mutex<Vec<WatchMessage>>);
+
+    impl WatchSink for Collected {
+        fn send(&self, message: WatchMessage) {
+            self.0.lock().unwrap().push(message);
+        }
+    }
+
+    /// Run a watch of pods until it fails, returning a summary of what it
+    /// sent, the `resourceVersion` of each watch request and the time it took
+    async fn run<F>(
+        resource_version: Option<&str>,
+        respond: F,
+    ) -> (Vec<String>, Vec<String>, Duration)
+    where
+        F: Fn(usize, usize) -> mock::MockResponse + Send + Sync + 'static,
+    {
+        let lists = AtomicUsize::new(0);
+        let watches = AtomicUsize::new(0);
+        let (client, requests) = mock::client(move |request| {
+            if request.query.contains("watch=true") {
+                respond(usize::MAX, watches.fetch_add(1, Ordering::SeqCst))
+            } else {
+                respond(lists.fetch_add(1, Ordering::SeqCst), usize::MAX)
+            }
+        });
+        let sink = Arc::new(Collected::default());
+        let stream = WatchStream {
+            sink: Arc::clone(&sink) as Arc<dyn WatchSink>,
+            watch_id: "watch-1".to_string(),
+            target: mock::pods(client),
+            label_selector: None,
+            field_selector: None,
+        };
+
+        let started = tokio::time::Instant::now();
+        stream.run(resource_version.map(String::from)).await;
+        let elapsed = started.elapsed();
+
+        let versions = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|r| r.query.contains("watch=true"))
+            .filter_map(|r| {
+                r.query
+                    .split('&')
+                    .find_map(|p| p.strip_prefix("resourceVersion="))
+                    .map(String::from)
+            })
+            .collect();
+        let name = |object: &Value| object["metadata"]["name"].as_str().unwrap().to_string();
+        let messages = sink
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|message| match message {
+                WatchMessage::Added { object } => format!("ADDED {}", name(object)),
+                WatchMessage::Modified { object } => format!("MODIFIED {}", name(object)),
+                WatchMessage::Deleted { object } => format!("DELETED {}", name(object)),
+                WatchMessage::Sync {
+                    resource_version, ..
+                } => format!("SYNC {}", resource_version),
+                WatchMessage::Error { fatal, .. } => format!("ERROR fatal={}", fatal),
+            })
+            .collect();
+        (messages, versions, elapsed)
+    }
+
+    fn pod_list(resource_version: &str) -> mock::MockResponse {
+        let list = json!({
+            "apiVersion": "v1",
+            "kind": "PodList",
+            "metadata": {"resourceVersion": resource_version},
+            "items": [pod("web-0", "9")]
+        });
+        (StatusCode::OK, list).into()
+    }
+
+    fn pod(name: &str, resource_version: &str) -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {"name": name, "namespace": "shop", "resourceVersion": resource_version}
+        })
+    }
+
+    fn expired() -> Value {
+        let (_, status) = mock::failure(StatusCode::GONE, "Expired", "too old resource version");
+        status
+    }
+
+    fn forbidden() -> mock::MockResponse {
+        mock::failure(StatusCode::FORBIDDEN, "Forbidden", "pods is forbidden").into()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_resumes_and_relists() {
+        let (messages, versions, elapsed) = run(None, |list, watch| match (list, watch) {
+            (0, _) => pod_list("10"),
+            (_, usize::MAX) => pod_list("20"),
+            (_, 0) => mock::watch_events(&[
+                json!({"type": "ADDED", "object": pod("web-1", "11")}),
+                json!({"type": "BOOKMARK", "object": {
+                    "kind": "Pod", "apiVersion": "v1", "metadata": {"resourceVersion": "15"}
+                }}),
+            ]),
+            (_, 1) => mock::watch_events(&[json!({"type": "ERROR", "object": expired()})]),
+            _ => forbidden(),
+        })
+        .await;
+
+        assert_eq!(versions, ["10", "15", "20"]);
+        assert_eq!(
+            messages,
+            ["SYNC 10", "ADDED web-1", "SYNC 20", "ERROR fatal=true"]
+        );
+        // One second after the clean end, two after the expiry
+        assert_eq!(elapsed.as_secs(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_backs_off_without_events() {
+        let (messages, versions, elapsed) = run(Some("5"), |_, watch| match watch {
+            usize::MAX => pod_list("7"),
+            0 => (StatusCode::GONE, expired()).into(),
+            1..=3 => mock::watch_events(&[]),
+            _ => forbidden(),
+        })
+        .await;
+
+        assert_eq!(versions, ["5", "7", "7", "7", "7"]);
+        assert_eq!(messages, ["SYNC 7", "ERROR fatal=true"]);
+        assert_eq!(elapsed.as_secs(), 1 + 2 + 4 + 8);
+    }
+
+    #[test]
+    fn test_field_selector_includes_name() {
+        let mut query = ResourceQuery {
+            kind: "pods".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(field_selector(&query), None);
+
+        query.field_selector = Some("status.phase=Running".to_string());
+        assert_eq!(
+            field_selector(&query).as_deref(),
+            Some("status.phase=Running")
+        );
+
+        query.name = Some("web-0".to_string());
+        assert_eq!(
+            field_selector(&query).as_deref(),
+            Some("metadata.name=web-0,status.phase=Running")
+        );
+    }
+}
@@ -25,12 +25,19 @@ use tauri::{
 
 mod command_palette;
 mod commands;
+mod fs;
 mod ipc;
+mod k8s;
 mod menu;
+mod prediction;
+mod pty;
+mod redact;
 mod screenshot;
+mod shell;
 mod window;
 
 use command_palette::*;
+use ipc::parse_args;
 use screenshot::ScreenRect;
 
 /// Application state to track open windows
@@ -38,6 +45,8 @@ struct AppState {
     window_count: Mutex<usize>,
     #[allow(dead_code)]
     fixed_windows: Mutex<HashMap<String, String>>,
+    pty: pty::PtyManager,
+    shell: shell::JobManager,
 }
 
 /// Window preferences for subwindows
@@ -76,6 +85,50 @@ struct ExecInvokeMessage {
     args: Vec<serde_json::Value>,
 }
 
+/// Arguments of the `write` PTY method
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PtyWriteArgs {
+    session_id: String,
+    data: String,
+}
+
+/// Arguments of the `resize` PTY method
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PtyResizeArgs {
+    session_id: String,
+    cols: u16,
+    rows: u16,
+}
+
+/// Arguments of PTY methods that only address a session
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PtySessionArgs {
+    session_id: String,
+}
+
+/// Arguments of shell methods that only address a job
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ShellJobArgs {
+    job_id: String,
+}
+
+/// Arguments of FS methods that only address a path
+#[derive(Debug, Deserialize)]
+struct FsPathArgs {
+    path: String,
+}
+
+/// Arguments of the `write_file` FS method
+#[derive(Debug, Deserialize)]
+struct FsWriteArgs {
+    path: String,
+    contents: String,
+}
+
 /// Create a new window with specified arguments
 fn create_window_internal(
     app: &AppHandle,
@@ -265,7 +318,12 @@ async fn synchronous_message(
 ///   "args": [...]
 /// }
 #[tauri::command]
-async fn exec_invoke(_app: AppHandle, message: String) -> Result<serde_json::Value, String> {
+async fn exec_invoke(
+    window: Window,
+    app: AppHandle,
+    state: State<'_, AppState>,
+    message: String,
+) -> Result<serde_json::Value, String> {
     debug!("Received exec invoke: {}", message);
 
     // Parse the message
@@ -282,16 +340,16 @@ async fn exec_invoke(_app: AppHandle, message: String) -> Result<serde_json::Val
     // Handle plugin-specific commands
     match msg.module.as_str() {
         // PTY/Terminal operations
-        "pty" => handle_pty_operation(&msg.method, &msg.args).await,
+        "pty" => handle_pty_operation(&app, &window, &state, &msg.method, &msg.args).await,
 
         // File system operations
-        "fs" => handle_fs_operation(&msg.method, &msg.args).await,
+        "fs" => handle_fs_operation(&app, &msg.method, &msg.args).await,
 
         // Shell operations
-        "shell" => handle_shell_operation(&msg.method, &msg.args).await,
+        "shell" => handle_shell_operation(&app, &window, &state, &msg.method, &msg.args).await,
 
         // Kubectl operations
-        "kubectl" => handle_kubectl_operation(&msg.method, &msg.args).await,
+        "kubectl" => handle_kubectl_operation(&app, &window, &msg.method, &msg.args).await,
 
         // Generic plugin operation
         "" | "generic" => {
@@ -314,68 +372,189 @@ async fn exec_invoke(_app: AppHandle, message: String) -> Result<serde_json::Val
 }
 
 /// Handle PTY-related operations
+///
+/// Sessions are owned by the calling window and stream their output back to
+/// it as `pty-data`/`pty-exit` events.
 async fn handle_pty_operation(
+    app: &AppHandle,
+    window: &Window,
+    state: &State<'_, AppState>,
     method: &str,
     args: &[serde_json::Value],
 ) -> Result<serde_json::Value, String> {
     debug!("PTY operation: {} with {} args", method, args.len());
 
-    match method {
+    let return_value = match method {
         "spawn" | "create" | "init" => {
-            // PTY spawn operations are handled via WebSocket server
-            // Just acknowledge the request
-            Ok(serde_json::json!({
-                "success": true,
-                "message": "PTY operations handled via WebSocket channel"
-            }))
+            let options: pty::SpawnOptions = if args.is_empty() {
+                pty::SpawnOptions::default()
+            } else {
+                parse_args(args)?
+            };
+            let session = state.pty.spawn(app, window.label(), options)?;
+            serde_json::to_value(session).map_err(|e| e.to_string())?
         }
-        _ => Ok(serde_json::json!({
-            "success": true,
-            "returnValue": null
-        })),
-    }
+        "exec" => {
+            let request: k8s::exec::ExecRequest = parse_args(args)?;
+            let clients = app.state::<k8s::KubeClients>();
+            let process = k8s::exec::attach(&clients, &request).await;
+            k8s::audit::record(
+                app,
+                k8s::audit::AuditEvent::new("exec", request.context.as_deref())
+                    .object(
+                        Some("Pod"),
+                        request.namespace.as_deref(),
+                        Some(&request.pod),
+                    )
+                    .detail(match request.command.is_empty() {
+                        true => "shell".to_string(),
+                        false => request.command.join(" "),
+                    })
+                    .result(&process),
+            )
+            .await;
+            let process = process?;
+            let session = state.pty.attach_remote(
+                app,
+                window.label(),
+                process,
+                request.cols,
+                request.rows,
+            )?;
+            serde_json::to_value(session).map_err(|e| e.to_string())?
+        }
+        "write" => {
+            let args: PtyWriteArgs = parse_args(args)?;
+            state.pty.write(&args.session_id, &args.data)?;
+            serde_json::Value::Null
+        }
+        "resize" => {
+            let args: PtyResizeArgs = parse_args(args)?;
+            state.pty.resize(&args.session_id, args.cols, args.rows)?;
+            serde_json::Value::Null
+        }
+        "kill" => {
+            let args: PtySessionArgs = parse_args(args)?;
+            state.pty.kill(&args.session_id)?;
+            serde_json::Value::Null
+        }
+        _ => return Err(format!("Unknown PTY method: {}", method)),
+    };
+
+    Ok(serde_json::json!({
+        "success": true,
+        "returnValue": return_value
+    }))
 }
 
 /// Handle filesystem operations
+///
+/// Mirrors the `read_file`/`write_file`/... commands for plugins that go
+/// through exec_invoke. Every path is checked against the `FsScope`.
 async fn handle_fs_operation(
+    app: &AppHandle,
     method: &str,
     args: &[serde_json::Value],
 ) -> Result<serde_json::Value, String> {
     debug!("FS operation: {} with {} args", method, args.len());
 
-    // File system operations can be implemented here
-    // For now, acknowledge the request
+    let scope = app.state::<fs::FsScope>();
+    let return_value = match method {
+        "read_file" => {
+            let args: FsPathArgs = parse_args(args)?;
+            let contents = fs::blocking(&scope, move |scope| scope.read_file(&args.path)).await;
+            serde_json::json!(contents.map_err(|e| e.to_string())?)
+        }
+        "write_file" => {
+            let args: FsWriteArgs = parse_args(args)?;
+            fs::blocking(&scope, move |scope| {
+                scope.write_file(&args.path, &args.contents)
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+            serde_json::Value::Null
+        }
+        "read_dir" => {
+            let args: FsPathArgs = parse_args(args)?;
+            let entries = fs::blocking(&scope, move |scope| scope.read_dir(&args.path)).await;
+            serde_json::json!(entries.map_err(|e| e.to_string())?)
+        }
+        "remove_file" => {
+            let args: FsPathArgs = parse_args(args)?;
+            fs::blocking(&scope, move |scope| scope.remove_file(&args.path))
+                .await
+                .map_err(|e| e.to_string())?;
+            serde_json::Value::Null
+        }
+        "path_exists" => {
+            let args: FsPathArgs = parse_args(args)?;
+            let exists = fs::blocking(&scope, move |scope| scope.path_exists(&args.path)).await;
+            serde_json::json!(exists.map_err(|e| e.to_string())?)
+        }
+        _ => return Err(format!("Unknown FS method: {}", method)),
+    };
+
     Ok(serde_json::json!({
         "success": true,
-        "returnValue": null
+        "returnValue": return_value
     }))
 }
 
 /// Handle shell operations
+///
+/// `exec` resolves once the job has exited; its output is streamed to the
+/// calling window as `shell-output` events in the meantime.
 async fn handle_shell_operation(
+    app: &AppHandle,
+    window: &Window,
+    state: &State<'_, AppState>,
     method: &str,
     args: &[serde_json::Value],
 ) -> Result<serde_json::Value, String> {
     debug!("Shell operation: {} with {} args", method, args.len());
 
-    // Shell operations are typically handled via PTY
+    let return_value = match method {
+        "exec" => {
+            let options: shell::ExecOptions = parse_args(args)?;
+            let result = state.shell.exec(app, window.label(), options).await?;
+            serde_json::to_value(result).map_err(|e| e.to_string())?
+        }
+        "cancel" => {
+            let args: ShellJobArgs = parse_args(args)?;
+            state.shell.cancel(&args.job_id)?;
+            serde_json::Value::Null
+        }
+        _ => return Err(format!("Unknown shell method: {}", method)),
+    };
+
     Ok(serde_json::json!({
         "success": true,
-        "returnValue": null
+        "returnValue": return_value
     }))
 }
 
 /// Handle kubectl operations
+///
+/// Requests are served by the native Kubernetes client rather than a
+/// kubectl subprocess; see the `k8s` module.
 async fn handle_kubectl_operation(
+    app: &AppHandle,
+    window: &Window,
     method: &str,
     args: &[serde_json::Value],
 ) -> Result<serde_json::Value, String> {
     debug!("Kubectl operation: {} with {} args", method, args.len());
 
-    // Kubectl operations are typically executed via shell/PTY
+    // Secrets, tokens and keys in responses stay masked until revealed
+    let vault = app.state::<redact::SecretVault>();
+    let return_value = k8s::handle_operation(app, window.label(), method, args)
+        .await
+        .map_err(|e| vault.redact_text(&e))?;
+    let return_value = vault.redact_json(return_value);
+
     Ok(serde_json::json!({
         "success": true,
-        "returnValue": null
+        "returnValue": return_value
     }))
 }
 
@@ -436,10 +615,38 @@ fn main() {
         .manage(AppState {
             window_count: Mutex::new(0),
             fixed_windows: Mutex::new(HashMap::new()),
+            pty: pty::PtyManager::new(),
+            shell: shell::JobManager::new(),
         })
+        .manage(redact::SecretVault::new())
+        .manage(k8s::KubeClients::new())
+        .manage(k8s::WatchManager::new())
+        .manage(k8s::InformerCache::new())
+        .manage(k8s::LogManager::new())
+        .manage(k8s::PortForwardManager::new())
+        .manage(k8s::EventTimeline::new())
+        .manage(k8s::MetricsPoller::new())
         .setup(|app| {
             info!("Kui starting up...");
 
+            // Restrict renderer filesystem access to the allowed roots
+            app.manage(fs::FsScope::new(app.handle())?);
+
+            // One database handle for the palette, event timeline, port-forward
+            // registry and audit log; if it can't be opened or migrated the
+            // app still starts and get_command_palette_db_status says why
+            app.manage(command_palette::CommandPaletteDb::new(app.handle()));
+
+            // Keep API discovery and schemas across restarts and offline
+            app.state::<k8s::KubeClients>()
+                .set_cache_dir(app.path().app_cache_dir()?.join("kube"));
+
+            // Bring back port-forwards the user asked to keep across restarts
+            k8s::portforward::restore_on_startup(app.handle());
+
+            // Record cluster events of the current context
+            k8s::EventTimeline::start(app.handle());
+
             // Initialize menu subsystem
             menu::init();
 
@@ -461,6 +668,18 @@ fn main() {
         .on_window_event(|window, event| {
             if let WindowEvent::CloseRequested { .. } = event {
                 let state = window.state::<AppState>();
+                state.pty.close_window(window.label());
+                state.shell.close_window(window.label());
+                window
+                    .state::<k8s::WatchManager>()
+                    .close_window(window.label());
+                window
+                    .state::<k8s::LogManager>()
+                    .close_window(window.label());
+                window
+                    .state::<k8s::InformerCache>()
+                    .close_window(window.label());
+
                 let mut count = state.window_count.lock().unwrap();
                 if *count > 0 {
                     *count -= 1;
@@ -473,9 +692,15 @@ fn main() {
             synchronous_message,
             exec_invoke,
             capture_to_clipboard,
+            redact::copy_to_clipboard,
+            redact::reveal_secret,
+            get_command_palette_db_status,
             record_command_invocation,
             get_command_stats,
             get_top_commands,
+            get_frecency_half_life,
+            set_frecency_half_life,
+            predict_next_commands,
             record_search_query,
             get_recent_queries,
             cleanup_command_palette_data,
@@ -486,6 +711,30 @@ fn main() {
             get_command_patterns,
             get_pattern_suggestions,
             get_command_history,
+            search_command_history,
+            fs::read_file,
+            fs::write_file,
+            fs::read_dir,
+            fs::remove_file,
+            fs::path_exists,
+            fs::path_home_dir,
+            fs::pick_fs_path,
+            k8s::kubeconfig::get_kubeconfig,
+            k8s::kubeconfig::use_kube_context,
+            k8s::kubeconfig::set_kube_namespace,
+            k8s::portforward::start_port_forward,
+            k8s::portforward::list_port_forwards,
+            k8s::portforward::stop_port_forward,
+            k8s::audit::query_audit_log,
+            k8s::audit::export_audit_log,
+            k8s::audit::verify_audit_log,
+            k8s::events::get_object_events,
+            k8s::events::get_recent_warnings,
+            k8s::events::get_events_by_reason,
+            k8s::metrics::get_resource_metrics,
+            k8s::metrics::get_metrics_history,
+            k8s::discovery::get_api_resources,
+            k8s::schema::get_resource_schema,
         ])
         .run(tauri::generate_context!())
         .expect("error while running Kui application");
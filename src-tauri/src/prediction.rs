@@ -0,0 +1,546 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Next-command prediction from command history
+//!
+//! A variable-order Markov model over command ids. Each level of the model
+//! counts what followed a history of up to `MAX_ORDER` commands within a
+//! scope: the same kube context, namespace and resource kind, then only
+//! the context and namespace, then only the context, then anywhere. Levels
+//! are tried from the longest history in the narrowest scope down to
+//! overall command frequency, and interpolated with Witten-Bell smoothing
+//! so that a level with little evidence defers to the ones below it. So
+//! flows repeated in prod predict prod, and a context without history of
+//! its own falls back on what the user does elsewhere.
+//!
+//! The smoothed distribution is then calibrated with a temperature fitted
+//! on the most recent history, so that a suggestion given 70% is taken
+//! about 70% of the time.
+
+use crate::command_palette::CommandScope;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Longest history the model conditions on
+const MAX_ORDER: usize = 3;
+
+/// Commands further apart than this aren't part of one flow
+const SESSION_GAP_SECS: i64 = 30 * 60;
+
+/// Share of history held out to fit the temperature
+const HOLDOUT_SHARE: usize = 10;
+
+/// Held-out commands needed to fit a temperature rather than assume 1
+const MIN_HOLDOUT: usize = 20;
+
+/// Temperatures tried when calibrating
+const TEMPERATURES: [f64; 7] = [0.5, 0.7, 1.0, 1.4, 2.0, 2.8, 4.0];
+
+/// Scopes a level can condition on, narrowest first: how many of context,
+/// namespace and resource kind it keeps
+const SCOPES: [usize; 4] = [3, 2, 1, 0];
+
+/// A successful command from history
+#[derive(Debug, Clone)]
+pub struct HistoryStep {
+    pub command_id: String,
+    /// RFC 3339
+    pub timestamp: String,
+    pub scope: CommandScope,
+}
+
+/// A predicted next command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandPrediction {
+    pub command_id: String,
+    /// Calibrated probability that this is the next command
+    pub probability: f64,
+    /// History supporting the prediction, strongest first
+    pub support: Vec<PredictionSupport>,
+}
+
+/// History supporting a prediction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PredictionSupport {
+    /// Commands the predicted one followed, oldest first; empty for how
+    /// often it is run at all
+    pub after: Vec<String>,
+    /// Scope the history is from; `None` where it isn't restricted
+    pub context: Option<String>,
+    pub namespace: Option<String>,
+    pub resource_kind: Option<String>,
+    /// Times the predicted command came next, out of `total`
+    pub count: u32,
+    pub total: u32,
+    pub last_seen: String,
+    /// Share of the prediction's probability this history accounts for
+    pub weight: f64,
+}
+
+/// Level of the model: a history length within a scope
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    scope: usize,
+    order: usize,
+}
+
+/// State a command ran in: the commands before it in its session and the
+/// scope the user was in
+#[derive(Debug, Clone)]
+struct Before {
+    commands: Vec<String>,
+    scope: CommandScope,
+}
+
+/// What a level conditions on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LevelKey {
+    scope: [Option<String>; 3],
+    after: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Continuation {
+    count: u32,
+    last_seen: String,
+}
+
+/// Commands seen after one `LevelKey`
+#[derive(Debug, Clone, Default)]
+struct Continuations {
+    total: u32,
+    next: HashMap<String, Continuation>,
+}
+
+impl Continuations {
+    /// Witten-Bell weight of this level against the ones below it
+    fn lambda(&self) -> f64 {
+        let total = f64::from(self.total);
+        total / (total + self.next.len() as f64)
+    }
+}
+
+/// Next-command model trained on history
+pub struct PredictionModel {
+    levels: Vec<Level>,
+    counts: Vec<HashMap<LevelKey, Continuations>>,
+    vocabulary: usize,
+    temperature: f64,
+}
+
+impl PredictionModel {
+    /// Train on successful commands, oldest first, calibrating on the
+    /// newest of them
+    pub fn train(history: &[HistoryStep]) -> Self {
+        let previous = previous_states(history);
+
+        // Calibrate on held-out history, then retrain on all of it
+        let holdout = history.len() / HOLDOUT_SHARE;
+        let temperature = if holdout >= MIN_HOLDOUT {
+            let split = history.len() - holdout;
+            Self::count(&history[..split], &previous[..split])
+                .fit_temperature(&history[split..], &previous[split..])
+        } else {
+            1.0
+        };
+
+        let mut model = Self::count(history, &previous);
+        model.temperature = temperature;
+        model
+    }
+
+    /// The `limit` most likely next commands after `last_commands`, oldest
+    /// first, in `scope`
+    pub fn predict(
+        &self,
+        last_commands: &[String],
+        scope: &CommandScope,
+        limit: usize,
+    ) -> Vec<CommandPrediction> {
+        let Some(distribution) = self.distribution(last_commands, scope) else {
+            return Vec::new();
+        };
+
+        let mut predictions: Vec<CommandPrediction> = distribution
+            .candidates
+            .iter()
+            .map(|(command_id, candidate)| {
+                let mut support: Vec<PredictionSupport> = candidate
+                    .support
+                    .iter()
+                    .map(|&(index, contribution)| {
+                        let (key, continuations) = &distribution.applicable[index];
+                        let next = &continuations.next[command_id];
+                        PredictionSupport {
+                            after: key.after.clone(),
+                            context: key.scope[0].clone(),
+                            namespace: key.scope[1].clone(),
+                            resource_kind: key.scope[2].clone(),
+                            count: next.count,
+                            total: continuations.total,
+                            last_seen: next.last_seen.clone(),
+                            weight: contribution / candidate.probability,
+                        }
+                    })
+                    .collect();
+                support.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+
+                CommandPrediction {
+                    command_id: command_id.clone(),
+                    probability: distribution.calibrated(candidate.probability),
+                    support,
+                }
+            })
+            .collect();
+
+        predictions.sort_by(|a, b| {
+            b.probability
+                .total_cmp(&a.probability)
+                .then_with(|| a.command_id.cmp(&b.command_id))
+        });
+        predictions.truncate(limit);
+        predictions
+    }
+
+    /// Count continuations at every level
+    fn count(history: &[HistoryStep], previous: &[Before]) -> Self {
+        let levels = levels();
+        let mut counts: Vec<HashMap<LevelKey, Continuations>> = vec![HashMap::new(); levels.len()];
+        let mut vocabulary = HashSet::new();
+
+        for (step, before) in history.iter().zip(previous) {
+            vocabulary.insert(step.command_id.as_str());
+            for (level, level_counts) in levels.iter().zip(counts.iter_mut()) {
+                let Some(key) = level_key(*level, &before.commands, &before.scope) else {
+                    continue;
+                };
+                let continuations = level_counts.entry(key).or_default();
+                continuations.total += 1;
+                let next = continuations
+                    .next
+                    .entry(step.command_id.clone())
+                    .or_default();
+                next.count += 1;
+                // History is in order, so the last one seen is the latest
+                next.last_seen = step.timestamp.clone();
+            }
+        }
+
+        PredictionModel {
+            levels,
+            counts,
+            vocabulary: vocabulary.len(),
+            temperature: 1.0,
+        }
+    }
+
+    /// Smoothed probabilities of the commands seen in the applicable levels
+    fn distribution(
+        &self,
+        last_commands: &[String],
+        scope: &CommandScope,
+    ) -> Option<Distribution<'_>> {
+        if self.vocabulary == 0 {
+            return None;
+        }
+
+        let applicable: Vec<(LevelKey, &Continuations)> = self
+            .levels
+            .iter()
+            .zip(&self.counts)
+            .filter_map(|(level, counts)| {
+                let key = level_key(*level, last_commands, scope)?;
+                let continuations = counts.get(&key)?;
+                Some((key, continuations))
+            })
+            .collect();
+
+        // Mass left to a level after the ones above it took their share
+        let mut remaining = 1.0;
+        let mut candidates: HashMap<String, Candidate> = HashMap::new();
+        for (index, (_, continuations)) in applicable.iter().enumerate() {
+            let lambda = continuations.lambda();
+            for (command_id, next) in &continuations.next {
+                let contribution =
+                    remaining * lambda * f64::from(next.count) / f64::from(continuations.total);
+                let candidate = candidates.entry(command_id.clone()).or_default();
+                candidate.probability += contribution;
+                candidate.support.push((index, contribution));
+            }
+            remaining *= 1.0 - lambda;
+        }
+
+        // Whatever is left is spread over every command ever seen
+        let uniform = remaining / self.vocabulary as f64;
+        for candidate in candidates.values_mut() {
+            candidate.probability += uniform;
+        }
+
+        let tempered = |p: f64| p.powf(1.0 / self.temperature);
+        let unseen = self.vocabulary.saturating_sub(candidates.len()) as f64;
+        let normalizer = candidates
+            .values()
+            .map(|candidate| tempered(candidate.probability))
+            .sum::<f64>()
+            + unseen * tempered(uniform);
+
+        Some(Distribution {
+            applicable,
+            candidates,
+            uniform,
+            temperature: self.temperature,
+            normalizer,
+        })
+    }
+
+    /// Temperature minimizing the log loss of predicting `holdout`
+    fn fit_temperature(&mut self, holdout: &[HistoryStep], previous: &[Before]) -> f64 {
+        let mut best = (f64::INFINITY, 1.0);
+        for temperature in TEMPERATURES {
+            self.temperature = temperature;
+            let loss: f64 = holdout
+                .iter()
+                .zip(previous)
+                .filter_map(|(step, before)| {
+                    let distribution = self.distribution(&before.commands, &before.scope)?;
+                    let probability = distribution
+                        .candidates
+                        .get(&step.command_id)
+                        .map_or(distribution.uniform, |candidate| candidate.probability);
+                    Some(
+                        -distribution
+                            .calibrated(probability)
+                            .max(f64::MIN_POSITIVE)
+                            .ln(),
+                    )
+                })
+                .sum();
+            if loss < best.0 {
+                best = (loss, temperature);
+            }
+        }
+        best.1
+    }
+}
+
+/// Result of evaluating the model for one history
+struct Distribution<'a> {
+    applicable: Vec<(LevelKey, &'a Continuations)>,
+    candidates: HashMap<String, Candidate>,
+    /// Smoothed probability of a command no applicable level has seen
+    uniform: f64,
+    temperature: f64,
+    normalizer: f64,
+}
+
+impl Distribution<'_> {
+    fn calibrated(&self, probability: f64) -> f64 {
+        probability.powf(1.0 / self.temperature) / self.normalizer
+    }
+}
+
+#[derive(Debug, Default)]
+struct Candidate {
+    probability: f64,
+    /// Contribution of each applicable level, by index
+    support: Vec<(usize, f64)>,
+}
+
+/// Levels from most to least specific: every history length in each scope,
+/// then command frequency in each scope
+fn levels() -> Vec<Level> {
+    let sequences = SCOPES.iter().flat_map(|&scope| {
+        (1..=MAX_ORDER)
+            .rev()
+            .map(move |order| Level { scope, order })
+    });
+    let frequencies = SCOPES.iter().map(|&scope| Level { scope, order: 0 });
+    sequences.chain(frequencies).collect()
+}
+
+/// What `level` conditions on for a command run after `before` in `scope`,
+/// if there is enough history for it
+fn level_key(level: Level, before: &[String], scope: &CommandScope) -> Option<LevelKey> {
+    if before.len() < level.order {
+        return None;
+    }
+    let keep = |index: usize, value: &Option<String>| {
+        if index < level.scope {
+            value.clone()
+        } else {
+            None
+        }
+    };
+    Some(LevelKey {
+        scope: [
+            keep(0, &scope.context),
+            keep(1, &scope.namespace),
+            keep(2, &scope.resource_kind),
+        ],
+        after: before[before.len() - level.order..].to_vec(),
+    })
+}
+
+/// The state before each step: the up to `MAX_ORDER` commands run before
+/// it in the same session, and the scope of the one right before it
+///
+/// The scope a command is recorded with is the one it left the user in, so
+/// a request's current scope matches the previous step's. A session starts
+/// in the context and namespace of its first command, on no resource.
+fn previous_states(history: &[HistoryStep]) -> Vec<Before> {
+    let mut previous = Vec::with_capacity(history.len());
+    let mut session: Vec<String> = Vec::new();
+    let mut scope: Option<&CommandScope> = None;
+    let mut last_time = None;
+
+    for step in history {
+        let time = DateTime::parse_from_rfc3339(&step.timestamp)
+            .ok()
+            .map(|t| t.timestamp());
+        if let (Some(last), Some(time)) = (last_time, time) {
+            if time - last > SESSION_GAP_SECS {
+                session.clear();
+                scope = None;
+            }
+        }
+        last_time = time.or(last_time);
+
+        previous.push(Before {
+            commands: session.clone(),
+            scope: scope.cloned().unwrap_or_else(|| CommandScope {
+                resource_kind: None,
+                ..step.scope.clone()
+            }),
+        });
+        session.push(step.command_id.clone());
+        if session.len() > MAX_ORDER {
+            session.remove(0);
+        }
+        scope = Some(&step.scope);
+    }
+    previous
+}
+
+/// The commands of the session still going on at `now`, oldest first
+pub fn current_session(history: &[HistoryStep], now: i64) -> Vec<String> {
+    let mut last_commands = Vec::new();
+    let mut after = now;
+    for step in history.iter().rev() {
+        let Ok(time) = DateTime::parse_from_rfc3339(&step.timestamp) else {
+            break;
+        };
+        if after - time.timestamp() > SESSION_GAP_SECS || last_commands.len() == MAX_ORDER {
+            break;
+        }
+        after = time.timestamp();
+        last_commands.insert(0, step.command_id.clone());
+    }
+    last_commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(command_id: &str, minute: u32, context: &str) -> HistoryStep {
+        HistoryStep {
+            command_id: command_id.to_string(),
+            timestamp: format!("2025-03-01T{:02}:{:02}:00Z", minute / 60, minute % 60),
+            scope: CommandScope {
+                context: Some(context.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_predict_by_context() {
+        // In prod, `get pods` is followed by `logs`; in dev, by `delete`
+        let mut history = Vec::new();
+        let mut minute = 0;
+        for _ in 0..10 {
+            history.push(step("get pods", minute, "prod"));
+            history.push(step("logs", minute + 1, "prod"));
+            history.push(step("get pods", minute + 2, "dev"));
+            history.push(step("delete pod", minute + 3, "dev"));
+            minute += 4;
+        }
+        let model = PredictionModel::train(&history);
+        let last = vec!["get pods".to_string()];
+
+        let prod = CommandScope {
+            context: Some("prod".to_string()),
+            ..Default::default()
+        };
+        let predictions = model.predict(&last, &prod, 10);
+        assert_eq!(predictions[0].command_id, "logs");
+        assert!(predictions[0].probability > 0.5);
+        let total: f64 = predictions.iter().map(|p| p.probability).sum();
+        assert!(total <= 1.0 + 1e-9);
+
+        // The strongest support is the prod flow itself
+        let support = &predictions[0].support[0];
+        assert_eq!(support.after, last);
+        assert_eq!(support.context.as_deref(), Some("prod"));
+        assert_eq!((support.count, support.total), (10, 10));
+
+        let dev = CommandScope {
+            context: Some("dev".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(model.predict(&last, &dev, 1)[0].command_id, "delete pod");
+
+        // Unknown context backs off to all history
+        let staging = CommandScope {
+            context: Some("staging".to_string()),
+            ..Default::default()
+        };
+        let predictions = model.predict(&last, &staging, 10);
+        let ids: Vec<&str> = predictions.iter().map(|p| p.command_id.as_str()).collect();
+        assert!(ids[..2].contains(&"logs") && ids[..2].contains(&"delete pod"));
+
+        // Resource kind is the one the user was on, not the next command's
+        let on = |command_id: &str, minute: u32, kind: &str| HistoryStep {
+            scope: CommandScope {
+                resource_kind: Some(kind.to_string()),
+                ..Default::default()
+            },
+            ..step(command_id, minute, "")
+        };
+        let mut kinds = Vec::new();
+        for minute in (0..40).step_by(4) {
+            kinds.push(on("view", minute, "Pod"));
+            kinds.push(on("edit", minute + 1, "ConfigMap"));
+            kinds.push(on("view", minute + 2, "Deployment"));
+            kinds.push(on("scale", minute + 3, "ReplicaSet"));
+        }
+        let on_pod = CommandScope {
+            resource_kind: Some("Pod".to_string()),
+            ..Default::default()
+        };
+        let predictions = PredictionModel::train(&kinds).predict(&["view".to_string()], &on_pod, 1);
+        assert_eq!(predictions[0].command_id, "edit");
+        assert!(predictions[0].probability > 0.5);
+
+        let now = DateTime::parse_from_rfc3339("2025-03-01T00:45:00Z")
+            .unwrap()
+            .timestamp();
+        assert_eq!(
+            current_session(&history, now),
+            vec!["logs", "get pods", "delete pod"]
+        );
+        assert!(current_session(&history, now + SESSION_GAP_SECS).is_empty());
+    }
+}
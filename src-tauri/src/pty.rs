@@ -0,0 +1,732 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native PTY session management for Kui
+//!
+//! Each session owns a pseudo-terminal running the user's login shell. Output
+//! is pumped from a reader thread to the owning window as `pty-data` events,
+//! and a final `pty-exit` event is emitted once the child process exits.
+//!
+//! A session may instead be attached to a process in a Kubernetes container
+//! (see `k8s::exec`). Such sessions are pumped by an async task but are
+//! otherwise indistinguishable to the renderer: they emit the same events and
+//! accept the same write/resize/kill methods.
+
+use crate::k8s::exec::exit_code;
+use crate::redact::{OutputRedaction, SecretVault, OUTPUT_FLUSH_DELAY};
+use kube::api::{AttachedProcess, TerminalSize};
+use log::{debug, error, info};
+use portable_pty::{native_pty_system, ChildKiller, CommandBuilder, MasterPty, PtySize};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, EventTarget, Manager};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, Notify};
+
+/// Event carrying terminal output for a session
+pub const PTY_DATA_EVENT: &str = "pty-data";
+
+/// Event emitted once the process behind a session has exited
+pub const PTY_EXIT_EVENT: &str = "pty-exit";
+
+/// Default terminal dimensions used when the renderer does not provide any
+const DEFAULT_COLS: u16 = 80;
+const DEFAULT_ROWS: u16 = 24;
+
+/// Time to wait for the exit status of a container process after its
+/// output has ended
+const REMOTE_STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Options accepted by the `spawn` method
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpawnOptions {
+    /// Program to run instead of the user's login shell
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// Arguments passed to `shell`
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub cols: Option<u16>,
+    #[serde(default)]
+    pub rows: Option<u16>,
+}
+
+/// Result of a successful `spawn`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpawnedSession {
+    pub session_id: String,
+    pub pid: Option<u32>,
+}
+
+/// Payload of `pty-data` events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PtyDataPayload {
+    session_id: String,
+    data: String,
+}
+
+/// Payload of `pty-exit` events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PtyExitPayload {
+    session_id: String,
+    exit_code: Option<u32>,
+}
+
+/// Where a session's output and exit go
+trait PtySink: Send + Sync + 'static {
+    /// Vault masking credentials in the output
+    fn vault(&self) -> &SecretVault;
+    fn data(&self, data: String);
+    fn exit(&self, exit_code: Option<u32>);
+}
+
+/// Sends a session's output and exit to the window that owns it
+struct WindowSink {
+    app: AppHandle,
+    window_label: String,
+    session_id: String,
+}
+
+impl PtySink for WindowSink {
+    fn vault(&self) -> &SecretVault {
+        self.app.state::<SecretVault>().inner()
+    }
+
+    fn data(&self, data: String) {
+        if let Err(e) = self.app.emit_to(
+            EventTarget::webview_window(self.window_label.as_str()),
+            PTY_DATA_EVENT,
+            PtyDataPayload {
+                session_id: self.session_id.clone(),
+                data,
+            },
+        ) {
+            error!("Failed to emit pty-data event: {}", e);
+        }
+    }
+
+    fn exit(&self, exit_code: Option<u32>) {
+        self.app
+            .emit_to(
+                EventTarget::webview_window(self.window_label.as_str()),
+                PTY_EXIT_EVENT,
+                PtyExitPayload {
+                    session_id: self.session_id.clone(),
+                    exit_code,
+                },
+            )
+            .unwrap_or_else(|e| error!("Failed to emit pty-exit event: {}", e));
+    }
+}
+
+/// A running terminal session
+struct PtySession {
+    window_label: String,
+    io: SessionIo,
+}
+
+/// What a session's terminal is connected to
+enum SessionIo {
+    /// A local process on a native PTY
+    Local {
+        master: Box<dyn MasterPty + Send>,
+        writer: Box<dyn Write + Send>,
+        killer: Box<dyn ChildKiller + Send + Sync>,
+    },
+    /// A process in a container, attached over the exec websocket
+    Remote {
+        stdin: mpsc::UnboundedSender<Vec<u8>>,
+        resize: Option<futures::channel::mpsc::Sender<TerminalSize>>,
+        cancel: Arc<Notify>,
+    },
+}
+
+/// Tracks every PTY session opened by the renderer
+pub struct PtyManager {
+    sessions: Arc<Mutex<HashMap<String, PtySession>>>,
+    next_id: AtomicUsize,
+}
+
+impl Default for PtyManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PtyManager {
+    pub fn new() -> Self {
+        PtyManager {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicUsize::new(1),
+        }
+    }
+
+    fn next_session_id(&self) -> String {
+        format!("pty-{}", self.next_id.fetch_add(1, Ordering::SeqCst))
+    }
+
+    /// Spawn a new session owned by the given window
+    pub fn spawn(
+        &self,
+        app: &AppHandle,
+        window_label: &str,
+        options: SpawnOptions,
+    ) -> Result<SpawnedSession, String> {
+        let session_id = self.next_session_id();
+        let sink = Arc::new(WindowSink {
+            app: app.clone(),
+            window_label: window_label.to_string(),
+            session_id: session_id.clone(),
+        });
+        self.spawn_local(sink, window_label, session_id, options)
+    }
+
+    /// Spawn a process on a new native PTY, sending its output to `sink`
+    fn spawn_local(
+        &self,
+        sink: Arc<dyn PtySink>,
+        window_label: &str,
+        session_id: String,
+        options: SpawnOptions,
+    ) -> Result<SpawnedSession, String> {
+        let size = PtySize {
+            rows: options.rows.unwrap_or(DEFAULT_ROWS),
+            cols: options.cols.unwrap_or(DEFAULT_COLS),
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+
+        let pair = native_pty_system()
+            .openpty(size)
+            .map_err(|e| format!("Failed to open PTY: {}", e))?;
+
+        let mut cmd = match options.shell {
+            Some(ref shell) => {
+                let mut cmd = CommandBuilder::new(shell);
+                cmd.args(&options.args);
+                cmd
+            }
+            // The default program is the user's shell, started as a login shell
+            None => CommandBuilder::new_default_prog(),
+        };
+
+        if let Some(ref cwd) = options.cwd {
+            cmd.cwd(cwd);
+        }
+        cmd.env("TERM", "xterm-256color");
+        cmd.env("COLORTERM", "truecolor");
+        for (key, value) in &options.env {
+            cmd.env(key, value);
+        }
+
+        let mut child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|e| format!("Failed to spawn shell: {}", e))?;
+
+        // The slave end must be closed in this process, otherwise the reader
+        // never observes EOF once the shell exits
+        drop(pair.slave);
+
+        let reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| format!("Failed to open PTY reader: {}", e))?;
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| format!("Failed to open PTY writer: {}", e))?;
+
+        let pid = child.process_id();
+
+        self.sessions.lock().unwrap().insert(
+            session_id.clone(),
+            PtySession {
+                window_label: window_label.to_string(),
+                io: SessionIo::Local {
+                    master: pair.master,
+                    writer,
+                    killer: child.clone_killer(),
+                },
+            },
+        );
+
+        let sessions = Arc::clone(&self.sessions);
+        let id = session_id.clone();
+        std::thread::Builder::new()
+            .name(format!("{}-reader", session_id))
+            .spawn(move || {
+                pump_output(sink.as_ref(), &id, reader);
+
+                let exit_code = child.wait().ok().map(|status| status.exit_code());
+                finish_session(sink.as_ref(), &sessions, id, exit_code);
+            })
+            .map_err(|e| format!("Failed to start PTY reader thread: {}", e))?;
+
+        info!("Spawned PTY session {} (pid {:?})", session_id, pid);
+        Ok(SpawnedSession { session_id, pid })
+    }
+
+    /// Serve a process attached in a container as a session of the given
+    /// window
+    pub fn attach_remote(
+        &self,
+        app: &AppHandle,
+        window_label: &str,
+        mut process: AttachedProcess,
+        cols: Option<u16>,
+        rows: Option<u16>,
+    ) -> Result<SpawnedSession, String> {
+        let stdout = process
+            .stdout()
+            .ok_or_else(|| "Attached process has no stdout".to_string())?;
+        let stdin_writer = process
+            .stdin()
+            .ok_or_else(|| "Attached process has no stdin".to_string())?;
+        let status = process
+            .take_status()
+            .ok_or_else(|| "Attached process has no status channel".to_string())?;
+
+        let mut resize = process.terminal_size();
+        if let Some(ref mut resize) = resize {
+            let size = TerminalSize {
+                width: cols.unwrap_or(DEFAULT_COLS),
+                height: rows.unwrap_or(DEFAULT_ROWS),
+            };
+            if let Err(e) = resize.try_send(size) {
+                debug!("Failed to send initial terminal size: {}", e);
+            }
+        }
+
+        let session_id = self.next_session_id();
+        let (stdin, stdin_rx) = mpsc::unbounded_channel();
+        let cancel = Arc::new(Notify::new());
+
+        self.sessions.lock().unwrap().insert(
+            session_id.clone(),
+            PtySession {
+                window_label: window_label.to_string(),
+                io: SessionIo::Remote {
+                    stdin,
+                    resize,
+                    cancel: Arc::clone(&cancel),
+                },
+            },
+        );
+
+        let sink = WindowSink {
+            app: app.clone(),
+            window_label: window_label.to_string(),
+            session_id: session_id.clone(),
+        };
+        let sessions = Arc::clone(&self.sessions);
+        let id = session_id.clone();
+        tokio::spawn(async move {
+            let killed = pump_remote(&sink, &id, stdout, stdin_writer, stdin_rx, &cancel).await;
+
+            // The final status trails the end of the output; none arrives if
+            // the session was killed or the connection was cut
+            let status = if killed {
+                None
+            } else {
+                tokio::time::timeout(REMOTE_STATUS_TIMEOUT, status)
+                    .await
+                    .ok()
+                    .flatten()
+            };
+            process.abort();
+
+            let exit_code = status.as_ref().and_then(exit_code);
+            finish_session(&sink, &sessions, id, exit_code);
+        });
+
+        info!("Attached PTY session {} to a container", session_id);
+        Ok(SpawnedSession {
+            session_id,
+            pid: None,
+        })
+    }
+
+    /// Write user input to a session
+    pub fn write(&self, session_id: &str, data: &str) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| format!("Unknown PTY session: {}", session_id))?;
+
+        match session.io {
+            SessionIo::Local { ref mut writer, .. } => writer
+                .write_all(data.as_bytes())
+                .and_then(|_| writer.flush())
+                .map_err(|e| format!("Failed to write to PTY: {}", e)),
+            SessionIo::Remote { ref stdin, .. } => stdin
+                .send(data.as_bytes().to_vec())
+                .map_err(|_| format!("PTY session {} has exited", session_id)),
+        }
+    }
+
+    /// Resize a session's terminal
+    pub fn resize(&self, session_id: &str, cols: u16, rows: u16) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| format!("Unknown PTY session: {}", session_id))?;
+
+        match session.io {
+            SessionIo::Local { ref master, .. } => master
+                .resize(PtySize {
+                    rows,
+                    cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                })
+                .map_err(|e| format!("Failed to resize PTY: {}", e)),
+            SessionIo::Remote {
+                resize: Some(ref mut resize),
+                ..
+            } => resize
+                .try_send(TerminalSize {
+                    width: cols,
+                    height: rows,
+                })
+                .map_err(|e| format!("Failed to resize PTY: {}", e)),
+            SessionIo::Remote { resize: None, .. } => Ok(()),
+        }
+    }
+
+    /// Kill the process behind a session
+    ///
+    /// The session itself is removed by its reader thread once the process
+    /// has exited and the final `pty-exit` event has been emitted.
+    pub fn kill(&self, session_id: &str) -> Result<(), String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .ok_or_else(|| format!("Unknown PTY session: {}", session_id))?;
+
+        session
+            .io
+            .kill()
+            .map_err(|e| format!("Failed to kill PTY process: {}", e))
+    }
+
+    /// Kill every session owned by a window that is going away
+    pub fn close_window(&self, window_label: &str) {
+        let mut sessions = self.sessions.lock().unwrap();
+        for (id, session) in sessions.iter_mut() {
+            if session.window_label == window_label {
+                debug!(
+                    "Killing PTY session {} of closed window {}",
+                    id, window_label
+                );
+                if let Err(e) = session.io.kill() {
+                    error!("Failed to kill PTY session {}: {}", id, e);
+                }
+            }
+        }
+    }
+}
+
+impl SessionIo {
+    fn kill(&mut self) -> std::io::Result<()> {
+        match self {
+            SessionIo::Local { killer, .. } => killer.kill(),
+            SessionIo::Remote { cancel, .. } => {
+                cancel.notify_one();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Deregister a session whose process is gone and tell its window
+fn finish_session(
+    sink: &dyn PtySink,
+    sessions: &Mutex<HashMap<String, PtySession>>,
+    session_id: String,
+    exit_code: Option<u32>,
+) {
+    sessions.lock().unwrap().remove(&session_id);
+    debug!("PTY session {} exited with {:?}", session_id, exit_code);
+    sink.exit(exit_code);
+}
+
+/// Send a chunk of terminal output with credentials masked; `None`
+/// releases the output held back waiting for the next chunk
+fn emit_data(sink: &dyn PtySink, data: Option<&str>, redaction: &mut OutputRedaction) {
+    let vault = sink.vault();
+    let data = match data {
+        Some(data) => vault.redact_output(data, redaction),
+        None => vault.flush_output(redaction),
+    };
+    if !data.is_empty() {
+        sink.data(data);
+    }
+}
+
+/// Forward everything the PTY produces to the sink until EOF
+fn pump_output(sink: &dyn PtySink, session_id: &str, mut reader: Box<dyn Read + Send>) {
+    // Read on a thread of its own, so that output held back for redaction
+    // can be released while the PTY is quiet
+    let (chunks, received) = std::sync::mpsc::channel::<Vec<u8>>();
+    let spawned = std::thread::Builder::new()
+        .name(format!("{}-read", session_id))
+        .spawn(move || {
+            let mut buf = [0u8; 8192];
+            loop {
+                let n = match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    // EIO is how most platforms report that the slave side has closed
+                    Err(_) => break,
+                };
+                if chunks.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        error!("Failed to start PTY read thread: {}", e);
+        return;
+    }
+
+    let mut pending = Vec::new();
+    let mut redaction = OutputRedaction::default();
+    loop {
+        let chunk = match redaction.is_holding() {
+            true => match received.recv_timeout(OUTPUT_FLUSH_DELAY) {
+                Ok(chunk) => chunk,
+                Err(RecvTimeoutError::Timeout) => {
+                    emit_data(sink, None, &mut redaction);
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            false => match received.recv() {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            },
+        };
+
+        let data = decode_utf8_chunk(&mut pending, &chunk);
+        emit_data(sink, Some(&data), &mut redaction);
+    }
+    emit_data(sink, None, &mut redaction);
+}
+
+/// Shuttle input and output of an attached container process until it
+/// exits or the session is killed; returns whether it was killed
+async fn pump_remote(
+    sink: &dyn PtySink,
+    session_id: &str,
+    mut stdout: impl tokio::io::AsyncRead + Unpin,
+    mut stdin: impl tokio::io::AsyncWrite + Unpin,
+    mut input: mpsc::UnboundedReceiver<Vec<u8>>,
+    cancel: &Notify,
+) -> bool {
+    let mut buf = [0u8; 8192];
+    let mut pending = Vec::new();
+    let mut redaction = OutputRedaction::default();
+
+    loop {
+        tokio::select! {
+            read = stdout.read(&mut buf) => {
+                let n = match read {
+                    Ok(0) | Err(_) => {
+                        emit_data(sink, None, &mut redaction);
+                        return false;
+                    }
+                    Ok(n) => n,
+                };
+                let data = decode_utf8_chunk(&mut pending, &buf[..n]);
+                emit_data(sink, Some(&data), &mut redaction);
+            }
+            _ = tokio::time::sleep(OUTPUT_FLUSH_DELAY), if redaction.is_holding() => {
+                emit_data(sink, None, &mut redaction);
+            }
+            Some(data) = input.recv() => {
+                if let Err(e) = stdin.write_all(&data).await {
+                    debug!("Failed to write to PTY session {}: {}", session_id, e);
+                    return false;
+                }
+            }
+            _ = cancel.notified() => {
+                debug!("Detaching PTY session {}", session_id);
+                return true;
+            }
+        }
+    }
+}
+
+/// Decode a chunk of terminal output, carrying an incomplete trailing UTF-8
+/// sequence over to the next chunk instead of mangling it
+pub fn decode_utf8_chunk(pending: &mut Vec<u8>, chunk: &[u8]) -> String {
+    pending.extend_from_slice(chunk);
+
+    let mut out = String::with_capacity(pending.len());
+    let mut rest: &[u8] = pending;
+
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                out.push_str(valid);
+                rest = &[];
+                break;
+            }
+            Err(e) => {
+                let (valid, after) = rest.split_at(e.valid_up_to());
+                out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+
+                match e.error_len() {
+                    Some(len) => {
+                        out.push(char::REPLACEMENT_CHARACTER);
+                        rest = &after[len..];
+                    }
+                    // Truncated sequence at the end: keep it for next time
+                    None => {
+                        rest = after;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    *pending = rest.to_vec();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[derive(Default)]
+    struct Collected {
+        vault: SecretVault,
+        data: Mutex<String>,
+        exit: Mutex<Option<Option<u32>>>,
+    }
+
+    impl PtySink for Collected {
+        fn vault(&self) -> &SecretVault {
+            &self.vault
+        }
+
+        fn data(&self, data: String) {
+            self.data.lock().unwrap().push_str(&data);
+        }
+
+        fn exit(&self, exit_code: Option<u32>) {
+            *self.exit.lock().unwrap() = Some(exit_code);
+        }
+    }
+
+    impl Collected {
+        /// Wait for the session to exit, returning its exit code
+        fn wait(&self) -> Option<u32> {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                if let Some(exit_code) = *self.exit.lock().unwrap() {
+                    return exit_code;
+                }
+                assert!(Instant::now() < deadline, "PTY session did not exit");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    /// Run `script` with `/bin/sh` in a new session
+    fn spawn(manager: &PtyManager, script: &str) -> (Arc<Collected>, String) {
+        let sink = Arc::new(Collected::default());
+        let options = SpawnOptions {
+            shell: Some("/bin/sh".to_string()),
+            args: vec!["-c".to_string(), script.to_string()],
+            ..Default::default()
+        };
+        let session_id = manager.next_session_id();
+        let spawned = manager
+            .spawn_local(sink.clone(), "main", session_id, options)
+            .unwrap();
+        assert!(spawned.pid.is_some());
+        (sink, spawned.session_id)
+    }
+
+    #[test]
+    fn test_spawn_delivers_output() {
+        let manager = PtyManager::new();
+        let (sink, session_id) = spawn(&manager, "echo hi");
+
+        assert_eq!(sink.wait(), Some(0));
+        assert_eq!(sink.data.lock().unwrap().trim_end(), "hi");
+        // The session is gone once its exit has been sent
+        assert!(manager.write(&session_id, "x").is_err());
+    }
+
+    #[test]
+    fn test_write_reaches_process() {
+        let manager = PtyManager::new();
+        let (sink, session_id) = spawn(&manager, "read line; echo \"got $line\"");
+
+        manager.write(&session_id, "ping\n").unwrap();
+        assert_eq!(sink.wait(), Some(0));
+        assert!(sink.data.lock().unwrap().contains("got ping"));
+    }
+
+    #[test]
+    fn test_kill() {
+        let manager = PtyManager::new();
+        let (sink, session_id) = spawn(&manager, "sleep 30");
+
+        manager.kill(&session_id).unwrap();
+        let started = Instant::now();
+        assert_ne!(sink.wait(), Some(0));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(manager.kill(&session_id).is_err());
+    }
+
+    #[test]
+    fn test_decode_utf8_chunk_split_sequence() {
+        let mut pending = Vec::new();
+        let bytes = "héllo".as_bytes();
+
+        // Split in the middle of the two-byte 'é'
+        assert_eq!(decode_utf8_chunk(&mut pending, &bytes[..2]), "h");
+        assert_eq!(pending.len(), 1);
+        assert_eq!(decode_utf8_chunk(&mut pending, &bytes[2..]), "éllo");
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_decode_utf8_chunk_invalid_bytes() {
+        let mut pending = Vec::new();
+        assert_eq!(decode_utf8_chunk(&mut pending, b"a\xffb"), "a\u{FFFD}b");
+        assert!(pending.is_empty());
+    }
+}
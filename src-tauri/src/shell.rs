@@ -0,0 +1,494 @@
+// Copyright 2025 The Kubernetes Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Non-interactive command execution for plugins
+//!
+//! A job runs a single argv in its own process group. Output is streamed to
+//! the owning window as `shell-output` events while the job is running, and
+//! the `exec` call resolves with the exit status once the process is gone.
+//! Cancelling a job sends SIGTERM to the whole group, followed by SIGKILL if
+//! it has not exited after a short grace period.
+
+use crate::pty::decode_utf8_chunk;
+use crate::redact::{OutputRedaction, SecretVault, OUTPUT_FLUSH_DELAY};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, EventTarget, Manager};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{Child, Command};
+use tokio::sync::Notify;
+
+/// Event carrying a chunk of job output
+pub const SHELL_OUTPUT_EVENT: &str = "shell-output";
+
+/// Time a job gets to exit after SIGTERM before it is SIGKILLed
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+/// Time to wait for buffered output after the process has exited; a
+/// backgrounded grandchild may keep the pipes open indefinitely
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Options accepted by the `exec` method
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecOptions {
+    /// Program and arguments; the program is looked up in `PATH`
+    pub argv: Vec<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Extra environment variables layered over the app's environment
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Caller-chosen job id, so output events can be matched before `exec`
+    /// resolves; generated if absent
+    #[serde(default)]
+    pub job_id: Option<String>,
+}
+
+/// Final status of a job
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecResult {
+    pub job_id: String,
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the process, if any
+    pub signal: Option<i32>,
+    pub timed_out: bool,
+    pub cancelled: bool,
+}
+
+/// Which stream an output chunk came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Payload of `shell-output` events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShellOutputPayload {
+    job_id: String,
+    stream: OutputStream,
+    data: String,
+}
+
+/// Where a job's output goes
+trait OutputSink: Send + Sync + 'static {
+    /// Vault masking credentials in the output
+    fn vault(&self) -> &SecretVault;
+    fn emit(&self, stream: OutputStream, data: String);
+}
+
+/// Sends a job's output to the window that started it
+struct WindowSink {
+    app: AppHandle,
+    window_label: String,
+    job_id: String,
+}
+
+impl OutputSink for WindowSink {
+    fn vault(&self) -> &SecretVault {
+        self.app.state::<SecretVault>().inner()
+    }
+
+    fn emit(&self, stream: OutputStream, data: String) {
+        if let Err(e) = self.app.emit_to(
+            EventTarget::webview_window(self.window_label.as_str()),
+            SHELL_OUTPUT_EVENT,
+            ShellOutputPayload {
+                job_id: self.job_id.clone(),
+                stream,
+                data,
+            },
+        ) {
+            error!("Failed to emit shell-output event: {}", e);
+        }
+    }
+}
+
+/// Bookkeeping for a running job
+struct Job {
+    window_label: String,
+    cancel: Arc<Notify>,
+}
+
+/// Tracks running shell jobs
+pub struct JobManager {
+    jobs: Mutex<HashMap<String, Job>>,
+    next_id: AtomicUsize,
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        JobManager {
+            jobs: Mutex::new(HashMap::new()),
+            next_id: AtomicUsize::new(1),
+        }
+    }
+
+    /// Run a job to completion, streaming its output to the given window
+    pub async fn exec(
+        &self,
+        app: &AppHandle,
+        window_label: &str,
+        options: ExecOptions,
+    ) -> Result<ExecResult, String> {
+        let (program, args) = options
+            .argv
+            .split_first()
+            .ok_or_else(|| "exec requires a non-empty argv".to_string())?;
+
+        let job_id = options
+            .job_id
+            .clone()
+            .unwrap_or_else(|| format!("job-{}", self.next_id.fetch_add(1, Ordering::SeqCst)));
+
+        let cancel = Arc::new(Notify::new());
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            if jobs.contains_key(&job_id) {
+                return Err(format!("Job {} is already running", job_id));
+            }
+            jobs.insert(
+                job_id.clone(),
+                Job {
+                    window_label: window_label.to_string(),
+                    cancel: Arc::clone(&cancel),
+                },
+            );
+        }
+
+        let sink = Arc::new(WindowSink {
+            app: app.clone(),
+            window_label: window_label.to_string(),
+            job_id: job_id.clone(),
+        });
+        let result = run_job(sink, &job_id, program, args, &options, &cancel).await;
+
+        self.jobs.lock().unwrap().remove(&job_id);
+        result
+    }
+
+    /// Request cancellation of a running job
+    pub fn cancel(&self, job_id: &str) -> Result<(), String> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs
+            .get(job_id)
+            .ok_or_else(|| format!("Unknown job: {}", job_id))?;
+
+        info!("Cancelling job {}", job_id);
+        job.cancel.notify_one();
+        Ok(())
+    }
+
+    /// Cancel every job owned by a window that is going away
+    pub fn close_window(&self, window_label: &str) {
+        let jobs = self.jobs.lock().unwrap();
+        for (id, job) in jobs.iter() {
+            if job.window_label == window_label {
+                debug!("Cancelling job {} of closed window {}", id, window_label);
+                job.cancel.notify_one();
+            }
+        }
+    }
+}
+
+/// Spawn the process and supervise it until it exits
+async fn run_job(
+    sink: Arc<dyn OutputSink>,
+    job_id: &str,
+    program: &str,
+    args: &[String],
+    options: &ExecOptions,
+    cancel: &Notify,
+) -> Result<ExecResult, String> {
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .envs(&options.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(ref cwd) = options.cwd {
+        cmd.current_dir(cwd);
+    }
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to spawn {}: {}", program, e))?;
+    debug!(
+        "Job {} started: {:?} (pid {:?})",
+        job_id,
+        options.argv,
+        child.id()
+    );
+
+    let stdout = child
+        .stdout
+        .take()
+        .map(|stdout| tokio::spawn(pump_output(Arc::clone(&sink), OutputStream::Stdout, stdout)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|stderr| tokio::spawn(pump_output(Arc::clone(&sink), OutputStream::Stderr, stderr)));
+
+    let deadline = async {
+        match options.timeout_ms {
+            Some(ms) => tokio::time::sleep(Duration::from_millis(ms)).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    let mut timed_out = false;
+    let mut cancelled = false;
+    let status = tokio::select! {
+        status = child.wait() => status,
+        _ = deadline => {
+            timed_out = true;
+            terminate(&mut child).await
+        }
+        _ = cancel.notified() => {
+            cancelled = true;
+            terminate(&mut child).await
+        }
+    }
+    .map_err(|e| format!("Failed to wait for {}: {}", program, e))?;
+
+    // Stop forwarding output once the job is over, even if something it
+    // left behind still writes to the pipes
+    for mut task in [stdout, stderr].into_iter().flatten() {
+        if tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, &mut task)
+            .await
+            .is_err()
+        {
+            debug!("Job {} left its output pipes open", job_id);
+            task.abort();
+        }
+    }
+
+    let result = ExecResult {
+        job_id: job_id.to_string(),
+        exit_code: status.code(),
+        signal: exit_signal(&status),
+        timed_out,
+        cancelled,
+    };
+    info!("Job {} finished: {:?}", job_id, result);
+    Ok(result)
+}
+
+/// Terminate a job's process group, escalating to SIGKILL after the grace period
+async fn terminate(child: &mut Child) -> std::io::Result<ExitStatus> {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        signal_group(pid, libc::SIGTERM);
+        if let Ok(status) = tokio::time::timeout(KILL_GRACE_PERIOD, child.wait()).await {
+            return status;
+        }
+        signal_group(pid, libc::SIGKILL);
+    }
+
+    match child.try_wait()? {
+        Some(status) => Ok(status),
+        None => {
+            child.start_kill()?;
+            child.wait().await
+        }
+    }
+}
+
+/// Send a signal to the process group led by `pid`
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) {
+    // The job was spawned with process_group(0), so its pid is the pgid
+    let rc = unsafe { libc::kill(-(pid as libc::pid_t), signal) };
+    if rc != 0 {
+        debug!(
+            "Failed to signal process group {}: {}",
+            pid,
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Forward one of the job's output streams to its sink until EOF
+async fn pump_output<R: AsyncRead + Unpin>(
+    sink: Arc<dyn OutputSink>,
+    stream: OutputStream,
+    mut reader: R,
+) {
+    let mut buf = [0u8; 8192];
+    let mut pending = Vec::new();
+    let mut redaction = OutputRedaction::default();
+
+    loop {
+        let read = match redaction.is_holding() {
+            true => tokio::time::timeout(OUTPUT_FLUSH_DELAY, reader.read(&mut buf)).await,
+            false => Ok(reader.read(&mut buf).await),
+        };
+        let vault = sink.vault();
+        let (data, eof) = match read {
+            Ok(Ok(0)) | Ok(Err(_)) => (vault.flush_output(&mut redaction), true),
+            Ok(Ok(n)) => {
+                let data = decode_utf8_chunk(&mut pending, &buf[..n]);
+                (vault.redact_output(&data, &mut redaction), false)
+            }
+            // Quiet for a while: release what was held back
+            Err(_) => (vault.flush_output(&mut redaction), false),
+        };
+
+        if !data.is_empty() {
+            sink.emit(stream, data);
+        }
+        if eof {
+            break;
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// Collects a job's output
+    #[derive(Default)]
+    struct Collected {
+        vault: SecretVault,
+        output: Mutex<Vec<(OutputStream, String)>>,
+    }
+
+    impl OutputSink for Collected {
+        fn vault(&self) -> &SecretVault {
+            &self.vault
+        }
+
+        fn emit(&self, stream: OutputStream, data: String) {
+            self.output.lock().unwrap().push((stream, data));
+        }
+    }
+
+    impl Collected {
+        fn text(&self, stream: OutputStream) -> String {
+            let output = self.output.lock().unwrap();
+            output
+                .iter()
+                .filter(|(s, _)| *s == stream)
+                .map(|(_, data)| data.as_str())
+                .collect()
+        }
+    }
+
+    async fn sh(
+        script: &str,
+        timeout_ms: Option<u64>,
+        cancel: &Notify,
+    ) -> (ExecResult, Arc<Collected>) {
+        let sink = Arc::new(Collected::default());
+        let options = ExecOptions {
+            argv: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            cwd: None,
+            env: HashMap::new(),
+            timeout_ms,
+            job_id: None,
+        };
+        let result = run_job(
+            sink.clone(),
+            "job-1",
+            "sh",
+            &options.argv[1..],
+            &options,
+            cancel,
+        )
+        .await
+        .unwrap();
+        (result, sink)
+    }
+
+    #[tokio::test]
+    async fn test_exit_code_and_output() {
+        let (result, output) = sh("echo out; echo err >&2; exit 3", None, &Notify::new()).await;
+        assert_eq!(result.exit_code, Some(3));
+        assert_eq!(result.signal, None);
+        assert!(!result.timed_out && !result.cancelled);
+        assert_eq!(output.text(OutputStream::Stdout), "out\n");
+        assert_eq!(output.text(OutputStream::Stderr), "err\n");
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let started = Instant::now();
+        let (result, _) = sh("sleep 30", Some(100), &Notify::new()).await;
+        assert!(result.timed_out);
+        assert_eq!(result.signal, Some(libc::SIGTERM));
+        assert!(started.elapsed() < KILL_GRACE_PERIOD);
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let cancel = Arc::new(Notify::new());
+        let cancel_soon = || {
+            let cancel = Arc::clone(&cancel);
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                cancel.notify_one();
+            })
+        };
+
+        cancel_soon();
+        let (result, _) = sh("sleep 30", None, &cancel).await;
+        assert!(result.cancelled);
+        assert_eq!(result.signal, Some(libc::SIGTERM));
+
+        // A job ignoring SIGTERM is killed with its whole process group
+        cancel_soon();
+        let started = Instant::now();
+        let (result, output) = sh("trap '' TERM; sleep 30 & echo $!; wait", None, &cancel).await;
+        assert!(result.cancelled);
+        assert_eq!(result.signal, Some(libc::SIGKILL));
+        assert!(started.elapsed() >= KILL_GRACE_PERIOD);
+
+        let grandchild: libc::pid_t = output.text(OutputStream::Stdout).trim().parse().unwrap();
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", grandchild));
+        assert!(stat.map_or(true, |stat| stat.contains(") Z ")));
+    }
+}